@@ -0,0 +1,522 @@
+//! Every function takes its operands, plus the incoming flags where
+//! the instruction keeps some of them, and returns the result and
+//! the new value of F.
+
+pub const FLAG_Z: u8 = 0b1000_0000;
+pub const FLAG_N: u8 = 0b0100_0000;
+pub const FLAG_H: u8 = 0b0010_0000;
+pub const FLAG_C: u8 = 0b0001_0000;
+
+fn flag(set: bool, mask: u8) -> u8 {
+    if set {
+        mask
+    } else {
+        0x00
+    }
+}
+
+fn zero(v: u8) -> u8 {
+    flag(v == 0x00, FLAG_Z)
+}
+
+/// ADD A,b
+///
+/// # Examples
+/// ```
+/// use rust_boy::alu::{add, FLAG_C, FLAG_H, FLAG_Z};
+/// assert_eq!(add(0x3A, 0xC6), (0x00, FLAG_Z | FLAG_H | FLAG_C));
+/// assert_eq!(add(0x01, 0x02), (0x03, 0x00));
+/// ```
+pub fn add(a: u8, b: u8) -> (u8, u8) {
+    adc(a, b, false)
+}
+
+/// ADC A,b
+pub fn adc(a: u8, b: u8, carry: bool) -> (u8, u8) {
+    let c = carry as u8;
+    let sum = a as u16 + b as u16 + c as u16;
+    let v = sum as u8;
+    let h = (a & 0x0F) + (b & 0x0F) + c > 0x0F;
+
+    (v, zero(v) | flag(h, FLAG_H) | flag(sum > 0xFF, FLAG_C))
+}
+
+/// SUB A,b
+///
+/// # Examples
+/// ```
+/// use rust_boy::alu::{sub, FLAG_C, FLAG_H, FLAG_N};
+/// assert_eq!(sub(0x3E, 0x0F), (0x2F, FLAG_N | FLAG_H));
+/// assert_eq!(sub(0x3E, 0x40), (0xFE, FLAG_N | FLAG_C));
+/// ```
+pub fn sub(a: u8, b: u8) -> (u8, u8) {
+    sbc(a, b, false)
+}
+
+/// SBC A,b
+pub fn sbc(a: u8, b: u8, carry: bool) -> (u8, u8) {
+    let c = carry as u8;
+    let v = a.wrapping_sub(b).wrapping_sub(c);
+    let h = (a & 0x0F) < (b & 0x0F) + c;
+    let borrow = (a as u16) < b as u16 + c as u16;
+
+    (v, zero(v) | FLAG_N | flag(h, FLAG_H) | flag(borrow, FLAG_C))
+}
+
+/// CP A,b is SUB without storing the result, so only
+/// the flags are returned.
+pub fn cp(a: u8, b: u8) -> u8 {
+    sub(a, b).1
+}
+
+/// AND A,b
+pub fn and(a: u8, b: u8) -> (u8, u8) {
+    let v = a & b;
+    (v, zero(v) | FLAG_H)
+}
+
+/// OR A,b
+pub fn or(a: u8, b: u8) -> (u8, u8) {
+    let v = a | b;
+    (v, zero(v))
+}
+
+/// XOR A,b
+pub fn xor(a: u8, b: u8) -> (u8, u8) {
+    let v = a ^ b;
+    (v, zero(v))
+}
+
+/// INC r, C is left unchanged.
+///
+/// # Examples
+/// ```
+/// use rust_boy::alu::{inc, FLAG_C, FLAG_H, FLAG_Z};
+/// assert_eq!(inc(0xFF, FLAG_C), (0x00, FLAG_Z | FLAG_H | FLAG_C));
+/// ```
+pub fn inc(v: u8, flags: u8) -> (u8, u8) {
+    let r = v.wrapping_add(1);
+    (r, zero(r) | flag(v & 0x0F == 0x0F, FLAG_H) | flags & FLAG_C)
+}
+
+/// DEC r, C is left unchanged.
+pub fn dec(v: u8, flags: u8) -> (u8, u8) {
+    let r = v.wrapping_sub(1);
+    (
+        r,
+        zero(r) | FLAG_N | flag(v & 0x0F == 0x00, FLAG_H) | flags & FLAG_C,
+    )
+}
+
+/// The result of a rotate or shift and the bit shifted out.
+fn shifted(v: u8, carry: bool) -> (u8, u8) {
+    (v, zero(v) | flag(carry, FLAG_C))
+}
+
+/// RLC r
+pub fn rlc(v: u8) -> (u8, u8) {
+    shifted(v.rotate_left(1), v & 0x80 != 0)
+}
+
+/// RRC r
+pub fn rrc(v: u8) -> (u8, u8) {
+    shifted(v.rotate_right(1), v & 0x01 != 0)
+}
+
+/// RL r, through the carry.
+pub fn rl(v: u8, carry: bool) -> (u8, u8) {
+    shifted((v << 1) | carry as u8, v & 0x80 != 0)
+}
+
+/// RR r, through the carry.
+pub fn rr(v: u8, carry: bool) -> (u8, u8) {
+    shifted((v >> 1) | (carry as u8) << 7, v & 0x01 != 0)
+}
+
+/// SLA r
+pub fn sla(v: u8) -> (u8, u8) {
+    shifted(v << 1, v & 0x80 != 0)
+}
+
+/// SRA r, bit 7 is kept.
+pub fn sra(v: u8) -> (u8, u8) {
+    shifted((v >> 1) | (v & 0x80), v & 0x01 != 0)
+}
+
+/// SRL r
+pub fn srl(v: u8) -> (u8, u8) {
+    shifted(v >> 1, v & 0x01 != 0)
+}
+
+/// SWAP r
+pub fn swap(v: u8) -> (u8, u8) {
+    shifted(v.rotate_left(4), false)
+}
+
+/// RLCA. The accumulator rotates always clear Z.
+///
+/// # Examples
+/// ```
+/// use rust_boy::alu::{rlca, FLAG_C};
+/// assert_eq!(rlca(0x80), (0x01, FLAG_C));
+/// ```
+pub fn rlca(a: u8) -> (u8, u8) {
+    let (v, f) = rlc(a);
+    (v, f & !FLAG_Z)
+}
+
+/// RRCA
+pub fn rrca(a: u8) -> (u8, u8) {
+    let (v, f) = rrc(a);
+    (v, f & !FLAG_Z)
+}
+
+/// RLA
+pub fn rla(a: u8, carry: bool) -> (u8, u8) {
+    let (v, f) = rl(a, carry);
+    (v, f & !FLAG_Z)
+}
+
+/// RRA
+pub fn rra(a: u8, carry: bool) -> (u8, u8) {
+    let (v, f) = rr(a, carry);
+    (v, f & !FLAG_Z)
+}
+
+/// DAA, adjust A to BCD after an addition or subtraction.
+/// N is kept, H is cleared.
+///
+/// # Examples
+/// ```
+/// use rust_boy::alu::{add, daa, FLAG_C, FLAG_Z};
+/// // 0x45 + 0x38 = 0x83 in BCD.
+/// let (a, f) = add(0x45, 0x38);
+/// assert_eq!(daa(a, f), (0x83, 0x00));
+/// // 0x99 + 0x01 = 0x00, carry 1.
+/// let (a, f) = add(0x99, 0x01);
+/// assert_eq!(daa(a, f), (0x00, FLAG_Z | FLAG_C));
+/// ```
+pub fn daa(a: u8, flags: u8) -> (u8, u8) {
+    let mut v = a;
+    let mut carry = flags & FLAG_C != 0;
+    let half = flags & FLAG_H != 0;
+
+    if flags & FLAG_N != 0 {
+        if carry {
+            v = v.wrapping_sub(0x60);
+        }
+        if half {
+            v = v.wrapping_sub(0x06);
+        }
+    } else {
+        if carry || v > 0x99 {
+            v = v.wrapping_add(0x60);
+            carry = true;
+        }
+        if half || v & 0x0F > 0x09 {
+            v = v.wrapping_add(0x06);
+        }
+    }
+
+    (v, zero(v) | flags & FLAG_N | flag(carry, FLAG_C))
+}
+
+/// CPL, Z and C are kept.
+pub fn cpl(a: u8, flags: u8) -> (u8, u8) {
+    (!a, flags & (FLAG_Z | FLAG_C) | FLAG_N | FLAG_H)
+}
+
+/// SCF, Z is kept.
+pub fn scf(flags: u8) -> u8 {
+    flags & FLAG_Z | FLAG_C
+}
+
+/// CCF, Z is kept.
+pub fn ccf(flags: u8) -> u8 {
+    flags & FLAG_Z | (flags ^ FLAG_C) & FLAG_C
+}
+
+/// BIT n,r only produces flags, C is kept.
+pub fn bit(n: u8, v: u8, flags: u8) -> u8 {
+    flag(v & (1 << n) == 0x00, FLAG_Z) | FLAG_H | flags & FLAG_C
+}
+
+/// ADD HL,rr. H is the carry out of bit 11, Z is kept.
+///
+/// # Examples
+/// ```
+/// use rust_boy::alu::{add16, FLAG_H, FLAG_Z};
+/// assert_eq!(add16(0x0FFF, 0x0001, FLAG_Z), (0x1000, FLAG_Z | FLAG_H));
+/// ```
+pub fn add16(hl: u16, rr: u16, flags: u8) -> (u16, u8) {
+    let (v, carry) = hl.overflowing_add(rr);
+    let h = (hl & 0x0FFF) + (rr & 0x0FFF) > 0x0FFF;
+
+    (v, flags & FLAG_Z | flag(h, FLAG_H) | flag(carry, FLAG_C))
+}
+
+/// SP plus the signed offset e, for ADD SP,e and LD HL,SP+e.
+/// H and C come from the unsigned addition of the low byte,
+/// Z and N are cleared.
+///
+/// # Examples
+/// ```
+/// use rust_boy::alu::{add_sp, FLAG_C, FLAG_H};
+/// assert_eq!(add_sp(0xFFF8, 0x08), (0x0000, FLAG_H | FLAG_C));
+/// assert_eq!(add_sp(0x0000, 0xFF), (0xFFFF, 0x00));
+/// ```
+pub fn add_sp(sp: u16, e: u8) -> (u16, u8) {
+    let (_, f) = add(sp as u8, e);
+    (sp.wrapping_add(e as i8 as u16), f & (FLAG_H | FLAG_C))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Bit serial full adder. Returns the sum and the carry out
+    /// of every bit, least significant first.
+    fn ripple_add(a: u16, b: u16, carry: bool, bits: u32) -> (u16, Vec<bool>) {
+        let mut sum = 0;
+        let mut c = carry;
+        let mut carries = Vec::new();
+        for i in 0..bits {
+            let x = a >> i & 1 == 1;
+            let y = b >> i & 1 == 1;
+            if x ^ y ^ c {
+                sum |= 1 << i;
+            }
+            c = (x && y) || (c && (x ^ y));
+            carries.push(c);
+        }
+        (sum, carries)
+    }
+
+    /// Bit serial full subtractor. Returns the difference and
+    /// the borrow out of every bit, least significant first.
+    fn ripple_sub(a: u8, b: u8, borrow: bool) -> (u8, Vec<bool>) {
+        let mut diff = 0;
+        let mut w = borrow;
+        let mut borrows = Vec::new();
+        for i in 0..8 {
+            let x = a >> i & 1 == 1;
+            let y = b >> i & 1 == 1;
+            if x ^ y ^ w {
+                diff |= 1 << i;
+            }
+            w = (!x && y) || (w && !(x ^ y));
+            borrows.push(w);
+        }
+        (diff, borrows)
+    }
+
+    fn flags(z: bool, n: bool, h: bool, c: bool) -> u8 {
+        let mut f = 0x00;
+        for (set, bit) in [(z, 7), (n, 6), (h, 5), (c, 4)] {
+            if set {
+                f |= 1 << bit;
+            }
+        }
+        f
+    }
+
+    fn pairs() -> impl Iterator<Item = (u8, u8, bool)> {
+        (0..=0xFF).flat_map(|a| (0..=0xFF).flat_map(move |b| [false, true].map(move |c| (a, b, c))))
+    }
+
+    #[test]
+    fn add_adc_exhaustive() {
+        for (a, b, c) in pairs() {
+            let (sum, carries) = ripple_add(a as u16, b as u16, c, 8);
+            let expected = (sum as u8, flags(sum == 0, false, carries[3], carries[7]));
+            assert_eq!(adc(a, b, c), expected, "ADC {:02X} {:02X} {}", a, b, c);
+            if !c {
+                assert_eq!(add(a, b), expected, "ADD {:02X} {:02X}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn sub_sbc_cp_exhaustive() {
+        for (a, b, c) in pairs() {
+            let (diff, borrows) = ripple_sub(a, b, c);
+            let expected = (diff, flags(diff == 0, true, borrows[3], borrows[7]));
+            assert_eq!(sbc(a, b, c), expected, "SBC {:02X} {:02X} {}", a, b, c);
+            if !c {
+                assert_eq!(sub(a, b), expected, "SUB {:02X} {:02X}", a, b);
+                assert_eq!(cp(a, b), expected.1, "CP {:02X} {:02X}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn logic_exhaustive() {
+        for (a, b, _) in pairs().filter(|p| !p.2) {
+            let bits = |op: fn(bool, bool) -> bool| {
+                (0..8).fold(0u8, |acc, i| {
+                    acc | ((op(a >> i & 1 == 1, b >> i & 1 == 1) as u8) << i)
+                })
+            };
+            let v = bits(|x, y| x && y);
+            assert_eq!(and(a, b), (v, flags(v == 0, false, true, false)));
+            let v = bits(|x, y| x || y);
+            assert_eq!(or(a, b), (v, flags(v == 0, false, false, false)));
+            let v = bits(|x, y| x != y);
+            assert_eq!(xor(a, b), (v, flags(v == 0, false, false, false)));
+        }
+    }
+
+    #[test]
+    fn inc_dec_exhaustive() {
+        for v in 0..=0xFF {
+            for c in [false, true] {
+                let f_in = flags(true, true, true, c);
+
+                let (sum, carries) = ripple_add(v as u16, 1, false, 8);
+                let expected = (sum as u8, flags(sum == 0, false, carries[3], c));
+                assert_eq!(inc(v, f_in), expected, "INC {:02X}", v);
+
+                let (diff, borrows) = ripple_sub(v, 1, false);
+                let expected = (diff, flags(diff == 0, true, borrows[3], c));
+                assert_eq!(dec(v, f_in), expected, "DEC {:02X}", v);
+            }
+        }
+    }
+
+    #[test]
+    fn rotates_exhaustive() {
+        for v in 0..=0xFF {
+            for c in [false, true] {
+                let mut bits = [false; 8];
+                for (i, b) in bits.iter_mut().enumerate() {
+                    *b = v >> i & 1 == 1;
+                }
+                let pack = |bits: [bool; 8]| {
+                    bits.iter()
+                        .enumerate()
+                        .fold(0u8, |acc, (i, b)| acc | ((*b as u8) << i))
+                };
+                let shift_left = |fill: bool| {
+                    let mut out = [fill; 8];
+                    out[1..].copy_from_slice(&bits[..7]);
+                    pack(out)
+                };
+                let shift_right = |fill: bool| {
+                    let mut out = [fill; 8];
+                    out[..7].copy_from_slice(&bits[1..]);
+                    pack(out)
+                };
+                let expect = |r: u8, carry: bool| (r, flags(r == 0, false, false, carry));
+
+                assert_eq!(rlc(v), expect(shift_left(bits[7]), bits[7]));
+                assert_eq!(rrc(v), expect(shift_right(bits[0]), bits[0]));
+                assert_eq!(rl(v, c), expect(shift_left(c), bits[7]));
+                assert_eq!(rr(v, c), expect(shift_right(c), bits[0]));
+                assert_eq!(sla(v), expect(shift_left(false), bits[7]));
+                assert_eq!(sra(v), expect(shift_right(bits[7]), bits[0]));
+                assert_eq!(srl(v), expect(shift_right(false), bits[0]));
+
+                let (r, _) = rlc(v);
+                assert_eq!(rlca(v), (r, flags(false, false, false, bits[7])));
+                let (r, _) = rrc(v);
+                assert_eq!(rrca(v), (r, flags(false, false, false, bits[0])));
+                let (r, _) = rl(v, c);
+                assert_eq!(rla(v, c), (r, flags(false, false, false, bits[7])));
+                let (r, _) = rr(v, c);
+                assert_eq!(rra(v, c), (r, flags(false, false, false, bits[0])));
+
+                let nibbles = (v & 0x0F) << 4 | v >> 4;
+                assert_eq!(swap(v), expect(nibbles, false));
+            }
+        }
+    }
+
+    #[test]
+    fn daa_exhaustive() {
+        for a in 0..=0xFFu8 {
+            for f_in in (0x00..=0xF0).step_by(0x10) {
+                let n = f_in & FLAG_N != 0;
+                let h = f_in & FLAG_H != 0;
+                let c = f_in & FLAG_C != 0;
+                let (hi, lo) = (a >> 4, a & 0x0F);
+
+                // Correction table, split by nibble.
+                let low_fix = if n { h } else { h || lo > 9 };
+                let high_fix = if n {
+                    c
+                } else {
+                    c || hi > 9 || (hi == 9 && lo > 9)
+                };
+                let correction = (low_fix as u8) * 0x06 + (high_fix as u8) * 0x60;
+                let r = if n {
+                    a.wrapping_sub(correction)
+                } else {
+                    a.wrapping_add(correction)
+                };
+
+                assert_eq!(
+                    daa(a, f_in),
+                    (r, flags(r == 0, n, false, high_fix)),
+                    "DAA {:02X} {:02X}",
+                    a,
+                    f_in
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn flag_ops_exhaustive() {
+        for f_in in (0x00..=0xF0).step_by(0x10) {
+            let z = f_in & FLAG_Z != 0;
+            let c = f_in & FLAG_C != 0;
+            assert_eq!(scf(f_in), flags(z, false, false, true));
+            assert_eq!(ccf(f_in), flags(z, false, false, !c));
+            for a in 0..=0xFF {
+                assert_eq!(cpl(a, f_in), (0xFF - a, flags(z, true, true, c)));
+                for n in 0..8 {
+                    let set = a >> n & 1 == 1;
+                    assert_eq!(bit(n, a, f_in), flags(!set, false, true, c));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn add16_sampled() {
+        let values = (0..=0xFFFF)
+            .step_by(0xF1)
+            .chain([0x0FFF, 0x1000, 0x7FFF, 0x8000, 0xFFFF]);
+        for hl in values.clone() {
+            for rr in values.clone() {
+                for z in [false, true] {
+                    let (sum, carries) = ripple_add(hl, rr, false, 16);
+                    assert_eq!(
+                        add16(hl, rr, flags(z, true, false, false)),
+                        (sum, flags(z, false, carries[11], carries[15])),
+                        "ADD HL {:04X} {:04X}",
+                        hl,
+                        rr
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn add_sp_exhaustive_offsets() {
+        for sp in (0..=0xFFFF).step_by(0x0101).chain([0xFFFF, 0x00FF, 0xFF00]) {
+            for e in 0..=0xFF {
+                let (_, carries) = ripple_add(sp & 0xFF, e as u16, false, 8);
+                let expected = (sp as i32 + e as i8 as i32).rem_euclid(0x10000) as u16;
+                assert_eq!(
+                    add_sp(sp, e),
+                    (expected, flags(false, false, carries[3], carries[7])),
+                    "SP {:04X} {:02X}",
+                    sp,
+                    e
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,125 @@
+use std::io;
+
+/// Everything the CPU can see on the other side of its address bus.
+///
+/// The CPU calls tick once per M-cycle, before the access made on
+/// that cycle, so a device sees reads and writes on the cycle they
+/// happen.
+pub trait Bus {
+    /// Read a byte as the CPU would.
+    fn read(&mut self, addr: u16) -> Result<u8, io::Error>;
+
+    fn write(&mut self, addr: u16, v: u8) -> Result<(), io::Error>;
+
+    /// Advance everything on the bus by one M-cycle.
+    fn tick(&mut self);
+
+    /// Read a byte without side effects, for decoding ahead of
+    /// the CPU and for debuggers.
+    fn peek(&self, addr: u16) -> Result<u8, io::Error>;
+
+    /// The ROM bank mapped at addr, or None if addr is not in ROM.
+    fn rom_bank(&self, _addr: u16) -> Option<u16> {
+        None
+    }
+
+    /// Called by STOP. Switches CGB speed if KEY1 asks for it, the
+    /// bus is then stalled until the clock is running again.
+    fn speed_switch(&mut self) -> bool {
+        false
+    }
+
+    /// True while a DMA holds the CPU off the bus, or a speed switch
+    /// has its clock stopped. It idles until the bus lets go.
+    fn stalled(&self) -> bool {
+        false
+    }
+}
+
+/// 64KB of plain RAM with no devices, every address reads back
+/// what was last written and tick does nothing.
+///
+/// # Examples
+/// ```
+/// use rust_boy::bus::Bus;
+/// let mut mem = [0u8; 0x10000];
+/// mem.write(0xFF04, 0xAB).unwrap();
+/// mem.tick();
+/// assert_eq!(mem.read(0xFF04).unwrap(), 0xAB);
+/// ```
+impl Bus for [u8; 0x10000] {
+    fn read(&mut self, addr: u16) -> Result<u8, io::Error> {
+        Ok(self[addr as usize])
+    }
+
+    fn write(&mut self, addr: u16, v: u8) -> Result<(), io::Error> {
+        self[addr as usize] = v;
+        Ok(())
+    }
+
+    fn tick(&mut self) {}
+
+    fn peek(&self, addr: u16) -> Result<u8, io::Error> {
+        Ok(self[addr as usize])
+    }
+}
+
+impl<B: Bus + ?Sized> Bus for &mut B {
+    fn read(&mut self, addr: u16) -> Result<u8, io::Error> {
+        (**self).read(addr)
+    }
+
+    fn write(&mut self, addr: u16, v: u8) -> Result<(), io::Error> {
+        (**self).write(addr, v)
+    }
+
+    fn tick(&mut self) {
+        (**self).tick()
+    }
+
+    fn peek(&self, addr: u16) -> Result<u8, io::Error> {
+        (**self).peek(addr)
+    }
+
+    fn rom_bank(&self, addr: u16) -> Option<u16> {
+        (**self).rom_bank(addr)
+    }
+
+    fn speed_switch(&mut self) -> bool {
+        (**self).speed_switch()
+    }
+
+    fn stalled(&self) -> bool {
+        (**self).stalled()
+    }
+}
+
+impl<B: Bus + ?Sized> Bus for Box<B> {
+    fn read(&mut self, addr: u16) -> Result<u8, io::Error> {
+        (**self).read(addr)
+    }
+
+    fn write(&mut self, addr: u16, v: u8) -> Result<(), io::Error> {
+        (**self).write(addr, v)
+    }
+
+    fn tick(&mut self) {
+        (**self).tick()
+    }
+
+    fn peek(&self, addr: u16) -> Result<u8, io::Error> {
+        (**self).peek(addr)
+    }
+
+    fn rom_bank(&self, addr: u16) -> Option<u16> {
+        (**self).rom_bank(addr)
+    }
+
+    fn speed_switch(&mut self) -> bool {
+        (**self).speed_switch()
+    }
+
+    fn stalled(&self) -> bool {
+        (**self).stalled()
+    }
+}
@@ -1,5 +1,59 @@
-use crate::memorymap::MemoryMap;
+use crate::alu;
+use crate::bus::Bus;
+use crate::instruction::{decode, decode_cb, Instruction, Operand};
+use crate::interrupts::{InterruptHandler, InterruptKind, IE_ADDR, IF_ADDR};
+use crate::memorymap::P1_ADDR;
+use crate::model::HardwareModel;
 use crate::registers::*;
+use crate::timer::DIV_ADDR;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io;
+
+#[derive(Debug)]
+pub enum CpuError {
+    /// One of the eleven opcodes that hang the hardware. The CPU
+    /// is left locked up and will not execute anything else.
+    IllegalOpcode {
+        opcode: u8,
+        addr: u16,
+        bank: Option<u16>,
+    },
+    /// A memory access failed.
+    Bus { addr: u16, source: io::Error },
+}
+
+impl fmt::Display for CpuError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CpuError::IllegalOpcode { opcode, addr, bank } => match bank {
+                Some(bank) => write!(
+                    f,
+                    "Illegal opcode {:#04X} at {:02X}:{:04X}, CPU locked up",
+                    opcode, bank, addr
+                ),
+                None => write!(
+                    f,
+                    "Illegal opcode {:#04X} at {:#06X}, CPU locked up",
+                    opcode, addr
+                ),
+            },
+            CpuError::Bus { addr, source } => {
+                write!(f, "Bus error at {:#06X}: {}", addr, source)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CpuError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CpuError::Bus { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
 
 pub struct CpuDataDebug {
     pub a: u8,
@@ -18,57 +72,206 @@ pub struct CpuDataDebug {
     pub mem3: u8,
 }
 
-pub struct Cpu<'m> {
+/// Everything needed to resume the CPU between instructions.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CpuState {
+    pub a: u8,
+    pub f: u8,
+    pub b: u8,
+    pub c: u8,
+    pub d: u8,
+    pub e: u8,
+    pub h: u8,
+    pub l: u8,
+    pub sp: u16,
+    pub pc: u16,
+    pub ime: bool,
+    /// EI has run, IME is set after the next instruction.
+    pub ime_scheduled: bool,
+    pub halted: bool,
+    pub stopped: bool,
+    /// The next opcode fetch will not increment PC.
+    pub halt_bug: bool,
+    /// An illegal opcode has hung the CPU.
+    pub locked: bool,
+}
+
+pub struct Cpu<B: Bus> {
     reg: Registers,
     sp: u16,
     pc: u16,
-    mem: &'m MemoryMap,
+    mem: B,
+    interrupts: InterruptHandler,
+    /// Interrupt master enable.
+    ime: bool,
+    /// Set by EI, IME is enabled after the following instruction.
+    ime_scheduled: bool,
+    /// Waiting in HALT for an interrupt.
+    halted: bool,
+    /// Waiting in STOP for a joypad press.
+    stopped: bool,
+    /// HALT was executed with IME clear and an interrupt pending,
+    /// so the next opcode fetch does not increment PC.
+    halt_bug: bool,
+    /// Hung by an illegal opcode, only a reset recovers.
+    locked: bool,
+    /// First error raised while executing the current instruction.
+    fault: Option<CpuError>,
+    /// M-cycles the rest of the system has been ticked.
+    mcycles: u64,
 }
 
-impl<'m> Cpu<'m> {
-    pub fn load(mem: &'m MemoryMap) -> Self {
-        // Load the state of the gameboy after
-        // loading the boot rom.
-        mem.write_byte(0xFF05, 0x00).unwrap();
-        mem.write_byte(0xFF06, 0x00).unwrap();
-        mem.write_byte(0xFF07, 0x00).unwrap();
-        mem.write_byte(0xFF10, 0x80).unwrap();
-        mem.write_byte(0xFF11, 0xBF).unwrap();
-        mem.write_byte(0xFF12, 0xF3).unwrap();
-        mem.write_byte(0xFF14, 0xBF).unwrap();
-        mem.write_byte(0xFF16, 0x3F).unwrap();
-        mem.write_byte(0xFF17, 0x00).unwrap();
-        mem.write_byte(0xFF19, 0xBF).unwrap();
-        mem.write_byte(0xFF1A, 0x7F).unwrap();
-        mem.write_byte(0xFF1B, 0xFF).unwrap();
-        mem.write_byte(0xFF1C, 0x9F).unwrap();
-        mem.write_byte(0xFF1E, 0xBF).unwrap();
-        mem.write_byte(0xFF20, 0xFF).unwrap();
-        mem.write_byte(0xFF21, 0x00).unwrap();
-        mem.write_byte(0xFF22, 0x00).unwrap();
-        mem.write_byte(0xFF23, 0xBF).unwrap();
-        mem.write_byte(0xFF24, 0x77).unwrap();
-        mem.write_byte(0xFF25, 0xF3).unwrap();
-        mem.write_byte(0xFF26, 0xF1).unwrap();
-        mem.write_byte(0xFF40, 0x91).unwrap();
-        mem.write_byte(0xFF42, 0x00).unwrap();
-        mem.write_byte(0xFF43, 0x00).unwrap();
-        mem.write_byte(0xFF44, 0x90).unwrap();
-        mem.write_byte(0xFF45, 0x00).unwrap();
-        mem.write_byte(0xFF47, 0xFC).unwrap();
-        mem.write_byte(0xFF48, 0xFF).unwrap();
-        mem.write_byte(0xFF49, 0xFF).unwrap();
-        mem.write_byte(0xFF4A, 0x00).unwrap();
-        mem.write_byte(0xFF4B, 0x00).unwrap();
-        mem.write_byte(0xFFFF, 0x00).unwrap();
+impl<B: Bus> Cpu<B> {
+    /// A CPU with every register cleared and nothing written to
+    /// memory, for tests and tools that set up their own state.
+    pub fn new(mem: B) -> Self {
         Self {
-            reg: Registers::dmg0(),
+            reg: Registers::default(),
+            sp: 0x0000,
+            pc: 0x0000,
+            mem,
+            interrupts: InterruptHandler::new(),
+            ime: false,
+            ime_scheduled: false,
+            halted: false,
+            stopped: false,
+            halt_bug: false,
+            locked: false,
+            fault: None,
+            mcycles: 0,
+        }
+    }
+
+    /// A CPU at power on, to run a boot ROM mapped at 0x0000.
+    pub fn boot(mem: B) -> Self {
+        Self::new(mem)
+    }
+
+    /// A CPU in the state a DMG boot ROM leaves, ready to run the
+    /// cartridge from 0x0100.
+    pub fn load(mem: B) -> Self {
+        Self::load_model(mem, HardwareModel::DMG)
+    }
+
+    /// A CPU, and the I/O registers, in the state model's boot ROM
+    /// leaves. A failed register write is reported by the first step.
+    pub fn load_model(mem: B, model: HardwareModel) -> Self {
+        let mut cpu = Self {
+            reg: model.registers(),
             sp: 0xFFFE,
             pc: 0x0100,
-            mem,
+            ..Self::new(mem)
+        };
+        for (addr, v) in model.io_registers() {
+            cpu.poke(addr, v);
+        }
+        cpu
+    }
+
+    pub fn registers(&self) -> &Registers {
+        &self.reg
+    }
+
+    pub fn registers_mut(&mut self) -> &mut Registers {
+        &mut self.reg
+    }
+
+    pub fn pc(&self) -> u16 {
+        self.pc
+    }
+
+    pub fn set_pc(&mut self, pc: u16) {
+        self.pc = pc;
+    }
+
+    pub fn sp(&self) -> u16 {
+        self.sp
+    }
+
+    pub fn set_sp(&mut self, sp: u16) {
+        self.sp = sp;
+    }
+
+    /// Interrupt master enable.
+    pub fn ime(&self) -> bool {
+        self.ime
+    }
+
+    pub fn set_ime(&mut self, ime: bool) {
+        self.ime = ime;
+        self.ime_scheduled = false;
+    }
+
+    /// Waiting in HALT for an interrupt.
+    pub fn halted(&self) -> bool {
+        self.halted
+    }
+
+    pub fn set_halted(&mut self, halted: bool) {
+        self.halted = halted;
+    }
+
+    /// Waiting in STOP for a joypad press.
+    pub fn stopped(&self) -> bool {
+        self.stopped
+    }
+
+    pub fn set_stopped(&mut self, stopped: bool) {
+        self.stopped = stopped;
+    }
+
+    pub fn state(&self) -> CpuState {
+        CpuState {
+            a: self.reg.a,
+            f: self.reg.f,
+            b: self.reg.b,
+            c: self.reg.c,
+            d: self.reg.d,
+            e: self.reg.e,
+            h: self.reg.h,
+            l: self.reg.l,
+            sp: self.sp,
+            pc: self.pc,
+            ime: self.ime,
+            ime_scheduled: self.ime_scheduled,
+            halted: self.halted,
+            stopped: self.stopped,
+            halt_bug: self.halt_bug,
+            locked: self.locked,
         }
     }
 
+    /// Load a state taken with state.
+    pub fn set_state(&mut self, state: &CpuState) {
+        self.reg = Registers {
+            a: state.a,
+            f: state.f,
+            b: state.b,
+            c: state.c,
+            d: state.d,
+            e: state.e,
+            h: state.h,
+            l: state.l,
+        };
+        self.sp = state.sp;
+        self.pc = state.pc;
+        self.ime = state.ime;
+        self.ime_scheduled = state.ime_scheduled;
+        self.halted = state.halted;
+        self.stopped = state.stopped;
+        self.halt_bug = state.halt_bug;
+        self.locked = state.locked;
+    }
+
+    pub fn bus(&self) -> &B {
+        &self.mem
+    }
+
+    pub fn bus_mut(&mut self) -> &mut B {
+        &mut self.mem
+    }
+
     pub fn get_cpu_data_debug(&self) -> CpuDataDebug {
         CpuDataDebug {
             a: self.reg.a,
@@ -81,423 +284,424 @@ impl<'m> Cpu<'m> {
             l: self.reg.l,
             sp: self.sp,
             pc: self.pc,
-            mem0: self.mem.read_byte(self.pc + 0).unwrap(),
-            mem1: self.mem.read_byte(self.pc + 1).unwrap(),
-            mem2: self.mem.read_byte(self.pc + 2).unwrap(),
-            mem3: self.mem.read_byte(self.pc + 3).unwrap(),
-        }
-    }
-
-    pub fn step(&mut self) -> u8 {
-        let opcode = self.mem.read_byte(self.pc).unwrap();
-        // println!("{:X}",opcode);
-        match opcode {
-            0x00 => self.nop(),                 //tested
-            0x0F => self.rrca(),                //tested
-            0xC9 => self.ret(),                 //tested
-            0xC0 => self.ret_cc(FlagCond::NZ),  //tested
-            0xC8 => self.ret_cc(FlagCond::Z),   //tested
-            0xD0 => self.ret_cc(FlagCond::NC),  //tested
-            0xD8 => self.ret_cc(FlagCond::C),   //tested
-            0xCD => self.call(),                //tested
-            0xC4 => self.call_cc(FlagCond::NZ), //tested
-            0xCC => self.call_cc(FlagCond::Z),  //tested
-            0xD4 => self.call_cc(FlagCond::NC), //tested
-            0xDC => self.call_cc(FlagCond::C),  //tested
-            0xC1 => self.pop_bc(),
-            0xD1 => self.pop_de(),
-            0xE1 => self.pop_hl(),
-            0xF1 => self.pop_af(),
-            0xC5 => self.push_bc(),
-            0xD5 => self.push_de(),
-            0xE5 => self.push_hl(),
-            0xF5 => self.push_af(),
-            0x3D => self.dec_r(StdReg::A), //tested
-            0x05 => self.dec_r(StdReg::B), //tested
-            0x0D => self.dec_r(StdReg::C), //tested
-            0x15 => self.dec_r(StdReg::D), //tested
-            0x1D => self.dec_r(StdReg::E), //tested
-            0x25 => self.dec_r(StdReg::H), //tested
-            0x2D => self.dec_r(StdReg::L), //tested
-            0xB8 => self.cp_a_r(StdReg::A),
-            0xB9 => self.cp_a_r(StdReg::B),
-            0xBA => self.cp_a_r(StdReg::C),
-            0xBB => self.cp_a_r(StdReg::D),
-            0xBC => self.cp_a_r(StdReg::E),
-            0xBD => self.cp_a_r(StdReg::H),
-            0xBF => self.cp_a_r(StdReg::L),
-            0xFE => self.cp_a_n(),
-            0x18 => self.jr(),
-            0x20 => self.jr_cond(FlagCond::NZ),
-            0x28 => self.jr_cond(FlagCond::Z),
-            0x30 => self.jr_cond(FlagCond::NC),
-            0x38 => self.jr_cond(FlagCond::C),
-            0x01 => self.ld_n_nn(LoadRegnnn::BC), //tested
-            0x11 => self.ld_n_nn(LoadRegnnn::DE), //tested
-            0x21 => self.ld_n_nn(LoadRegnnn::HL), //tested
-            0x31 => self.ld_n_nn(LoadRegnnn::SP), //tested
-            0x7F => self.ld_a_n(LoadReg::A),      //tested
-            0x78 => self.ld_a_n(LoadReg::B),      //tested
-            0x79 => self.ld_a_n(LoadReg::C),      //tested
-            0x7A => self.ld_a_n(LoadReg::D),      //tested
-            0x7B => self.ld_a_n(LoadReg::E),      //tested
-            0x7C => self.ld_a_n(LoadReg::H),      //tested
-            0x7D => self.ld_a_n(LoadReg::L),      //tested
-            0x0A => self.ld_a_n(LoadReg::MemBC),  //tested
-            0x1A => self.ld_a_n(LoadReg::MemDE),  //tested
-            0x7E => self.ld_a_n(LoadReg::MemHL),  //tested
-            0xFA => self.ld_a_n(LoadReg::MemNN),  //tested
-            0x3E => self.ld_a_n(LoadReg::N),      //tested
-            0x47 => self.ld_n_a(LoadReg::B),      //tested
-            0x4F => self.ld_n_a(LoadReg::C),      //tested
-            0x57 => self.ld_n_a(LoadReg::D),      //tested
-            0x5F => self.ld_n_a(LoadReg::E),      //tested
-            0x67 => self.ld_n_a(LoadReg::H),      //tested
-            0x6F => self.ld_n_a(LoadReg::L),      //tested
-            0x02 => self.ld_n_a(LoadReg::MemBC),  //tested
-            0x12 => self.ld_n_a(LoadReg::MemDE),  //tested
-            0x77 => self.ld_n_a(LoadReg::MemHL),  //tested
-            0xEA => self.ld_n_a(LoadReg::MemNN),  //tested
-            0xE0 => self.ld_ff00_a(),
-            0xF0 => self.ld_a_ff00(),
-            0x40 => self.ld_r_r(StdReg::B, StdReg::B), //tested
-            0x41 => self.ld_r_r(StdReg::B, StdReg::C), //tested
-            0x42 => self.ld_r_r(StdReg::B, StdReg::D), //tested
-            0x43 => self.ld_r_r(StdReg::B, StdReg::E), //tested
-            0x44 => self.ld_r_r(StdReg::B, StdReg::H), //tested
-            0x45 => self.ld_r_r(StdReg::B, StdReg::L), //tested
-            0x46 => self.ld_r_r(StdReg::B, StdReg::HL), //tested
-            0x48 => self.ld_r_r(StdReg::C, StdReg::B), //tested
-            0x49 => self.ld_r_r(StdReg::C, StdReg::C), //tested
-            0x4A => self.ld_r_r(StdReg::C, StdReg::D), //tested
-            0x4B => self.ld_r_r(StdReg::C, StdReg::E), //tested
-            0x4C => self.ld_r_r(StdReg::C, StdReg::H), //tested
-            0x4D => self.ld_r_r(StdReg::C, StdReg::L), //tested
-            0x4E => self.ld_r_r(StdReg::C, StdReg::HL), //tested
-            0x50 => self.ld_r_r(StdReg::D, StdReg::B), //tested
-            0x51 => self.ld_r_r(StdReg::D, StdReg::C), //tested
-            0x52 => self.ld_r_r(StdReg::D, StdReg::D), //tested
-            0x53 => self.ld_r_r(StdReg::D, StdReg::E), //tested
-            0x54 => self.ld_r_r(StdReg::D, StdReg::H), //tested
-            0x55 => self.ld_r_r(StdReg::D, StdReg::L), //tested
-            0x56 => self.ld_r_r(StdReg::D, StdReg::HL), //tested
-            0x58 => self.ld_r_r(StdReg::E, StdReg::B), //tested
-            0x59 => self.ld_r_r(StdReg::E, StdReg::C), //tested
-            0x5A => self.ld_r_r(StdReg::E, StdReg::D), //tested
-            0x5B => self.ld_r_r(StdReg::E, StdReg::E), //tested
-            0x5C => self.ld_r_r(StdReg::E, StdReg::H), //tested
-            0x5D => self.ld_r_r(StdReg::E, StdReg::L), //tested
-            0x5E => self.ld_r_r(StdReg::E, StdReg::HL), //tested
-            0x60 => self.ld_r_r(StdReg::H, StdReg::B), //tested
-            0x61 => self.ld_r_r(StdReg::H, StdReg::C), //tested
-            0x62 => self.ld_r_r(StdReg::H, StdReg::D), //tested
-            0x63 => self.ld_r_r(StdReg::H, StdReg::E), //tested
-            0x64 => self.ld_r_r(StdReg::H, StdReg::H), //tested
-            0x65 => self.ld_r_r(StdReg::H, StdReg::L), //tested
-            0x66 => self.ld_r_r(StdReg::H, StdReg::HL), //tested
-            0x68 => self.ld_r_r(StdReg::L, StdReg::B), //tested
-            0x69 => self.ld_r_r(StdReg::L, StdReg::C), //tested
-            0x6A => self.ld_r_r(StdReg::L, StdReg::D), //tested
-            0x6B => self.ld_r_r(StdReg::L, StdReg::E), //tested
-            0x6C => self.ld_r_r(StdReg::L, StdReg::H), //tested
-            0x6D => self.ld_r_r(StdReg::L, StdReg::L), //tested
-            0x6E => self.ld_r_r(StdReg::L, StdReg::HL), //tested
-            0x70 => self.ld_r_r(StdReg::HL, StdReg::B), //tested
-            0x71 => self.ld_r_r(StdReg::HL, StdReg::C), //tested
-            0x72 => self.ld_r_r(StdReg::HL, StdReg::D), //tested
-            0x73 => self.ld_r_r(StdReg::HL, StdReg::E), //tested
-            0x74 => self.ld_r_r(StdReg::HL, StdReg::H), //tested
-            0x75 => self.ld_r_r(StdReg::HL, StdReg::L), //tested
-            0x36 => self.ld_r_r(StdReg::HL, StdReg::HL), //tested
-            0x2A => self.ldi_a_memhl(),
-            // 0x06 => self.ld_b_n(),
-            0x0E => self.ld_r_n(StdReg::C),
-            0x06 => self.ld_r_n(StdReg::B),
-            // 0x0E => self.ld_r_n(StdReg::C),
-            0x16 => self.ld_r_n(StdReg::D),
-            0x1E => self.ld_r_n(StdReg::E),
-            0x26 => self.ld_r_n(StdReg::H),
-            0x2E => self.ld_r_n(StdReg::L),
-            0x22 => self.ld_mem_hl_a_inc(), // tested (!SHEET)
-            0x32 => self.ld_mem_hl_a_dec(), // tested (!SHEET)
-            0xC3 => self.jp_nn(),
-            0xA7 => self.and_a_r(LoadReg::A),
-            0xA0 => self.and_a_r(LoadReg::B),
-            0xA1 => self.and_a_r(LoadReg::C),
-            0xA2 => self.and_a_r(LoadReg::D),
-            0xA3 => self.and_a_r(LoadReg::E),
-            0xA4 => self.and_a_r(LoadReg::H),
-            0xA5 => self.and_a_r(LoadReg::L),
-            0xA6 => self.and_a_r(LoadReg::MemHL),
-            0xE6 => self.and_a_r(LoadReg::N),
-            0xB1 => self.or_a_r(StdReg::C),
-            0xB2 => self.or_a_r(StdReg::D),
-            0xB3 => self.or_a_r(StdReg::E),
-            0xB4 => self.or_a_r(StdReg::H),
-            0xB5 => self.or_a_r(StdReg::L),
-            0xB7 => self.or_a_r(StdReg::A),
-            0xB6 => self.or_a_r(StdReg::HL),
-            0xAF => self.xor_r(StdRegN::A),
-            0xA8 => self.xor_r(StdRegN::B),
-            0xA9 => self.xor_r(StdRegN::C),
-            0xAA => self.xor_r(StdRegN::D),
-            0xAB => self.xor_r(StdRegN::E),
-            0xAC => self.xor_r(StdRegN::H),
-            0xAD => self.xor_r(StdRegN::L),
-            0xAE => self.xor_r(StdRegN::HL),
-            0xEE => self.xor_r(StdRegN::N),
-            0xC7 => self.rst_00(),        //tested
-            0xCF => self.rst_08(),        //tested
-            0xD7 => self.rst_10(),        //tested
-            0xDF => self.rst_18(),        //tested
-            0xE7 => self.rst_20(),        //tested
-            0xEF => self.rst_28(),        //tested
-            0xF7 => self.rst_30(),        //tested
-            0xFF => self.rst_38(),        //tested
-            0x1F => self.rr_n(StdReg::A), //tested
-            0x90 => self.sub_a_r(StdRegN::B),
-            0x91 => self.sub_a_r(StdRegN::C),
-            0x92 => self.sub_a_r(StdRegN::D),
-            0x93 => self.sub_a_r(StdRegN::E),
-            0x94 => self.sub_a_r(StdRegN::H),
-            0x95 => self.sub_a_r(StdRegN::L),
-            0x96 => self.sub_a_r(StdRegN::HL), //TODO
-            0x97 => self.sub_a_r(StdRegN::A),
-            0xD6 => self.sub_a_r(StdRegN::N),
-            0x80 => self.add_a_r(StdRegN::B),
-            0x81 => self.add_a_r(StdRegN::C),
-            0x82 => self.add_a_r(StdRegN::D),
-            0x83 => self.add_a_r(StdRegN::E),
-            0x84 => self.add_a_r(StdRegN::H),
-            0x85 => self.add_a_r(StdRegN::L),
-            0x86 => self.add_a_r(StdRegN::HL), //TODO
-            0x87 => self.add_a_r(StdRegN::A),
-            0xC6 => self.add_a_r(StdRegN::N),
-            0x8F => self.adc_a_n(StdRegN::A),
-            0x88 => self.adc_a_n(StdRegN::B),
-            0x89 => self.adc_a_n(StdRegN::C),
-            0x8A => self.adc_a_n(StdRegN::D),
-            0x8B => self.adc_a_n(StdRegN::E),
-            0x8C => self.adc_a_n(StdRegN::H),
-            0x8D => self.adc_a_n(StdRegN::L),
-            0x8E => self.adc_a_n(StdRegN::HL),
-            0xCE => self.adc_a_n(StdRegN::N),
-            0x3C => self.inc_reg(IncDecReg::A), //tested
-            0x04 => self.inc_reg(IncDecReg::B), //tested
-            0x0C => self.inc_reg(IncDecReg::C), //tested
-            0x14 => self.inc_reg(IncDecReg::D), //tested
-            0x1C => self.inc_reg(IncDecReg::E), //tested
-            0x24 => self.inc_reg(IncDecReg::H), //tested
-            0x2C => self.inc_reg(IncDecReg::L), //tested
-            0x23 => self.inc_reg(IncDecReg::HL),
-            0x03 => self.inc_reg(IncDecReg::BC),
-            0x13 => self.inc_reg(IncDecReg::DE),
-            0x33 => self.inc_reg(IncDecReg::SP),
-            0x34 => self.inc_reg(IncDecReg::MemHL),
-            0xF3 => self.di(),
-            0xFB => self.ei(),
-            0xCB => {
-                self.pc = self.pc.wrapping_add(1);
-                let opcode = self.mem.read_byte(self.pc).unwrap();
-                // println!("{:X}",opcode);
-                match opcode {
-                    0x1F => self.rr_n(StdReg::A), //tested
-                    0x18 => self.rr_n(StdReg::B), //tested
-                    0x19 => self.rr_n(StdReg::C), //tested
-                    0x1A => self.rr_n(StdReg::D), //tested
-                    0x1B => self.rr_n(StdReg::E), //tested
-                    0x1C => self.rr_n(StdReg::H), //tested
-                    0x1D => self.rr_n(StdReg::L), //tested
-                    0x1E => self.rr_n(StdReg::HL),
-                    0x38 => self.srl(StdReg::B),
-                    _ => {
-                        println!("Opcode not implmented : CB {:#04X}", opcode);
-                        std::process::abort()
-                    }
-                }
+            mem0: self.peek(self.pc),
+            mem1: self.peek(self.pc.wrapping_add(1)),
+            mem2: self.peek(self.pc.wrapping_add(2)),
+            mem3: self.peek(self.pc.wrapping_add(3)),
+        }
+    }
+
+    /// Execute one instruction, or service one interrupt, and
+    /// return the number of cycles it took.
+    pub fn step(&mut self) -> Result<u8, CpuError> {
+        let start = self.mcycles;
+        self.step_inner();
+        let cycles = ((self.mcycles - start) * 4) as u8;
+
+        match self.fault.take() {
+            Some(err) => Err(err),
+            None => Ok(cycles),
+        }
+    }
+
+    fn step_inner(&mut self) {
+        if self.locked {
+            self.tick();
+            return;
+        }
+
+        if self.mem.stalled() {
+            self.tick();
+            return;
+        }
+
+        if self.stopped {
+            // The system clock is halted, nothing else runs either.
+            if !self.joypad_pressed() {
+                return;
             }
-            _ => {
-                println!("Opcode not implmented : {:#04X}", opcode);
-                std::process::abort()
+            self.stopped = false;
+        }
+
+        if self.halted {
+            if self.pending_interrupt().is_none() {
+                self.tick();
+                return;
             }
+            self.halted = false;
+        }
+
+        if self.service_interrupts() {
+            return;
+        }
+
+        let enable_ime = self.ime_scheduled;
+        self.execute();
+        // EI takes effect after the instruction following it,
+        // unless that instruction was a DI.
+        if enable_ime && self.ime_scheduled {
+            self.ime = true;
+            self.ime_scheduled = false;
         }
     }
 
-    fn nop(&mut self) -> u8 {
-        self.pc = self.pc.wrapping_add(1);
-        4
+    /// True once an illegal opcode has hung the CPU.
+    pub fn is_locked(&self) -> bool {
+        self.locked
     }
 
-    fn di(&mut self) -> u8 {
-        self.pc = self.pc.wrapping_add(1);
-        self.mem.write_byte(0xFFFF, 0x00).unwrap();
-        4
+    /// Decode the instruction at PC without executing it.
+    pub fn next_instruction(&self) -> Instruction {
+        decode(&[
+            self.peek(self.pc),
+            self.peek(self.pc.wrapping_add(1)),
+            self.peek(self.pc.wrapping_add(2)),
+        ])
     }
 
-    fn ei(&mut self) -> u8 {
-        self.pc = self.pc.wrapping_add(1);
-        self.mem.write_byte(0xFFFF, 0xFF).unwrap();
-        4
+    /// Total M-cycles the rest of the system has been advanced by.
+    pub fn mcycles(&self) -> u64 {
+        self.mcycles
     }
 
-    fn rrca(&mut self) -> u8 {
-        let carry = self.reg.a & 0b0000_0001;
-        self.reg.a = self.reg.a.rotate_right(1);
-        self.reg.unset_all_flags();
-        if carry == 0b0000_0001 {
-            self.reg.set_c();
-        } else {
-            self.reg.unset_c();
+    /// Advance the rest of the system by one M-cycle. Every bus
+    /// access takes one, instructions tick their internal cycles
+    /// explicitly so devices see accesses on the right cycle.
+    fn tick(&mut self) {
+        self.mem.tick();
+        self.mcycles += 1;
+    }
+
+    /// Read a byte for decoding. Unlike read this has no effect
+    /// on the CPU, a failed read is left for the real fetch.
+    fn peek(&self, addr: u16) -> u8 {
+        self.mem.peek(addr).unwrap_or(0xFF)
+    }
+
+    /// Write a byte without taking a bus cycle.
+    fn poke(&mut self, addr: u16, v: u8) {
+        if let Err(source) = self.mem.write(addr, v) {
+            self.fault.get_or_insert(CpuError::Bus { addr, source });
         }
-        self.pc = self.pc.wrapping_add(1);
-        4
     }
 
-    fn sub_a_r(&mut self, reg: StdRegN) -> u8 {
-        let mut cycles = 4;
+    fn read(&mut self, addr: u16) -> u8 {
+        self.tick();
+        match self.mem.read(addr) {
+            Ok(v) => v,
+            Err(source) => {
+                self.fault.get_or_insert(CpuError::Bus { addr, source });
+                0xFF
+            }
+        }
+    }
 
-        macro_rules! sub {
-            ($a:expr,$b:expr) => {{
-                if will_half_borrow($a, $b) {
-                    self.reg.set_h();
-                } else {
-                    self.reg.unset_h();
-                }
-                if will_borrow($a, $b) {
-                    self.reg.set_c();
-                } else {
-                    self.reg.unset_c();
-                }
-                $a = $a.wrapping_sub($b);
-                if $a == 0x00 {
-                    self.reg.set_z();
-                    self.reg.set_c();
-                } else {
-                    self.reg.unset_z();
-                }
-                self.reg.set_n();
-            }};
+    fn write(&mut self, addr: u16, v: u8) {
+        self.tick();
+        self.poke(addr, v);
+    }
+
+    fn lock_up(&mut self, opcode: u8) {
+        self.locked = true;
+        self.fault.get_or_insert(CpuError::IllegalOpcode {
+            opcode,
+            addr: self.pc,
+            bank: self.mem.rom_bank(self.pc),
+        });
+    }
+
+    /// Jump to the highest priority pending interrupt if IME is set,
+    /// returning whether one was serviced.
+    fn service_interrupts(&mut self) -> bool {
+        if !self.ime {
+            return false;
         }
 
-        match reg {
-            StdRegN::A => sub!(self.reg.a, self.reg.a),
-            StdRegN::B => sub!(self.reg.a, self.reg.b),
-            StdRegN::C => sub!(self.reg.a, self.reg.c),
-            StdRegN::D => sub!(self.reg.a, self.reg.d),
-            StdRegN::E => sub!(self.reg.a, self.reg.e),
-            StdRegN::H => sub!(self.reg.a, self.reg.h),
-            StdRegN::L => sub!(self.reg.a, self.reg.l),
-            StdRegN::N => {
-                let amt = self.read_u8();
-                sub!(self.reg.a, amt);
-                self.pc = self.pc.wrapping_sub(1);
-                cycles = 8;
+        let vector = match self.interrupts.service(&mut self.mem) {
+            Ok(Some(vector)) => vector,
+            Ok(None) => return false,
+            Err(source) => {
+                self.interrupt_fault(source);
+                return false;
+            }
+        };
+        self.ime = false;
+        self.ime_scheduled = false;
+        self.tick();
+        self.tick();
+        // An EI; HALT pair hitting the HALT bug returns to the HALT.
+        if self.halt_bug {
+            self.halt_bug = false;
+            self.pc = self.pc.wrapping_sub(1);
+        }
+        let pc = self.pc.to_be_bytes();
+        self.push(pc[0], pc[1]);
+        self.pc = vector;
+        self.tick();
+
+        true
+    }
+
+    /// The interrupt that would be serviced next, if any.
+    fn pending_interrupt(&mut self) -> Option<InterruptKind> {
+        match self.interrupts.pending(&self.mem) {
+            Ok(kind) => kind,
+            Err(source) => {
+                self.interrupt_fault(source);
+                None
             }
-            StdRegN::HL => println!("HL Not implemented"),
         }
+    }
+
+    /// Record a failed IE or IF access.
+    fn interrupt_fault(&mut self, source: io::Error) {
+        let addr = if self.mem.peek(IE_ADDR).is_err() {
+            IE_ADDR
+        } else {
+            IF_ADDR
+        };
+        self.fault.get_or_insert(CpuError::Bus { addr, source });
+    }
+
+    fn joypad_pressed(&self) -> bool {
+        self.peek(P1_ADDR) & 0x0F != 0x0F
+    }
+
+    fn execute(&mut self) {
+        let opcode = self.read(self.pc);
+        if self.halt_bug {
+            // The byte after HALT is fetched without incrementing PC,
+            // so it is read again as the first operand byte.
+            self.halt_bug = false;
+            self.pc = self.pc.wrapping_sub(1);
+        }
+        // Immediate operands are read again, timed, by the handlers.
+        let instr = if opcode == 0xCB {
+            self.pc = self.pc.wrapping_add(1);
+            decode_cb(self.read(self.pc))
+        } else {
+            decode(&[
+                opcode,
+                self.peek(self.pc.wrapping_add(1)),
+                self.peek(self.pc.wrapping_add(2)),
+            ])
+        };
+
+        match instr {
+            Instruction::Nop => self.nop(),   //tested
+            Instruction::Halt => self.halt(), //tested
+            Instruction::Stop => self.stop(), //tested
+            Instruction::Di => self.di(),
+            Instruction::Ei => self.ei(),
+            Instruction::Rrca => self.rrca(), //tested
+            Instruction::Rlca => self.rlca(), //tested
+            Instruction::Rra => self.rra(),   //tested
+            Instruction::Rla => self.rla(),   //tested
+            Instruction::Daa => self.daa(),   //tested
+            Instruction::Cpl => self.cpl(),   //tested
+            Instruction::Scf => self.scf(),   //tested
+            Instruction::Ccf => self.ccf(),   //tested
+            Instruction::LdRR(r1, r2) => self.ld_r_r(r1, r2), //tested
+            Instruction::LdRN(r, _) => self.ld_r_n(r), //tested
+            Instruction::LdRrNn(rr, _) => self.ld_n_nn(rr), //tested
+            Instruction::LdAMemRr(LoadRegnnn::BC) => self.ld_a_n(LoadReg::MemBC), //tested
+            Instruction::LdAMemRr(_) => self.ld_a_n(LoadReg::MemDE), //tested
+            Instruction::LdMemRrA(LoadRegnnn::BC) => self.ld_n_a(LoadReg::MemBC), //tested
+            Instruction::LdMemRrA(_) => self.ld_n_a(LoadReg::MemDE), //tested
+            Instruction::LdAMemNn(_) => self.ld_a_n(LoadReg::MemNN), //tested
+            Instruction::LdMemNnA(_) => self.ld_n_a(LoadReg::MemNN), //tested
+            Instruction::LdiAMemHl => self.ldi_a_memhl(), //tested
+            Instruction::LddAMemHl => self.ldd_a_memhl(), //tested
+            Instruction::LdiMemHlA => self.ld_mem_hl_a_inc(), // tested (!SHEET)
+            Instruction::LddMemHlA => self.ld_mem_hl_a_dec(), // tested (!SHEET)
+            Instruction::LdhNA(_) => self.ld_ff00_a(),
+            Instruction::LdhAN(_) => self.ld_a_ff00(),
+            Instruction::LdhCA => self.ld_ff00c_a(), //tested
+            Instruction::LdhAC => self.ld_a_ff00c(), //tested
+            Instruction::LdNnSp(_) => self.ld_nn_sp(), //tested
+            Instruction::LdSpHl => self.ld_sp_hl(),  //tested
+            Instruction::LdHlSpE(_) => self.ld_hl_sp_e8(), //tested
+            Instruction::AddSpE(_) => self.add_sp_e8(), //tested
+            Instruction::Inc8(r) => self.inc_reg(r.into()), //tested
+            Instruction::Inc16(rr) => self.inc_reg(rr.into()),
+            Instruction::Dec8(r) => self.dec_r(r),     //tested
+            Instruction::Dec16(rr) => self.dec_rr(rr), //tested
+            Instruction::AddHl(rr) => self.add_hl_rr(rr), //tested
+            Instruction::Add(op) => self.add_a_r(op.into()),
+            Instruction::Adc(op) => self.adc_a_n(op.into()),
+            Instruction::Sub(op) => self.sub_a_r(op.into()),
+            Instruction::Sbc(op) => self.sbc_a_n(op.into()), //tested
+            Instruction::And(op) => self.and_a_r(op.into()),
+            Instruction::Xor(op) => self.xor_r(op.into()),
+            Instruction::Or(op) => self.or_a_r(op.into()), //tested
+            Instruction::Cp(Operand::Reg(r)) => self.cp_a_r(r), //tested
+            Instruction::Cp(Operand::Imm(_)) => self.cp_a_n(), //tested
+            Instruction::Rlc(r) => self.rlc_n(r),          //tested
+            Instruction::Rrc(r) => self.rrc_n(r),          //tested
+            Instruction::Rl(r) => self.rl_n(r),            //tested
+            Instruction::Rr(r) => self.rr_n(r),            //tested
+            Instruction::Sla(r) => self.sla(r),            //tested
+            Instruction::Sra(r) => self.sra(r),            //tested
+            Instruction::Swap(r) => self.swap(r),          //tested
+            Instruction::Srl(r) => self.srl(r),            //tested
+            Instruction::Bit(b, r) => self.bit(b, r),      //tested
+            Instruction::Res(b, r) => self.res(b, r),      //tested
+            Instruction::Set(b, r) => self.set(b, r),      //tested
+            Instruction::Jp(_) => self.jp_nn(),            //tested
+            Instruction::JpCc(cc, _) => self.jp_cc(cc),    //tested
+            Instruction::JpHl => self.jp_hl(),             //tested
+            Instruction::Jr(_) => self.jr(),
+            Instruction::JrCc(cc, _) => self.jr_cond(cc),
+            Instruction::Call(_) => self.call(), //tested
+            Instruction::CallCc(cc, _) => self.call_cc(cc), //tested
+            Instruction::Ret => self.ret(),      //tested
+            Instruction::RetCc(cc) => self.ret_cc(cc), //tested
+            Instruction::Reti => self.reti(),    //tested
+            Instruction::Rst(vector) => self.rst(vector as u16), //tested
+            Instruction::Push(StackReg::BC) => self.push_bc(),
+            Instruction::Push(StackReg::DE) => self.push_de(),
+            Instruction::Push(StackReg::HL) => self.push_hl(),
+            Instruction::Push(StackReg::AF) => self.push_af(),
+            Instruction::Pop(StackReg::BC) => self.pop_bc(),
+            Instruction::Pop(StackReg::DE) => self.pop_de(),
+            Instruction::Pop(StackReg::HL) => self.pop_hl(),
+            Instruction::Pop(StackReg::AF) => self.pop_af(),
+            Instruction::Illegal(opcode) => self.lock_up(opcode),
+        }
+    }
 
+    fn nop(&mut self) {
         self.pc = self.pc.wrapping_add(1);
-        cycles
     }
 
-    fn add_a_r(&mut self, reg: StdRegN) -> u8 {
-        let mut cycles = 4;
+    fn halt(&mut self) {
+        self.pc = self.pc.wrapping_add(1);
+        if !self.ime && self.pending_interrupt().is_some() {
+            self.halt_bug = true;
+        } else {
+            self.halted = true;
+        }
+    }
 
-        macro_rules! add {
-            ($a:expr,$b:expr) => {{
-                if will_half_carry($a, $b) {
-                    self.reg.set_h();
-                };
-                if will_carry($a, $b) {
-                    self.reg.set_c();
-                };
-                $a = $a.wrapping_add($b);
-                if $a == 0x00 {
-                    self.reg.set_z();
-                    self.reg.set_c();
-                } else {
-                    self.reg.unset_z();
-                }
-                self.reg.unset_n();
-            }};
+    fn stop(&mut self) {
+        if self.mem.speed_switch() {
+            self.pc = self.pc.wrapping_add(2);
+            self.poke(DIV_ADDR, 0x00);
+            return;
         }
 
-        match reg {
-            StdRegN::A => add!(self.reg.a, self.reg.a),
-            StdRegN::B => add!(self.reg.a, self.reg.b),
-            StdRegN::C => add!(self.reg.a, self.reg.c),
-            StdRegN::D => add!(self.reg.a, self.reg.d),
-            StdRegN::E => add!(self.reg.a, self.reg.e),
-            StdRegN::H => add!(self.reg.a, self.reg.h),
-            StdRegN::L => add!(self.reg.a, self.reg.l),
-            StdRegN::N => {
-                let amt = self.read_u8();
-                add!(self.reg.a, amt);
-                self.pc = self.pc.wrapping_sub(1);
-                cycles = 8;
+        let pending = self.pending_interrupt().is_some();
+
+        if self.joypad_pressed() {
+            if pending {
+                // One byte opcode, nothing happens.
+                self.pc = self.pc.wrapping_add(1);
+            } else {
+                self.pc = self.pc.wrapping_add(2);
+                self.halted = true;
             }
-            StdRegN::HL => println!("HL Not implemented"),
+        } else {
+            self.pc = self.pc.wrapping_add(if pending { 1 } else { 2 });
+            self.poke(DIV_ADDR, 0x00);
+            self.stopped = true;
         }
+    }
 
+    fn di(&mut self) {
         self.pc = self.pc.wrapping_add(1);
-        cycles
+        self.ime = false;
+        self.ime_scheduled = false;
     }
 
-    fn adc_a_n(&mut self, reg: StdRegN) -> u8 {
-        let mut cycles = 4;
+    fn ei(&mut self) {
+        self.pc = self.pc.wrapping_add(1);
+        self.ime_scheduled = true;
+    }
 
-        let carry = self.reg.get_carry();
+    fn rrca(&mut self) {
+        (self.reg.a, self.reg.f) = alu::rrca(self.reg.a);
+        self.pc = self.pc.wrapping_add(1);
+    }
 
-        macro_rules! adc {
-            ($a:expr,$b:expr) => {{
-                if will_half_carry($a, $b) {
-                    self.reg.set_h();
-                };
-                if will_carry($a, $b) {
-                    self.reg.set_c();
-                };
-                $a = $a.wrapping_add($b.wrapping_add(carry));
-            }};
-        }
+    fn daa(&mut self) {
+        (self.reg.a, self.reg.f) = alu::daa(self.reg.a, self.reg.f);
+        self.pc = self.pc.wrapping_add(1);
+    }
 
-        match reg {
-            StdRegN::A => adc!(self.reg.a, self.reg.a),
-            StdRegN::B => adc!(self.reg.a, self.reg.b),
-            StdRegN::C => adc!(self.reg.a, self.reg.c),
-            StdRegN::D => adc!(self.reg.a, self.reg.d),
-            StdRegN::E => adc!(self.reg.a, self.reg.e),
-            StdRegN::H => adc!(self.reg.a, self.reg.h),
-            StdRegN::L => adc!(self.reg.a, self.reg.l),
-            StdRegN::HL => todo!(),
+    fn cpl(&mut self) {
+        (self.reg.a, self.reg.f) = alu::cpl(self.reg.a, self.reg.f);
+        self.pc = self.pc.wrapping_add(1);
+    }
+
+    fn scf(&mut self) {
+        self.reg.f = alu::scf(self.reg.f);
+        self.pc = self.pc.wrapping_add(1);
+    }
+
+    fn ccf(&mut self) {
+        self.reg.f = alu::ccf(self.reg.f);
+        self.pc = self.pc.wrapping_add(1);
+    }
+
+    fn rra(&mut self) {
+        (self.reg.a, self.reg.f) = alu::rra(self.reg.a, self.reg.is_c());
+        self.pc = self.pc.wrapping_add(1);
+    }
+
+    fn rlca(&mut self) {
+        (self.reg.a, self.reg.f) = alu::rlca(self.reg.a);
+        self.pc = self.pc.wrapping_add(1);
+    }
+
+    fn rla(&mut self) {
+        (self.reg.a, self.reg.f) = alu::rla(self.reg.a, self.reg.is_c());
+        self.pc = self.pc.wrapping_add(1);
+    }
+
+    /// Shared body of the 8 bit ALU instructions. `op` receives A
+    /// and the operand and returns the new A and flags.
+    fn alu_op(&mut self, reg: StdRegN, op: impl FnOnce(u8, u8) -> (u8, u8)) {
+        let v = match reg {
+            StdRegN::A => self.reg.a,
+            StdRegN::B => self.reg.b,
+            StdRegN::C => self.reg.c,
+            StdRegN::D => self.reg.d,
+            StdRegN::E => self.reg.e,
+            StdRegN::H => self.reg.h,
+            StdRegN::L => self.reg.l,
+            StdRegN::HL => self.read(self.reg.get_hl()),
             StdRegN::N => {
-                let amt = self.read_u8();
-                adc!(self.reg.a, amt);
-                self.pc = self.pc.wrapping_sub(1);
-                cycles = 8;
+                self.pc = self.pc.wrapping_add(1);
+                self.read(self.pc)
             }
-        }
+        };
+        (self.reg.a, self.reg.f) = op(self.reg.a, v);
 
         self.pc = self.pc.wrapping_add(1);
-        cycles
     }
 
-    fn inc_reg(&mut self, reg: IncDecReg) -> u8 {
-        let mut cycles = 4;
+    fn sub_a_r(&mut self, reg: StdRegN) {
+        self.alu_op(reg, alu::sub)
+    }
+
+    fn add_a_r(&mut self, reg: StdRegN) {
+        self.alu_op(reg, alu::add)
+    }
+
+    fn adc_a_n(&mut self, reg: StdRegN) {
+        let carry = self.reg.is_c();
+        self.alu_op(reg, |a, b| alu::adc(a, b, carry))
+    }
+
+    fn sbc_a_n(&mut self, reg: StdRegN) {
+        let carry = self.reg.is_c();
+        self.alu_op(reg, |a, b| alu::sbc(a, b, carry))
+    }
 
+    fn inc_reg(&mut self, reg: IncDecReg) {
         macro_rules! inc {
             ($a:expr) => {{
-                if will_half_carry($a, 1) {
-                    self.reg.set_h();
-                } else {
-                    self.reg.unset_h();
-                }
-                $a = $a.wrapping_add(1);
-                if $a == 0x00 {
-                    self.reg.set_z();
-                } else {
-                    self.reg.unset_z();
-                }
-                self.reg.unset_n();
+                ($a, self.reg.f) = alu::inc($a, self.reg.f);
             }};
         }
 
@@ -510,87 +714,36 @@ impl<'m> Cpu<'m> {
             IncDecReg::H => inc!(self.reg.h),
             IncDecReg::L => inc!(self.reg.l),
             IncDecReg::HL => {
-                cycles = 8;
+                self.tick();
                 self.reg.set_hl(self.reg.get_hl().wrapping_add(1));
             }
             IncDecReg::BC => {
-                cycles = 8;
+                self.tick();
                 self.reg.set_bc(self.reg.get_bc().wrapping_add(1));
             }
             IncDecReg::DE => {
-                cycles = 8;
+                self.tick();
                 self.reg.set_de(self.reg.get_de().wrapping_add(1));
             }
             IncDecReg::SP => {
-                cycles = 8;
+                self.tick();
                 self.sp = self.sp.wrapping_add(1)
             }
             IncDecReg::MemHL => {
-                cycles = 12;
-                let loc = self.read_u16();
-                let v: u8 = self.mem.read_byte(loc).unwrap();
-
-                if will_half_carry(v, 1) {
-                    self.reg.set_h();
-                }
-                self.mem.write_byte(loc, v.wrapping_add(1)).unwrap();
-                if v.wrapping_add(1) == 0x00 {
-                    self.reg.set_z();
-                }
-                self.reg.unset_n();
+                let loc = self.reg.get_hl();
+                let mut v: u8 = self.read(loc);
+                inc!(v);
+                self.write(loc, v);
             }
         };
 
         self.pc = self.pc.wrapping_add(1);
-
-        cycles
-    }
-
-    fn substract_u8_from_u8(&mut self, value: u8, amt: u8) -> u8 {
-        let v = value.wrapping_sub(amt);
-
-        if v == 0x00 {
-            self.reg.set_z();
-        } else {
-            self.reg.unset_z();
-        }
-
-        if will_half_borrow(value, amt) {
-            self.reg.set_h();
-        } else {
-            self.reg.unset_h();
-        }
-
-        if amt > value {
-            self.reg.set_c();
-        } else {
-            self.reg.unset_c();
-        }
-
-        self.reg.set_n();
-
-        v
     }
 
-    fn dec_r(&mut self, reg: StdReg) -> u8 {
-        let cycles = 4;
+    fn dec_r(&mut self, reg: StdReg) {
         macro_rules! dec {
             ($a:expr) => {{
-                if will_half_borrow($a, 0x01) {
-                    self.reg.set_h();
-                } else {
-                    self.reg.unset_h();
-                }
-
-                $a = $a.wrapping_sub(0x01);
-
-                if $a == 0x00 {
-                    self.reg.set_z();
-                } else {
-                    self.reg.unset_z();
-                }
-
-                self.reg.set_n();
+                ($a, self.reg.f) = alu::dec($a, self.reg.f);
             }};
         }
         match reg {
@@ -601,206 +754,107 @@ impl<'m> Cpu<'m> {
             StdReg::E => dec!(self.reg.e),
             StdReg::H => dec!(self.reg.h),
             StdReg::L => dec!(self.reg.l),
-            StdReg::HL => todo!(),
+            StdReg::HL => {
+                let loc = self.reg.get_hl();
+                let mut v: u8 = self.read(loc);
+                dec!(v);
+                self.write(loc, v);
+            }
         }
 
         self.pc = self.pc.wrapping_add(1);
-
-        cycles
     }
 
-    fn cp_a_r(&mut self, reg: StdReg) -> u8 {
-        let cycles = 4;
-        self.pc = self.pc.wrapping_add(1);
+    fn dec_rr(&mut self, reg: LoadRegnnn) {
         match reg {
-            StdReg::A => self.substract_u8_from_u8(self.reg.a, 0x01),
-            StdReg::B => self.substract_u8_from_u8(self.reg.b, 0x01),
-            StdReg::C => self.substract_u8_from_u8(self.reg.c, 0x01),
-            StdReg::D => self.substract_u8_from_u8(self.reg.d, 0x01),
-            StdReg::E => self.substract_u8_from_u8(self.reg.e, 0x01),
-            StdReg::H => self.substract_u8_from_u8(self.reg.h, 0x01),
-            StdReg::L => self.substract_u8_from_u8(self.reg.l, 0x01),
-            StdReg::HL => todo!(),
-        };
-        cycles
-    }
-
-    fn cp_a_n(&mut self) -> u8 {
-        let n = self.read_u8();
-        self.substract_u8_from_u8(self.reg.a, n);
+            LoadRegnnn::BC => self.reg.set_bc(self.reg.get_bc().wrapping_sub(1)),
+            LoadRegnnn::DE => self.reg.set_de(self.reg.get_de().wrapping_sub(1)),
+            LoadRegnnn::HL => self.reg.set_hl(self.reg.get_hl().wrapping_sub(1)),
+            LoadRegnnn::SP => self.sp = self.sp.wrapping_sub(1),
+        }
+        self.tick();
 
-        4
+        self.pc = self.pc.wrapping_add(1);
     }
 
-    fn jr(&mut self) -> u8 {
-        let cycles = 12;
-        let v = self.read_u8();
-
-        let sig: u16;
-        let is_neg: bool;
+    fn cp_a_r(&mut self, reg: StdReg) {
+        self.alu_op(reg.into(), |a, b| (a, alu::cp(a, b)))
+    }
 
-        if (v & 0b1000_0000) == 0b1000_0000 {
-            is_neg = true;
-            sig = !v as u16 + 1;
-        } else {
-            is_neg = false;
-            sig = v as u16;
-        }
+    fn cp_a_n(&mut self) {
+        self.alu_op(StdRegN::N, |a, b| (a, alu::cp(a, b)))
+    }
 
-        if is_neg {
-            self.pc -= sig;
-        } else {
-            self.pc += sig;
-        }
+    fn jr(&mut self) {
+        let v = self.read_u8() as i8;
+        self.tick();
 
-        cycles
+        self.pc = self.pc.wrapping_add(v as u16);
     }
-    fn jr_cond(&mut self, cond: FlagCond) -> u8 {
-        let mut cycles: u8 = 8;
+    fn jr_cond(&mut self, cond: FlagCond) {
         if cond.check(self.reg.f) {
-            cycles = self.jr();
+            self.jr();
         } else {
-            self.pc = self.pc.wrapping_add(2);
+            self.read_u8();
         }
-
-        cycles
     }
 
-    fn jp_nn(&mut self) -> u8 {
+    fn jp_nn(&mut self) {
         let loc = self.read_u16();
+        self.tick();
         self.pc = loc;
-        16
     }
 
-    fn and_a_r(&mut self, reg: LoadReg) -> u8 {
-        macro_rules! and {
-            ($a:expr) => {{
-                self.reg.a = self.reg.a & $a;
-                self.reg.unset_all_flags();
-                if self.reg.a == 0x00 {
-                    self.reg.set_z();
-                }
-                self.reg.set_h();
-                self.reg.unset_n();
-                self.reg.unset_c();
-            }};
-        }
-
-        match reg {
-            LoadReg::A => and!(self.reg.a),
-            LoadReg::B => and!(self.reg.b),
-            LoadReg::C => and!(self.reg.c),
-            LoadReg::D => and!(self.reg.d),
-            LoadReg::E => and!(self.reg.e),
-            LoadReg::H => and!(self.reg.h),
-            LoadReg::L => and!(self.reg.l),
-            LoadReg::MemBC => todo!(),
-            LoadReg::MemDE => todo!(),
-            LoadReg::MemHL => todo!(),
-            LoadReg::MemNN => todo!(),
-            LoadReg::N => {
-                and!(self.read_u8());
-                self.pc = self.pc.wrapping_sub(1);
-            }
+    fn jp_cc(&mut self, cond: FlagCond) {
+        if cond.check(self.reg.f) {
+            self.jp_nn();
+        } else {
+            self.read_u16();
         }
-
-        self.pc = self.pc.wrapping_add(1);
-        4
     }
 
-    fn or_a_r(&mut self, reg: StdReg) -> u8 {
-        macro_rules! or {
-            ($a:expr) => {{
-                self.reg.a = self.reg.a | $a;
-                self.reg.unset_all_flags();
-                if self.reg.a == 0x00 {
-                    self.reg.set_z();
-                }
-            }};
-        }
-
-        match reg {
-            StdReg::A => or!(self.reg.a),
-            StdReg::B => or!(self.reg.b),
-            StdReg::C => or!(self.reg.c),
-            StdReg::D => or!(self.reg.d),
-            StdReg::E => or!(self.reg.e),
-            StdReg::H => or!(self.reg.h),
-            StdReg::L => or!(self.reg.l),
-            StdReg::HL => or!(self.mem.read_byte(self.reg.get_hl()).unwrap()),
-        }
-
-        self.pc = self.pc.wrapping_add(1);
-        4
+    fn jp_hl(&mut self) {
+        self.pc = self.reg.get_hl();
     }
 
-    fn xor_r(&mut self, reg: StdRegN) -> u8 {
-        macro_rules! xor {
-            ($a:expr) => {{
-                self.reg.a = self.reg.a ^ $a;
-                self.reg.unset_all_flags();
-                if self.reg.a == 0x00 {
-                    self.reg.set_z();
-                }
-            }};
-        }
-
-        match reg {
-            StdRegN::A => xor!(self.reg.a),
-            StdRegN::B => xor!(self.reg.b),
-            StdRegN::C => xor!(self.reg.c),
-            StdRegN::D => xor!(self.reg.d),
-            StdRegN::E => xor!(self.reg.e),
-            StdRegN::H => xor!(self.reg.h),
-            StdRegN::L => xor!(self.reg.l),
-            StdRegN::HL => xor!(self.mem.read_byte(self.reg.get_hl()).unwrap()),
-            StdRegN::N => {
-                xor!(self.read_u8());
-                self.pc = self.pc.wrapping_sub(1);
-            }
-        }
+    fn and_a_r(&mut self, reg: StdRegN) {
+        self.alu_op(reg, alu::and)
+    }
 
-        self.pc = self.pc.wrapping_add(1);
-        4
+    fn or_a_r(&mut self, reg: StdRegN) {
+        self.alu_op(reg, alu::or)
     }
 
-    fn ld_ff00_a(&mut self) -> u8 {
-        //Must be between $FF00 and $FFFF
-        let cycles = 12;
+    fn xor_r(&mut self, reg: StdRegN) {
+        self.alu_op(reg, alu::xor)
+    }
 
+    fn ld_ff00_a(&mut self) {
         let b = 0xFF00 + self.read_u8() as u16;
-
-        if (0xFF00..0xFFFF).contains(&b) {
-            self.mem.write_byte(b, self.reg.a).unwrap();
-        }
-
-        cycles
+        self.write(b, self.reg.a);
     }
 
-    fn ld_a_ff00(&mut self) -> u8 {
-        //Must be between $FF00 and $FFFF
-        let cycles = 12;
-
-        let a = self.read_u8();
-        let b = 0xFF00 + a as u16;
+    fn ld_a_ff00(&mut self) {
+        let b = 0xFF00 + self.read_u8() as u16;
+        self.reg.a = self.read(b);
+    }
 
-        if (0xFF00..0xFFFF).contains(&b) {
-            self.reg.a = self.mem.read_byte(b).unwrap();
-            // for i in 0xFF00..0xFFFF {
-            //     println!("{:X} :: {:X}",i,self.mem.read_byte(i).unwrap());
-            // }
-        } else {
-            println!("ld_a_ff00() --> Memory location {} is not readable.", b);
-        }
+    fn ld_ff00c_a(&mut self) {
+        let b = 0xFF00 + self.reg.c as u16;
+        self.write(b, self.reg.a);
+        self.pc = self.pc.wrapping_add(1);
+    }
 
-        cycles
+    fn ld_a_ff00c(&mut self) {
+        let b = 0xFF00 + self.reg.c as u16;
+        self.reg.a = self.read(b);
+        self.pc = self.pc.wrapping_add(1);
     }
 
-    fn ld_r_r(&mut self, r1: StdReg, r2: StdReg) -> u8 {
-        let mut cycles = 4;
-        let _ret = match r1 {
+    fn ld_r_r(&mut self, r1: StdReg, r2: StdReg) {
+        match r1 {
             StdReg::A => match r2 {
-                StdReg::A => self.reg.a = self.reg.a,
+                StdReg::A => {}
                 StdReg::B => self.reg.a = self.reg.b,
                 StdReg::C => self.reg.a = self.reg.c,
                 StdReg::D => self.reg.a = self.reg.d,
@@ -808,47 +862,43 @@ impl<'m> Cpu<'m> {
                 StdReg::H => self.reg.a = self.reg.h,
                 StdReg::L => self.reg.a = self.reg.l,
                 StdReg::HL => {
-                    cycles += 4;
-                    self.reg.a = self.mem.read_byte(self.reg.get_hl()).unwrap();
+                    self.reg.a = self.read(self.reg.get_hl());
                 }
             },
             StdReg::B => match r2 {
                 StdReg::A => self.reg.b = self.reg.a,
-                StdReg::B => self.reg.b = self.reg.b,
+                StdReg::B => {}
                 StdReg::C => self.reg.b = self.reg.c,
                 StdReg::D => self.reg.b = self.reg.d,
                 StdReg::E => self.reg.b = self.reg.e,
                 StdReg::H => self.reg.b = self.reg.h,
                 StdReg::L => self.reg.b = self.reg.l,
                 StdReg::HL => {
-                    cycles += 4;
-                    self.reg.b = self.mem.read_byte(self.reg.get_hl()).unwrap();
+                    self.reg.b = self.read(self.reg.get_hl());
                 }
             },
             StdReg::C => match r2 {
                 StdReg::A => self.reg.c = self.reg.a,
                 StdReg::B => self.reg.c = self.reg.b,
-                StdReg::C => self.reg.c = self.reg.c,
+                StdReg::C => {}
                 StdReg::D => self.reg.c = self.reg.d,
                 StdReg::E => self.reg.c = self.reg.e,
                 StdReg::H => self.reg.c = self.reg.h,
                 StdReg::L => self.reg.c = self.reg.l,
                 StdReg::HL => {
-                    cycles += 4;
-                    self.reg.c = self.mem.read_byte(self.reg.get_hl()).unwrap();
+                    self.reg.c = self.read(self.reg.get_hl());
                 }
             },
             StdReg::D => match r2 {
                 StdReg::A => self.reg.d = self.reg.a,
                 StdReg::B => self.reg.d = self.reg.b,
                 StdReg::C => self.reg.d = self.reg.c,
-                StdReg::D => self.reg.d = self.reg.d,
+                StdReg::D => {}
                 StdReg::E => self.reg.d = self.reg.e,
                 StdReg::H => self.reg.d = self.reg.h,
                 StdReg::L => self.reg.d = self.reg.l,
                 StdReg::HL => {
-                    cycles += 4;
-                    self.reg.d = self.mem.read_byte(self.reg.get_hl()).unwrap();
+                    self.reg.d = self.read(self.reg.get_hl());
                 }
             },
             StdReg::E => match r2 {
@@ -856,12 +906,11 @@ impl<'m> Cpu<'m> {
                 StdReg::B => self.reg.e = self.reg.b,
                 StdReg::C => self.reg.e = self.reg.c,
                 StdReg::D => self.reg.e = self.reg.d,
-                StdReg::E => self.reg.e = self.reg.e,
+                StdReg::E => {}
                 StdReg::H => self.reg.e = self.reg.h,
                 StdReg::L => self.reg.e = self.reg.l,
                 StdReg::HL => {
-                    cycles += 4;
-                    self.reg.e = self.mem.read_byte(self.reg.get_hl()).unwrap();
+                    self.reg.e = self.read(self.reg.get_hl());
                 }
             },
             StdReg::H => match r2 {
@@ -870,11 +919,10 @@ impl<'m> Cpu<'m> {
                 StdReg::C => self.reg.h = self.reg.c,
                 StdReg::D => self.reg.h = self.reg.d,
                 StdReg::E => self.reg.h = self.reg.e,
-                StdReg::H => self.reg.h = self.reg.h,
+                StdReg::H => {}
                 StdReg::L => self.reg.h = self.reg.l,
                 StdReg::HL => {
-                    cycles += 4;
-                    self.reg.h = self.mem.read_byte(self.reg.get_hl()).unwrap();
+                    self.reg.h = self.read(self.reg.get_hl());
                 }
             },
             StdReg::L => match r2 {
@@ -884,73 +932,64 @@ impl<'m> Cpu<'m> {
                 StdReg::D => self.reg.l = self.reg.d,
                 StdReg::E => self.reg.l = self.reg.e,
                 StdReg::H => self.reg.l = self.reg.h,
-                StdReg::L => self.reg.l = self.reg.l,
+                StdReg::L => {}
                 StdReg::HL => {
-                    cycles += 4;
-                    self.reg.l = self.mem.read_byte(self.reg.get_hl()).unwrap();
+                    self.reg.l = self.read(self.reg.get_hl());
                 }
             },
             StdReg::HL => {
-                cycles += 4;
-                match r2 {
-                    StdReg::A => self.mem.write_byte(self.reg.get_hl(), self.reg.a).unwrap(),
-                    StdReg::B => self.mem.write_byte(self.reg.get_hl(), self.reg.b).unwrap(),
-                    StdReg::C => self.mem.write_byte(self.reg.get_hl(), self.reg.c).unwrap(),
-                    StdReg::D => self.mem.write_byte(self.reg.get_hl(), self.reg.d).unwrap(),
-                    StdReg::E => self.mem.write_byte(self.reg.get_hl(), self.reg.e).unwrap(),
-                    StdReg::H => self.mem.write_byte(self.reg.get_hl(), self.reg.h).unwrap(),
-                    StdReg::L => self.mem.write_byte(self.reg.get_hl(), self.reg.a).unwrap(),
-                    StdReg::HL => {
-                        self.pc = self.pc.wrapping_add(1);
-                        self.mem
-                            .write_byte(self.reg.get_hl(), self.mem.read_byte(self.pc).unwrap())
-                            .unwrap();
-                        0x00
-                    }
+                let v = match r2 {
+                    StdReg::A => self.reg.a,
+                    StdReg::B => self.reg.b,
+                    StdReg::C => self.reg.c,
+                    StdReg::D => self.reg.d,
+                    StdReg::E => self.reg.e,
+                    StdReg::H => self.reg.h,
+                    StdReg::L => self.reg.l,
+                    StdReg::HL => unreachable!("LD (HL),(HL) is encoded as HALT"),
                 };
+                self.write(self.reg.get_hl(), v);
             }
         };
         self.pc = self.pc.wrapping_add(1);
-        cycles
     }
 
-    fn ldi_a_memhl(&mut self) -> u8 {
+    fn ldi_a_memhl(&mut self) {
         let hl = self.reg.get_hl();
 
-        self.reg.a = self.mem.read_byte(hl).unwrap();
+        self.reg.a = self.read(hl);
         self.reg.set_hl(hl.wrapping_add(1));
 
         self.pc = self.pc.wrapping_add(1);
-
-        8
     }
 
-    fn ld_mem_hl_a(&mut self) -> u8 {
-        self.mem.write_byte(self.reg.get_hl(), self.reg.a).unwrap();
+    fn ldd_a_memhl(&mut self) {
+        let hl = self.reg.get_hl();
+
+        self.reg.a = self.read(hl);
+        self.reg.set_hl(hl.wrapping_sub(1));
+
         self.pc = self.pc.wrapping_add(1);
+    }
 
-        8
+    fn ld_mem_hl_a(&mut self) {
+        self.write(self.reg.get_hl(), self.reg.a);
+        self.pc = self.pc.wrapping_add(1);
     }
 
-    fn ld_mem_hl_a_dec(&mut self) -> u8 {
-        let cycles = self.ld_mem_hl_a();
+    fn ld_mem_hl_a_dec(&mut self) {
+        self.ld_mem_hl_a();
         self.reg.set_hl(self.reg.get_hl().overflowing_sub(1).0);
-
-        cycles
     }
 
-    fn ld_mem_hl_a_inc(&mut self) -> u8 {
-        let cycles = self.ld_mem_hl_a();
+    fn ld_mem_hl_a_inc(&mut self) {
+        self.ld_mem_hl_a();
         self.reg.set_hl(self.reg.get_hl().overflowing_add(1).0);
-
-        cycles
     }
 
-    fn ld_a_n(&mut self, reg: LoadReg) -> u8 {
-        let mut cycles = 4;
-
+    fn ld_a_n(&mut self, reg: LoadReg) {
         match reg {
-            LoadReg::A => self.reg.a = self.reg.a,
+            LoadReg::A => {}
             LoadReg::B => self.reg.a = self.reg.b,
             LoadReg::C => self.reg.a = self.reg.c,
             LoadReg::D => self.reg.a = self.reg.d,
@@ -958,39 +997,31 @@ impl<'m> Cpu<'m> {
             LoadReg::H => self.reg.a = self.reg.h,
             LoadReg::L => self.reg.a = self.reg.l,
             LoadReg::MemBC => {
-                cycles = 8;
-                self.reg.a = self.mem.read_byte(self.reg.get_bc()).unwrap();
+                self.reg.a = self.read(self.reg.get_bc());
             }
             LoadReg::MemDE => {
-                cycles = 8;
-                self.reg.a = self.mem.read_byte(self.reg.get_de()).unwrap();
+                self.reg.a = self.read(self.reg.get_de());
             }
             LoadReg::MemHL => {
-                cycles = 8;
-                self.reg.a = self.mem.read_byte(self.reg.get_hl()).unwrap();
+                self.reg.a = self.read(self.reg.get_hl());
             }
             LoadReg::MemNN => {
-                cycles = 16;
                 let loc = self.read_u16();
-                self.reg.a = self.mem.read_byte(loc).unwrap();
+                self.reg.a = self.read(loc);
                 self.pc = self.pc.wrapping_sub(1);
             }
             LoadReg::N => {
-                cycles = 8;
                 self.reg.a = self.read_u8();
                 self.pc = self.pc.wrapping_sub(1);
             }
         }
 
         self.pc = self.pc.wrapping_add(1);
-
-        cycles
     }
 
-    fn ld_n_a(&mut self, reg: LoadReg) -> u8 {
-        let mut cycles = 4;
+    fn ld_n_a(&mut self, reg: LoadReg) {
         match reg {
-            LoadReg::A => self.reg.a = self.reg.a,
+            LoadReg::A => {}
             LoadReg::B => self.reg.b = self.reg.a,
             LoadReg::C => self.reg.c = self.reg.a,
             LoadReg::D => self.reg.d = self.reg.a,
@@ -998,34 +1029,26 @@ impl<'m> Cpu<'m> {
             LoadReg::H => self.reg.h = self.reg.a,
             LoadReg::L => self.reg.l = self.reg.a,
             LoadReg::MemBC => {
-                cycles = 8;
-                self.mem.write_byte(self.reg.get_bc(), self.reg.a).unwrap();
+                self.write(self.reg.get_bc(), self.reg.a);
             }
             LoadReg::MemDE => {
-                cycles = 8;
-                self.mem.write_byte(self.reg.get_de(), self.reg.a).unwrap();
+                self.write(self.reg.get_de(), self.reg.a);
             }
             LoadReg::MemHL => {
-                cycles = 8;
-                self.mem.write_byte(self.reg.get_hl(), self.reg.a).unwrap();
+                self.write(self.reg.get_hl(), self.reg.a);
             }
             LoadReg::MemNN => {
-                cycles = 16;
                 let v = self.read_u16();
-                self.mem.write_byte(v, self.reg.a).unwrap();
+                self.write(v, self.reg.a);
                 self.pc = self.pc.wrapping_sub(1);
             }
             LoadReg::N => (),
         };
 
         self.pc = self.pc.wrapping_add(1);
-
-        cycles
     }
 
-    fn ld_n_nn(&mut self, reg: LoadRegnnn) -> u8 {
-        let cycles = 12;
-
+    fn ld_n_nn(&mut self, reg: LoadRegnnn) {
         let nn = self.read_u16();
 
         match reg {
@@ -1034,12 +1057,59 @@ impl<'m> Cpu<'m> {
             LoadRegnnn::HL => self.reg.set_hl(nn),
             LoadRegnnn::SP => self.sp = nn,
         }
+    }
+
+    fn ld_nn_sp(&mut self) {
+        let loc = self.read_u16();
+        let sp = self.sp.to_le_bytes();
+        self.write(loc, sp[0]);
+        self.write(loc.wrapping_add(1), sp[1]);
+    }
+
+    fn ld_sp_hl(&mut self) {
+        self.sp = self.reg.get_hl();
+        self.tick();
+        self.pc = self.pc.wrapping_add(1);
+    }
+
+    fn add_hl_rr(&mut self, reg: LoadRegnnn) {
+        let hl = self.reg.get_hl();
+        let rr = match reg {
+            LoadRegnnn::BC => self.reg.get_bc(),
+            LoadRegnnn::DE => self.reg.get_de(),
+            LoadRegnnn::HL => hl,
+            LoadRegnnn::SP => self.sp,
+        };
+
+        let (v, f) = alu::add16(hl, rr, self.reg.f);
+        self.reg.set_hl(v);
+        self.reg.f = f;
+        self.tick();
+
+        self.pc = self.pc.wrapping_add(1);
+    }
+
+    /// SP plus the signed immediate, setting the flags.
+    fn sp_plus_e8(&mut self) -> u16 {
+        let e = self.read_u8();
+        let (v, f) = alu::add_sp(self.sp, e);
+        self.reg.f = f;
+        v
+    }
+
+    fn add_sp_e8(&mut self) {
+        self.sp = self.sp_plus_e8();
+        self.tick();
+        self.tick();
+    }
 
-        cycles
+    fn ld_hl_sp_e8(&mut self) {
+        let v = self.sp_plus_e8();
+        self.tick();
+        self.reg.set_hl(v);
     }
 
-    //TODO Make this do all the Regs
-    fn ld_r_n(&mut self, reg: StdReg) -> u8 {
+    fn ld_r_n(&mut self, reg: StdReg) {
         macro_rules! ld {
             ($a:expr) => {{
                 $a = self.read_u8();
@@ -1054,288 +1124,272 @@ impl<'m> Cpu<'m> {
             StdReg::E => ld!(self.reg.e),
             StdReg::H => ld!(self.reg.h),
             StdReg::L => ld!(self.reg.l),
-            StdReg::HL => todo!(),
+            StdReg::HL => {
+                let n = self.read_u8();
+                self.write(self.reg.get_hl(), n);
+            }
         }
-
-        8
     }
 
-    fn rst(&mut self, pc: u16) -> u8 {
-        self.pc = self.pc.wrapping_add(1);
-        let lo = self.mem.read_byte(self.pc).unwrap();
-        self.pc = self.pc.wrapping_add(1);
-        let hi = self.mem.read_byte(self.pc).unwrap();
-        self.push(lo, hi);
+    fn rst(&mut self, pc: u16) {
+        let ret = self.pc.wrapping_add(1).to_be_bytes();
+        self.tick();
+        self.push(ret[0], ret[1]);
         self.pc = pc;
-        16
     }
-    fn rst_00(&mut self) -> u8 {
-        self.rst(0x0000)
-    }
-    fn rst_08(&mut self) -> u8 {
-        self.rst(0x0008)
+
+    fn get_std_reg(&mut self, reg: &StdReg) -> u8 {
+        match reg {
+            StdReg::A => self.reg.a,
+            StdReg::B => self.reg.b,
+            StdReg::C => self.reg.c,
+            StdReg::D => self.reg.d,
+            StdReg::E => self.reg.e,
+            StdReg::H => self.reg.h,
+            StdReg::L => self.reg.l,
+            StdReg::HL => self.read(self.reg.get_hl()),
+        }
     }
-    fn rst_10(&mut self) -> u8 {
-        self.rst(0x0010)
+
+    fn set_std_reg(&mut self, reg: &StdReg, v: u8) {
+        match reg {
+            StdReg::A => self.reg.a = v,
+            StdReg::B => self.reg.b = v,
+            StdReg::C => self.reg.c = v,
+            StdReg::D => self.reg.d = v,
+            StdReg::E => self.reg.e = v,
+            StdReg::H => self.reg.h = v,
+            StdReg::L => self.reg.l = v,
+            StdReg::HL => {
+                self.write(self.reg.get_hl(), v);
+            }
+        }
     }
-    fn rst_18(&mut self) -> u8 {
-        self.rst(0x0018)
+
+    /// Shared body of the CB-prefixed rotates and shifts. `op` receives
+    /// the operand and the current carry and returns the result and the
+    /// new flags.
+    fn shift_op(&mut self, reg: StdReg, op: impl FnOnce(u8, bool) -> (u8, u8)) {
+        let (v, f) = op(self.get_std_reg(&reg), self.reg.is_c());
+        self.set_std_reg(&reg, v);
+        self.reg.f = f;
+
+        self.pc = self.pc.wrapping_add(1);
     }
-    fn rst_20(&mut self) -> u8 {
-        self.rst(0x0020)
+
+    fn rlc_n(&mut self, reg: StdReg) {
+        self.shift_op(reg, |v, _| alu::rlc(v))
     }
-    fn rst_28(&mut self) -> u8 {
-        self.rst(0x0028)
+
+    fn rrc_n(&mut self, reg: StdReg) {
+        self.shift_op(reg, |v, _| alu::rrc(v))
     }
-    fn rst_30(&mut self) -> u8 {
-        self.rst(0x0030)
+
+    fn rl_n(&mut self, reg: StdReg) {
+        self.shift_op(reg, alu::rl)
     }
-    fn rst_38(&mut self) -> u8 {
-        self.rst(0x0038)
+
+    fn rr_n(&mut self, reg: StdReg) {
+        self.shift_op(reg, alu::rr)
     }
 
-    fn rr_n(&mut self, reg: StdReg) -> u8 {
-        let mut cycles = 4;
-        let c;
+    fn sla(&mut self, reg: StdReg) {
+        self.shift_op(reg, |v, _| alu::sla(v))
+    }
 
-        macro_rules! rr {
-            ($a:expr) => {{
-                c = $a & 0x01;
-                $a = $a.rotate_right(1);
-                if self.reg.is_c() {
-                    $a |= 0x80;
-                } else {
-                    $a &= 0x7F;
-                }
-            }};
-        }
+    fn sra(&mut self, reg: StdReg) {
+        self.shift_op(reg, |v, _| alu::sra(v))
+    }
 
-        match reg {
-            StdReg::A => rr!(self.reg.a),
-            StdReg::B => rr!(self.reg.b),
-            StdReg::C => rr!(self.reg.c),
-            StdReg::D => rr!(self.reg.d),
-            StdReg::E => rr!(self.reg.e),
-            StdReg::H => rr!(self.reg.h),
-            StdReg::L => rr!(self.reg.l),
-            StdReg::HL => {
-                cycles += 8;
-                let mut val = self.mem.read_byte(self.reg.get_hl()).unwrap();
-                c = val & 0x01;
-                val = val.rotate_right(1);
-                if self.reg.is_c() {
-                    val |= 0x80;
-                } else {
-                    val &= 0x7F;
-                }
-                self.mem.write_byte(self.reg.get_hl(), val).unwrap();
-            }
-        };
+    fn swap(&mut self, reg: StdReg) {
+        self.shift_op(reg, |v, _| alu::swap(v))
+    }
 
-        self.reg.unset_z();
-        self.reg.unset_n();
-        self.reg.unset_h();
+    fn srl(&mut self, reg: StdReg) {
+        self.shift_op(reg, |v, _| alu::srl(v))
+    }
 
-        if c == 0x01 {
-            self.reg.set_c();
-        } else {
-            self.reg.unset_c();
-        }
+    fn bit(&mut self, bit: u8, reg: StdReg) {
+        let v = self.get_std_reg(&reg);
+        self.reg.f = alu::bit(bit, v, self.reg.f);
 
         self.pc = self.pc.wrapping_add(1);
-
-        cycles // HL = 16
     }
 
-    fn srl(&mut self, reg: StdReg) -> u8 {
-        let cycles = 4;
-
-        macro_rules! srl {
-            ($a:expr) => {{
-                if $a & 0x01 == 0x01 {
-                    self.reg.set_c();
-                }
-                $a = $a >> 0x01;
-                self.reg.unset_h();
-                self.reg.unset_n();
-            }};
-        }
+    fn res(&mut self, bit: u8, reg: StdReg) {
+        let v = self.get_std_reg(&reg);
+        self.set_std_reg(&reg, v & !(1 << bit));
 
-        match reg {
-            StdReg::A => srl!(self.reg.a),
-            StdReg::B => srl!(self.reg.b),
-            StdReg::C => srl!(self.reg.c),
-            StdReg::D => srl!(self.reg.d),
-            StdReg::E => srl!(self.reg.e),
-            StdReg::H => srl!(self.reg.h),
-            StdReg::L => srl!(self.reg.l),
-            StdReg::HL => todo!(),
-        };
+        self.pc = self.pc.wrapping_add(1);
+    }
 
-        // if c == 0x01 {
-        //     self.reg.set_c();
-        // } else {
-        //     self.reg.unset_c();
-        // }
+    fn set(&mut self, bit: u8, reg: StdReg) {
+        let v = self.get_std_reg(&reg);
+        self.set_std_reg(&reg, v | (1 << bit));
 
         self.pc = self.pc.wrapping_add(1);
-
-        cycles // HL = 16
     }
 
     fn pop(&mut self) -> u16 {
-        let lo = self.mem.read_byte(self.sp).unwrap();
+        let lo = self.read(self.sp);
         self.sp = self.sp.wrapping_add(1);
-        let hi = self.mem.read_byte(self.sp).unwrap();
+        let hi = self.read(self.sp);
         self.sp = self.sp.wrapping_add(1);
 
         ((hi as u16) << 8) | lo as u16
     }
 
-    fn pop_bc(&mut self) -> u8 {
-        let cycles: u8 = 12;
-        self.reg.c = self.mem.read_byte(self.sp).unwrap();
+    fn pop_bc(&mut self) {
+        self.reg.c = self.read(self.sp);
         self.sp = self.sp.wrapping_add(1);
-        self.reg.b = self.mem.read_byte(self.sp).unwrap();
+        self.reg.b = self.read(self.sp);
         self.sp = self.sp.wrapping_add(1);
 
         self.pc = self.pc.wrapping_add(1);
-
-        cycles
     }
 
-    fn pop_de(&mut self) -> u8 {
-        let cycles: u8 = 12;
-        self.reg.e = self.mem.read_byte(self.sp).unwrap();
+    fn pop_de(&mut self) {
+        self.reg.e = self.read(self.sp);
         self.sp = self.sp.wrapping_add(1);
-        self.reg.d = self.mem.read_byte(self.sp).unwrap();
+        self.reg.d = self.read(self.sp);
         self.sp = self.sp.wrapping_add(1);
 
         self.pc = self.pc.wrapping_add(1);
-
-        cycles
     }
 
-    fn pop_af(&mut self) -> u8 {
-        let cycles: u8 = 12;
-        self.reg.f = self.mem.read_byte(self.sp).unwrap();
+    fn pop_af(&mut self) {
+        // The low nibble of F is hardwired to zero.
+        self.reg.f = self.read(self.sp) & 0xF0;
         self.sp = self.sp.wrapping_add(1);
-        self.reg.a = self.mem.read_byte(self.sp).unwrap();
+        self.reg.a = self.read(self.sp);
         self.sp = self.sp.wrapping_add(1);
 
         self.pc = self.pc.wrapping_add(1);
-
-        cycles
     }
 
-    fn pop_hl(&mut self) -> u8 {
-        let cycles: u8 = 12;
-        self.reg.l = self.mem.read_byte(self.sp).unwrap();
+    fn pop_hl(&mut self) {
+        self.reg.l = self.read(self.sp);
         self.sp = self.sp.wrapping_add(1);
-        self.reg.h = self.mem.read_byte(self.sp).unwrap();
+        self.reg.h = self.read(self.sp);
         self.sp = self.sp.wrapping_add(1);
 
         self.pc = self.pc.wrapping_add(1);
-
-        cycles
     }
 
-    fn push(&mut self, lo: u8, hi: u8) {
+    fn push(&mut self, hi: u8, lo: u8) {
         self.sp = self.sp.wrapping_sub(1);
-        self.mem.write_byte(self.sp, lo).unwrap();
+        self.write(self.sp, hi);
         self.sp = self.sp.wrapping_sub(1);
-        self.mem.write_byte(self.sp, hi).unwrap();
+        self.write(self.sp, lo);
     }
 
-    fn push_rr(&mut self, lo: u8, hi: u8) -> u8 {
-        let cycles: u8 = 16;
-        self.push(lo, hi);
+    fn push_rr(&mut self, hi: u8, lo: u8) {
+        self.tick();
+        self.push(hi, lo);
         self.pc = self.pc.wrapping_add(1);
-        cycles
     }
 
-    fn push_bc(&mut self) -> u8 {
+    fn push_bc(&mut self) {
         self.push_rr(self.reg.b, self.reg.c)
     }
 
-    fn push_de(&mut self) -> u8 {
+    fn push_de(&mut self) {
         self.push_rr(self.reg.d, self.reg.e)
     }
 
-    fn push_hl(&mut self) -> u8 {
+    fn push_hl(&mut self) {
         self.push_rr(self.reg.h, self.reg.l)
     }
 
-    fn push_af(&mut self) -> u8 {
+    fn push_af(&mut self) {
         self.push_rr(self.reg.a, self.reg.f)
     }
 
     fn read_u8(&mut self) -> u8 {
         self.pc = self.pc.wrapping_add(1);
-        let b = self.mem.read_byte(self.pc).unwrap();
+        let b = self.read(self.pc);
         self.pc = self.pc.wrapping_add(1);
         b
     }
 
     fn read_u16(&mut self) -> u16 {
         self.pc = self.pc.wrapping_add(1);
-        let low = self.mem.read_byte(self.pc).unwrap();
+        let low = self.read(self.pc);
         self.pc = self.pc.wrapping_add(1);
-        let high = self.mem.read_byte(self.pc).unwrap();
+        let high = self.read(self.pc);
         self.pc = self.pc.wrapping_add(1);
         self.reg.get_nn(low, high)
     }
 
-    fn ret(&mut self) -> u8 {
-        let cycles = 16;
+    fn ret(&mut self) {
         self.pc = self.pop();
-        cycles
+        self.tick();
     }
 
-    fn ret_cc(&mut self, cond: FlagCond) -> u8 {
-        let mut cycles = 8;
+    fn reti(&mut self) {
+        self.ret();
+        self.ime = true;
+        self.ime_scheduled = false;
+    }
 
+    fn ret_cc(&mut self, cond: FlagCond) {
+        self.tick();
         if cond.check(self.reg.f) {
-            cycles = 20;
             self.pc = self.pop();
+            self.tick();
         } else {
             self.pc = self.pc.wrapping_add(1);
         }
-
-        cycles
     }
 
-    fn call_cc(&mut self, cond: FlagCond) -> u8 {
-        let mut cycles = 12;
+    fn call_cc(&mut self, cond: FlagCond) {
         if cond.check(self.reg.f) {
-            cycles = self.call();
+            self.call();
         } else {
-            self.pc = self.pc.wrapping_add(3);
+            self.read_u16();
         }
-        cycles
     }
 
-    fn call(&mut self) -> u8 {
-        let cycles = 24;
+    fn call(&mut self) {
         let jp = self.read_u16();
+        self.tick();
         let pos = self.pc.to_be_bytes();
         self.push(pos[0], pos[1]);
         self.pc = jp;
-        cycles
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::cartridge::Cartridge;
+    use crate::dma::{DMA_ADDR, HDMA_BLOCK_MCYCLES};
+    use crate::memorymap::MemoryMap;
+    use crate::speed::{KEY1_ADDR, SWITCH_MCYCLES};
+    use crate::timer::{TAC_ADDR, TIMA_ADDR, TMA_ADDR};
+
+    impl<B: Bus> Cpu<B> {
+        /// Cycles a handler takes when called directly, counting the
+        /// opcode fetch execute makes before dispatching to it.
+        fn timed(&mut self, handler: impl FnOnce(&mut Self)) -> u8 {
+            let start = self.mcycles;
+            handler(self);
+            ((self.mcycles - start + 1) * 4) as u8
+        }
+
+        /// As timed, for handlers behind the CB prefix.
+        fn timed_cb(&mut self, handler: impl FnOnce(&mut Self)) -> u8 {
+            self.timed(handler) + 4
+        }
+    }
 
     #[test]
     fn nop() {
-        let mut memmap = MemoryMap::default();
-        let mut cpu = Cpu::load(&mut memmap);
+        let memmap = MemoryMap::default();
+        let mut cpu = Cpu::load(&memmap);
 
         let pc1 = cpu.pc;
-        let cycles = cpu.nop();
+        let cycles = cpu.timed(|cpu| cpu.nop());
         let pc2 = cpu.pc;
         assert_eq!(pc2, pc1 + 1);
         assert_eq!(cycles, 4);
@@ -1343,28 +1397,28 @@ mod tests {
 
     #[test]
     fn rrca() {
-        let mut memmap = MemoryMap::default();
-        let mut cpu = Cpu::load(&mut memmap);
+        let memmap = MemoryMap::default();
+        let mut cpu = Cpu::load(&memmap);
 
         cpu.reg.a = 0b0000_0001;
-        let cycles = cpu.rrca();
+        let cycles = cpu.timed(|cpu| cpu.rrca());
 
         assert_eq!(cpu.reg.a, 0b1000_0000);
-        assert_eq!(cpu.reg.is_c(), true);
+        assert!(cpu.reg.is_c());
         assert_eq!(cycles, 4);
     }
 
     #[test]
     fn rr_n() {
-        let mut memmap = MemoryMap::default();
-        let mut cpu = Cpu::load(&mut memmap);
+        let memmap = MemoryMap::default();
+        let mut cpu = Cpu::load(&memmap);
 
         macro_rules! rr {
             ($x:expr, $reg:expr) => {
                 $x = 0b0000_0001;
-                assert_eq!(cpu.rr_n($reg), 4);
+                assert_eq!(cpu.timed_cb(|cpu| cpu.rr_n($reg)), 8);
                 assert_eq!($x, 0b1000_0000);
-                assert_eq!(cpu.reg.is_c(), true);
+                assert!(cpu.reg.is_c());
             };
         }
         rr!(cpu.reg.a, StdReg::A);
@@ -1374,18 +1428,177 @@ mod tests {
         rr!(cpu.reg.e, StdReg::E);
         rr!(cpu.reg.h, StdReg::H);
         rr!(cpu.reg.l, StdReg::L);
+
+        cpu.reg.set_hl(0x8100);
+        cpu.mem.write_byte(0x8100, 0b0000_0001).unwrap();
+        cpu.reg.unset_c();
+        assert_eq!(cpu.timed_cb(|cpu| cpu.rr_n(StdReg::HL)), 16);
+        assert_eq!(cpu.mem.read_byte(0x8100).unwrap(), 0x00);
+        assert!(cpu.reg.is_z());
+        assert!(cpu.reg.is_c());
+    }
+
+    #[test]
+    fn rra() {
+        let memmap = MemoryMap::default();
+        let mut cpu = Cpu::load(&memmap);
+
+        cpu.reg.a = 0b0000_0001;
+        cpu.reg.unset_c();
+        assert_eq!(cpu.timed(|cpu| cpu.rra()), 4);
+        assert_eq!(cpu.reg.a, 0x00);
+        assert!(!cpu.reg.is_z());
+        assert!(cpu.reg.is_c());
+
+        assert_eq!(cpu.timed(|cpu| cpu.rra()), 4);
+        assert_eq!(cpu.reg.a, 0b1000_0000);
+        assert!(!cpu.reg.is_c());
+    }
+
+    #[test]
+    fn rotates_and_shifts() {
+        let memmap = MemoryMap::default();
+        let mut cpu = Cpu::load(&memmap);
+
+        macro_rules! shift {
+            ($op:ident, $input:expr, $carry_in:expr, $output:expr, $flags:expr) => {
+                cpu.reg.b = $input;
+                cpu.reg.f = if $carry_in { 0b0001_0000 } else { 0x00 };
+                assert_eq!(cpu.timed_cb(|cpu| cpu.$op(StdReg::B)), 8);
+                assert_eq!(cpu.reg.b, $output);
+                assert_eq!(cpu.reg.f, $flags);
+            };
+        }
+
+        shift!(rlc_n, 0b1000_0101, false, 0b0000_1011, 0b0001_0000);
+        shift!(rlc_n, 0x00, true, 0x00, 0b1000_0000);
+        shift!(rrc_n, 0b0000_0001, false, 0b1000_0000, 0b0001_0000);
+        shift!(rrc_n, 0b0000_0010, true, 0b0000_0001, 0b0000_0000);
+        shift!(rl_n, 0b1000_0000, false, 0x00, 0b1001_0000);
+        shift!(rl_n, 0b0001_0001, true, 0b0010_0011, 0b0000_0000);
+        shift!(rr_n, 0b0000_0001, false, 0x00, 0b1001_0000);
+        shift!(rr_n, 0b1000_1010, true, 0b1100_0101, 0b0000_0000);
+        shift!(sla, 0b1000_0000, false, 0x00, 0b1001_0000);
+        shift!(sla, 0b1111_1111, false, 0b1111_1110, 0b0001_0000);
+        shift!(sra, 0b1000_1010, false, 0b1100_0101, 0b0000_0000);
+        shift!(sra, 0b0000_0001, true, 0x00, 0b1001_0000);
+        shift!(swap, 0xF0, true, 0x0F, 0b0000_0000);
+        shift!(swap, 0x00, true, 0x00, 0b1000_0000);
+        shift!(srl, 0b0000_0001, false, 0x00, 0b1001_0000);
+        shift!(srl, 0b1111_1111, false, 0b0111_1111, 0b0001_0000);
+        shift!(srl, 0b1000_0000, true, 0b0100_0000, 0b0000_0000);
+    }
+
+    #[test]
+    fn shift_all_operands() {
+        let memmap = MemoryMap::default();
+        let mut cpu = Cpu::load(&memmap);
+
+        macro_rules! swap {
+            ($r:expr, $reg:expr) => {
+                $r = 0x12;
+                assert_eq!(cpu.timed_cb(|cpu| cpu.swap($reg)), 8);
+                assert_eq!($r, 0x21);
+            };
+        }
+        swap!(cpu.reg.a, StdReg::A);
+        swap!(cpu.reg.b, StdReg::B);
+        swap!(cpu.reg.c, StdReg::C);
+        swap!(cpu.reg.d, StdReg::D);
+        swap!(cpu.reg.e, StdReg::E);
+        swap!(cpu.reg.h, StdReg::H);
+        swap!(cpu.reg.l, StdReg::L);
+
+        cpu.reg.set_hl(0x8100);
+        cpu.mem.write_byte(0x8100, 0x34).unwrap();
+        assert_eq!(cpu.timed_cb(|cpu| cpu.swap(StdReg::HL)), 16);
+        assert_eq!(cpu.mem.read_byte(0x8100).unwrap(), 0x43);
+        assert_eq!(cpu.timed_cb(|cpu| cpu.sla(StdReg::HL)), 16);
+        assert_eq!(cpu.mem.read_byte(0x8100).unwrap(), 0x86);
+        assert_eq!(cpu.timed_cb(|cpu| cpu.sra(StdReg::HL)), 16);
+        assert_eq!(cpu.mem.read_byte(0x8100).unwrap(), 0xC3);
+        assert_eq!(cpu.timed_cb(|cpu| cpu.srl(StdReg::HL)), 16);
+        assert_eq!(cpu.mem.read_byte(0x8100).unwrap(), 0x61);
+        assert_eq!(cpu.timed_cb(|cpu| cpu.rlc_n(StdReg::HL)), 16);
+        assert_eq!(cpu.mem.read_byte(0x8100).unwrap(), 0xC2);
+        assert_eq!(cpu.timed_cb(|cpu| cpu.rrc_n(StdReg::HL)), 16);
+        assert_eq!(cpu.mem.read_byte(0x8100).unwrap(), 0x61);
+        assert!(!cpu.reg.is_c());
+        assert_eq!(cpu.timed_cb(|cpu| cpu.rl_n(StdReg::HL)), 16);
+        assert_eq!(cpu.mem.read_byte(0x8100).unwrap(), 0xC2);
+    }
+
+    #[test]
+    fn bit_res_set() {
+        let memmap = MemoryMap::default();
+        let mut cpu = Cpu::load(&memmap);
+
+        cpu.reg.f = 0b0101_0000;
+        cpu.reg.d = 0b0000_1000;
+        assert_eq!(cpu.timed_cb(|cpu| cpu.bit(3, StdReg::D)), 8);
+        assert_eq!(cpu.reg.f, 0b0011_0000);
+        assert_eq!(cpu.timed_cb(|cpu| cpu.bit(4, StdReg::D)), 8);
+        assert_eq!(cpu.reg.f, 0b1011_0000);
+
+        cpu.reg.set_hl(0x8100);
+        cpu.mem.write_byte(0x8100, 0x80).unwrap();
+        cpu.reg.f = 0x00;
+        assert_eq!(cpu.timed_cb(|cpu| cpu.bit(7, StdReg::HL)), 12);
+        assert_eq!(cpu.reg.f, 0b0010_0000);
+
+        assert_eq!(cpu.timed_cb(|cpu| cpu.res(7, StdReg::HL)), 16);
+        assert_eq!(cpu.mem.read_byte(0x8100).unwrap(), 0x00);
+        assert_eq!(cpu.timed_cb(|cpu| cpu.set(0, StdReg::HL)), 16);
+        assert_eq!(cpu.mem.read_byte(0x8100).unwrap(), 0x01);
+        assert_eq!(cpu.reg.f, 0b0010_0000);
+
+        for bit in 0..8 {
+            cpu.reg.a = 0x00;
+            assert_eq!(cpu.timed_cb(|cpu| cpu.set(bit, StdReg::A)), 8);
+            assert_eq!(cpu.reg.a, 1 << bit);
+            cpu.reg.a = 0xFF;
+            assert_eq!(cpu.timed_cb(|cpu| cpu.res(bit, StdReg::A)), 8);
+            assert_eq!(cpu.reg.a, !(1 << bit));
+        }
+    }
+
+    #[test]
+    fn step_cb_prefix() {
+        let memmap = MemoryMap::default();
+        let mut cpu = Cpu::load(&memmap);
+
+        // SWAP A; BIT 7,(HL); SET 7,(HL); RES 0,C; SRL (HL)
+        let program = [0xCB, 0x37, 0xCB, 0x7E, 0xCB, 0xFE, 0xCB, 0x81, 0xCB, 0x3E];
+        for (i, b) in program.iter().enumerate() {
+            memmap.patch_byte(0x0100 + i as u16, *b).unwrap();
+        }
+        cpu.reg.a = 0xA5;
+        cpu.reg.c = 0xFF;
+        cpu.reg.set_hl(0xC000);
+
+        assert_eq!(cpu.step().unwrap(), 8);
+        assert_eq!(cpu.reg.a, 0x5A);
+        assert_eq!(cpu.step().unwrap(), 12);
+        assert!(cpu.reg.is_z());
+        assert_eq!(cpu.step().unwrap(), 16);
+        assert_eq!(memmap.read_byte(0xC000).unwrap(), 0x80);
+        assert_eq!(cpu.step().unwrap(), 8);
+        assert_eq!(cpu.reg.c, 0xFE);
+        assert_eq!(cpu.step().unwrap(), 16);
+        assert_eq!(memmap.read_byte(0xC000).unwrap(), 0x40);
+        assert_eq!(cpu.pc, 0x010A);
     }
 
     #[test]
     fn ret() {
-        let mut memmap = MemoryMap::default();
-        let mut cpu = Cpu::load(&mut memmap);
+        let memmap = MemoryMap::default();
+        let mut cpu = Cpu::load(&memmap);
 
         let lo = 0x50;
         let hi = 0x80;
 
         cpu.push(lo, hi);
-        let cycles = cpu.ret();
+        let cycles = cpu.timed(|cpu| cpu.ret());
 
         assert_eq!(cpu.pc, 0x5080);
         assert_eq!(cycles, 16);
@@ -1393,13 +1606,13 @@ mod tests {
 
     #[test]
     fn ld_a_n() {
-        let mut memmap = MemoryMap::default();
-        let mut cpu = Cpu::load(&mut memmap);
+        let memmap = MemoryMap::default();
+        let mut cpu = Cpu::load(&memmap);
 
         cpu.pc = 0x8050;
         cpu.reg.a = 0x00;
         cpu.reg.b = 0xFF;
-        let cycles = cpu.ld_a_n(LoadReg::B);
+        let cycles = cpu.timed(|cpu| cpu.ld_a_n(LoadReg::B));
         assert_eq!(cycles, 4);
         assert_eq!(cpu.reg.a, cpu.reg.b);
         assert_eq!(cpu.pc, 0x8051);
@@ -1408,54 +1621,54 @@ mod tests {
         cpu.reg.c = 0x80;
         cpu.mem.write_byte(0x8080, 0xFF).unwrap();
         cpu.reg.a = 0x00;
-        let cycles = cpu.ld_a_n(LoadReg::MemBC);
+        let cycles = cpu.timed(|cpu| cpu.ld_a_n(LoadReg::MemBC));
         assert_eq!(cycles, 8);
         assert_eq!(cpu.reg.a, 0xFF);
 
         cpu.mem.write_byte(0x8080, 0xFF).unwrap();
         cpu.reg.a = 0x00;
-        let cycles = cpu.ld_a_n(LoadReg::MemNN);
+        let cycles = cpu.timed(|cpu| cpu.ld_a_n(LoadReg::MemNN));
         assert_eq!(cycles, 16);
         assert_eq!(cpu.reg.a, 0x00);
     }
 
     #[test]
     fn ld_n_nn() {
-        let mut memmap = MemoryMap::default();
-        let mut cpu = Cpu::load(&mut memmap);
+        let memmap = MemoryMap::default();
+        let mut cpu = Cpu::load(&memmap);
 
         cpu.pc = 0x8200;
-        cpu.mem.write_byte(cpu.pc + 1, 0xFF).unwrap();
-        cpu.mem.write_byte(cpu.pc + 2, 0xAA).unwrap();
-        assert_eq!(cpu.ld_n_nn(LoadRegnnn::BC), 3 * 4);
+        cpu.mem.patch_byte(cpu.pc + 1, 0xFF).unwrap();
+        cpu.mem.patch_byte(cpu.pc + 2, 0xAA).unwrap();
+        assert_eq!(cpu.timed(|cpu| cpu.ld_n_nn(LoadRegnnn::BC)), 3 * 4);
         assert_eq!(cpu.reg.get_bc(), 0xAAFF);
         cpu.pc = 0x8200;
-        cpu.mem.write_byte(cpu.pc + 1, 0xFF).unwrap();
-        cpu.mem.write_byte(cpu.pc + 2, 0xAA).unwrap();
-        assert_eq!(cpu.ld_n_nn(LoadRegnnn::DE), 3 * 4);
+        cpu.mem.patch_byte(cpu.pc + 1, 0xFF).unwrap();
+        cpu.mem.patch_byte(cpu.pc + 2, 0xAA).unwrap();
+        assert_eq!(cpu.timed(|cpu| cpu.ld_n_nn(LoadRegnnn::DE)), 3 * 4);
         assert_eq!(cpu.reg.get_de(), 0xAAFF);
         cpu.pc = 0x8200;
-        cpu.mem.write_byte(cpu.pc + 1, 0xFF).unwrap();
-        cpu.mem.write_byte(cpu.pc + 2, 0xAA).unwrap();
-        assert_eq!(cpu.ld_n_nn(LoadRegnnn::HL), 3 * 4);
+        cpu.mem.patch_byte(cpu.pc + 1, 0xFF).unwrap();
+        cpu.mem.patch_byte(cpu.pc + 2, 0xAA).unwrap();
+        assert_eq!(cpu.timed(|cpu| cpu.ld_n_nn(LoadRegnnn::HL)), 3 * 4);
         assert_eq!(cpu.reg.get_hl(), 0xAAFF);
         cpu.pc = 0x8200;
-        cpu.mem.write_byte(cpu.pc + 1, 0xFF).unwrap();
-        cpu.mem.write_byte(cpu.pc + 2, 0xAA).unwrap();
-        assert_eq!(cpu.ld_n_nn(LoadRegnnn::SP), 3 * 4);
+        cpu.mem.patch_byte(cpu.pc + 1, 0xFF).unwrap();
+        cpu.mem.patch_byte(cpu.pc + 2, 0xAA).unwrap();
+        assert_eq!(cpu.timed(|cpu| cpu.ld_n_nn(LoadRegnnn::SP)), 3 * 4);
         assert_eq!(cpu.sp, 0xAAFF);
     }
 
     #[test]
     fn ld_n_a() {
-        let mut memmap = MemoryMap::default();
-        let mut cpu = Cpu::load(&mut memmap);
+        let memmap = MemoryMap::default();
+        let mut cpu = Cpu::load(&memmap);
 
         macro_rules! ld {
             ($x:expr, $reg:expr) => {
                 cpu.reg.a = 0xFF;
                 $x = 0xDD;
-                assert_eq!(cpu.ld_n_a($reg), 4);
+                assert_eq!(cpu.timed(|cpu| cpu.ld_n_a($reg)), 4);
                 assert_eq!(cpu.reg.a, $x);
             };
         }
@@ -1477,13 +1690,13 @@ mod tests {
                     LoadReg::MemHL => cpu.reg.set_hl(0x8100),
                     LoadReg::MemNN => {
                         cpu.pc = 0x8500;
-                        cpu.mem.write_byte(cpu.pc + 1, 0x00).unwrap();
-                        cpu.mem.write_byte(cpu.pc + 2, 0x81).unwrap();
+                        cpu.mem.patch_byte(cpu.pc + 1, 0x00).unwrap();
+                        cpu.mem.patch_byte(cpu.pc + 2, 0x81).unwrap();
                     }
                     _ => cpu.reg.a = 0xAA,
                 }
                 cpu.mem.write_byte(0x8100, 0xAA).unwrap();
-                assert_eq!(cpu.ld_n_a($reg), $cycles);
+                assert_eq!(cpu.timed(|cpu| cpu.ld_n_a($reg)), $cycles);
                 assert_eq!(cpu.mem.read_byte(0x8100).unwrap(), 0xAA);
             };
         }
@@ -1495,13 +1708,13 @@ mod tests {
 
     #[test]
     fn ld_r_r() {
-        let mut memmap = MemoryMap::default();
-        let mut cpu = Cpu::load(&mut memmap);
+        let memmap = MemoryMap::default();
+        let mut cpu = Cpu::load(&memmap);
         macro_rules! ld {
             ($x:expr, $y:expr, $reg1:expr, $reg2:expr) => {
                 $x = 0xDD;
                 $y = 0xFF;
-                assert_eq!(cpu.ld_r_r($reg1, $reg2), 4);
+                assert_eq!(cpu.timed(|cpu| cpu.ld_r_r($reg1, $reg2)), 4);
                 assert_eq!($x, $y);
             };
         }
@@ -1548,7 +1761,7 @@ mod tests {
                 $r = 0xAA;
                 cpu.reg.set_hl(0x8100);
                 cpu.mem.write_byte(cpu.reg.get_hl(), 0xAA).unwrap();
-                assert_eq!(cpu.ld_r_r($reg, StdReg::HL), 8);
+                assert_eq!(cpu.timed(|cpu| cpu.ld_r_r($reg, StdReg::HL)), 8);
                 assert_eq!($r, 0xAA);
             };
         }
@@ -1564,7 +1777,7 @@ mod tests {
             ($r:expr, $reg:expr) => {
                 $r = 0xAA;
                 cpu.reg.set_hl(0x8100);
-                assert_eq!(cpu.ld_r_r(StdReg::HL, $reg), 8);
+                assert_eq!(cpu.timed(|cpu| cpu.ld_r_r(StdReg::HL, $reg)), 8);
                 assert_eq!(cpu.mem.read_byte(0x8100).unwrap(), 0xAA);
             };
         }
@@ -1577,8 +1790,8 @@ mod tests {
 
     #[test]
     fn sub_a_r() {
-        let mut memmap = MemoryMap::default();
-        let mut cpu = Cpu::load(&mut memmap);
+        let memmap = MemoryMap::default();
+        let mut cpu = Cpu::load(&memmap);
 
         macro_rules! sub {
             ($r:expr) => {
@@ -1586,11 +1799,11 @@ mod tests {
                 cpu.reg.unset_h();
                 cpu.reg.unset_n();
                 cpu.reg.a = 20;
-                assert_eq!(cpu.sub_a_r($r), 4);
+                assert_eq!(cpu.timed(|cpu| cpu.sub_a_r($r)), 4);
                 assert_eq!(cpu.reg.a, 19);
-                assert_eq!(cpu.reg.is_z(), false);
-                assert_eq!(cpu.reg.is_h(), false);
-                assert_eq!(cpu.reg.is_n(), true);
+                assert!(!cpu.reg.is_z());
+                assert!(!cpu.reg.is_h());
+                assert!(cpu.reg.is_n());
             };
         }
 
@@ -1608,55 +1821,55 @@ mod tests {
         sub!(StdRegN::L);
 
         cpu.pc = 0x8200;
-        cpu.mem.write_byte(cpu.pc + 1, 1).unwrap();
+        cpu.mem.patch_byte(cpu.pc + 1, 1).unwrap();
         cpu.reg.unset_z();
         cpu.reg.unset_h();
         cpu.reg.unset_n();
         cpu.reg.unset_c();
         cpu.reg.a = 20;
-        assert_eq!(cpu.sub_a_r(StdRegN::N), 8);
+        assert_eq!(cpu.timed(|cpu| cpu.sub_a_r(StdRegN::N)), 8);
         assert_eq!(cpu.reg.a, 19);
-        assert_eq!(cpu.reg.is_z(), false);
-        assert_eq!(cpu.reg.is_h(), false);
-        assert_eq!(cpu.reg.is_n(), true);
-        assert_eq!(cpu.reg.is_c(), false);
+        assert!(!cpu.reg.is_z());
+        assert!(!cpu.reg.is_h());
+        assert!(cpu.reg.is_n());
+        assert!(!cpu.reg.is_c());
         assert_eq!(cpu.pc, 0x8200 + 0x02);
 
         cpu.pc = 0x8200;
-        cpu.mem.write_byte(cpu.pc + 1, 0x08).unwrap();
+        cpu.mem.patch_byte(cpu.pc + 1, 0x08).unwrap();
         cpu.reg.unset_z();
         cpu.reg.unset_h();
         cpu.reg.unset_n();
         cpu.reg.unset_c();
         cpu.reg.a = 0xf8;
-        assert_eq!(cpu.sub_a_r(StdRegN::N), 8);
+        assert_eq!(cpu.timed(|cpu| cpu.sub_a_r(StdRegN::N)), 8);
         assert_eq!(cpu.reg.a, 0xf0);
-        assert_eq!(cpu.reg.is_z(), false);
-        assert_eq!(cpu.reg.is_h(), false);
-        assert_eq!(cpu.reg.is_n(), true);
-        assert_eq!(cpu.reg.is_c(), false);
+        assert!(!cpu.reg.is_z());
+        assert!(!cpu.reg.is_h());
+        assert!(cpu.reg.is_n());
+        assert!(!cpu.reg.is_c());
         assert_eq!(cpu.pc, 0x8200 + 0x02);
 
         cpu.pc = 0x8200;
-        cpu.mem.write_byte(cpu.pc + 1, 0x05).unwrap();
+        cpu.mem.patch_byte(cpu.pc + 1, 0x05).unwrap();
         cpu.reg.unset_z();
         cpu.reg.unset_h();
         cpu.reg.unset_n();
         cpu.reg.unset_c();
         cpu.reg.a = 0xDF;
-        assert_eq!(cpu.sub_a_r(StdRegN::N), 8);
+        assert_eq!(cpu.timed(|cpu| cpu.sub_a_r(StdRegN::N)), 8);
         assert_eq!(cpu.reg.a, 0xDA);
-        assert_eq!(cpu.reg.is_z(), false);
-        assert_eq!(cpu.reg.is_h(), false);
-        assert_eq!(cpu.reg.is_n(), true);
-        assert_eq!(cpu.reg.is_c(), false);
+        assert!(!cpu.reg.is_z());
+        assert!(!cpu.reg.is_h());
+        assert!(cpu.reg.is_n());
+        assert!(!cpu.reg.is_c());
         assert_eq!(cpu.pc, 0x8200 + 0x02);
     }
 
     #[test]
     fn add_a_r() {
-        let mut memmap = MemoryMap::default();
-        let mut cpu = Cpu::load(&mut memmap);
+        let memmap = MemoryMap::default();
+        let mut cpu = Cpu::load(&memmap);
 
         macro_rules! add {
             ($r:expr) => {
@@ -1664,11 +1877,11 @@ mod tests {
                 cpu.reg.unset_h();
                 cpu.reg.unset_n();
                 cpu.reg.a = 20;
-                assert_eq!(cpu.add_a_r($r), 4);
+                assert_eq!(cpu.timed(|cpu| cpu.add_a_r($r)), 4);
                 assert_eq!(cpu.reg.a, 21);
-                assert_eq!(cpu.reg.is_z(), false);
-                assert_eq!(cpu.reg.is_h(), false);
-                assert_eq!(cpu.reg.is_n(), false);
+                assert!(!cpu.reg.is_z());
+                assert!(!cpu.reg.is_h());
+                assert!(!cpu.reg.is_n());
             };
         }
 
@@ -1686,40 +1899,40 @@ mod tests {
         add!(StdRegN::L);
 
         cpu.pc = 0x8200;
-        cpu.mem.write_byte(cpu.pc + 1, 1).unwrap();
+        cpu.mem.patch_byte(cpu.pc + 1, 1).unwrap();
         cpu.reg.unset_z();
         cpu.reg.unset_h();
         cpu.reg.unset_n();
         cpu.reg.unset_c();
         cpu.reg.a = 20;
-        assert_eq!(cpu.add_a_r(StdRegN::N), 8);
+        assert_eq!(cpu.timed(|cpu| cpu.add_a_r(StdRegN::N)), 8);
         assert_eq!(cpu.reg.a, 21);
-        assert_eq!(cpu.reg.is_z(), false);
-        assert_eq!(cpu.reg.is_h(), false);
-        assert_eq!(cpu.reg.is_n(), false);
-        assert_eq!(cpu.reg.is_c(), false);
+        assert!(!cpu.reg.is_z());
+        assert!(!cpu.reg.is_h());
+        assert!(!cpu.reg.is_n());
+        assert!(!cpu.reg.is_c());
         assert_eq!(cpu.pc, 0x8200 + 0x02);
 
         cpu.pc = 0x8200;
-        cpu.mem.write_byte(cpu.pc + 1, 0x08).unwrap();
+        cpu.mem.patch_byte(cpu.pc + 1, 0x08).unwrap();
         cpu.reg.unset_z();
         cpu.reg.unset_h();
         cpu.reg.unset_n();
         cpu.reg.unset_c();
         cpu.reg.a = 0xf8;
-        assert_eq!(cpu.add_a_r(StdRegN::N), 8);
+        assert_eq!(cpu.timed(|cpu| cpu.add_a_r(StdRegN::N)), 8);
         assert_eq!(cpu.reg.a, 0);
-        assert_eq!(cpu.reg.is_z(), true);
-        assert_eq!(cpu.reg.is_h(), true);
-        assert_eq!(cpu.reg.is_n(), false);
-        assert_eq!(cpu.reg.is_c(), true);
+        assert!(cpu.reg.is_z());
+        assert!(cpu.reg.is_h());
+        assert!(!cpu.reg.is_n());
+        assert!(cpu.reg.is_c());
         assert_eq!(cpu.pc, 0x8200 + 0x02);
     }
 
     #[test]
     fn dec_r() {
-        let mut memmap = MemoryMap::default();
-        let mut cpu = Cpu::load(&mut memmap);
+        let memmap = MemoryMap::default();
+        let mut cpu = Cpu::load(&memmap);
 
         macro_rules! dec {
             ($r:expr, $reg:expr) => {
@@ -1727,11 +1940,11 @@ mod tests {
                 cpu.reg.unset_h();
                 cpu.reg.unset_n();
                 $r = 20;
-                assert_eq!(cpu.dec_r($reg), 4);
+                assert_eq!(cpu.timed(|cpu| cpu.dec_r($reg)), 4);
                 assert_eq!($r, 19);
-                assert_eq!(cpu.reg.is_z(), false);
-                assert_eq!(cpu.reg.is_h(), false);
-                assert_eq!(cpu.reg.is_n(), true);
+                assert!(!cpu.reg.is_z());
+                assert!(!cpu.reg.is_h());
+                assert!(cpu.reg.is_n());
             };
         }
 
@@ -1747,17 +1960,17 @@ mod tests {
         cpu.reg.unset_h();
         cpu.reg.set_n();
         cpu.reg.a = 0x01;
-        assert_eq!(cpu.dec_r(StdReg::A), 4);
+        assert_eq!(cpu.timed(|cpu| cpu.dec_r(StdReg::A)), 4);
         assert_eq!(cpu.reg.a, 0);
-        assert_eq!(cpu.reg.is_z(), true);
-        assert_eq!(cpu.reg.is_h(), false);
-        assert_eq!(cpu.reg.is_n(), true);
+        assert!(cpu.reg.is_z());
+        assert!(!cpu.reg.is_h());
+        assert!(cpu.reg.is_n());
     }
 
     #[test]
     fn inc_reg() {
-        let mut memmap = MemoryMap::default();
-        let mut cpu = Cpu::load(&mut memmap);
+        let memmap = MemoryMap::default();
+        let mut cpu = Cpu::load(&memmap);
 
         macro_rules! inc {
             ($r:expr, $reg:expr) => {
@@ -1765,11 +1978,11 @@ mod tests {
                 cpu.reg.unset_h();
                 cpu.reg.set_n();
                 $r = 0b0000_1111;
-                assert_eq!(cpu.inc_reg($reg), 4);
+                assert_eq!(cpu.timed(|cpu| cpu.inc_reg($reg)), 4);
                 assert_eq!($r, 16);
-                assert_eq!(cpu.reg.is_z(), false);
-                assert_eq!(cpu.reg.is_h(), true);
-                assert_eq!(cpu.reg.is_n(), false);
+                assert!(!cpu.reg.is_z());
+                assert!(cpu.reg.is_h());
+                assert!(!cpu.reg.is_n());
             };
         }
         inc!(cpu.reg.a, IncDecReg::A);
@@ -1784,33 +1997,33 @@ mod tests {
         cpu.reg.unset_h();
         cpu.reg.set_n();
         cpu.reg.a = 0b1111_1111;
-        assert_eq!(cpu.inc_reg(IncDecReg::A), 4);
+        assert_eq!(cpu.timed(|cpu| cpu.inc_reg(IncDecReg::A)), 4);
         assert_eq!(cpu.reg.a, 0);
-        assert_eq!(cpu.reg.is_z(), true);
-        assert_eq!(cpu.reg.is_h(), true);
-        assert_eq!(cpu.reg.is_n(), false);
+        assert!(cpu.reg.is_z());
+        assert!(cpu.reg.is_h());
+        assert!(!cpu.reg.is_n());
 
         cpu.reg.set_bc(0x0FFF);
-        assert_eq!(cpu.inc_reg(IncDecReg::BC), 8);
+        assert_eq!(cpu.timed(|cpu| cpu.inc_reg(IncDecReg::BC)), 8);
         assert_eq!(cpu.reg.get_bc(), 0x0FFF + 1);
 
         cpu.reg.set_hl(0x0FFF);
-        assert_eq!(cpu.inc_reg(IncDecReg::HL), 8);
+        assert_eq!(cpu.timed(|cpu| cpu.inc_reg(IncDecReg::HL)), 8);
         assert_eq!(cpu.reg.get_hl(), 0x0FFF + 1);
 
         cpu.reg.set_de(0x0FFF);
-        assert_eq!(cpu.inc_reg(IncDecReg::DE), 8);
+        assert_eq!(cpu.timed(|cpu| cpu.inc_reg(IncDecReg::DE)), 8);
         assert_eq!(cpu.reg.get_de(), 0x0FFF + 1);
 
         cpu.sp = 0x0FFF;
-        assert_eq!(cpu.inc_reg(IncDecReg::SP), 8);
+        assert_eq!(cpu.timed(|cpu| cpu.inc_reg(IncDecReg::SP)), 8);
         assert_eq!(cpu.sp, 0x0FFF + 0x0001);
     }
 
     #[test]
     fn ret_cc() {
-        let mut memmap = MemoryMap::default();
-        let mut cpu = Cpu::load(&mut memmap);
+        let memmap = MemoryMap::default();
+        let mut cpu = Cpu::load(&memmap);
 
         let lo = 0x50;
         let hi = 0x80;
@@ -1818,13 +2031,13 @@ mod tests {
 
         cpu.pc = 0x0000;
         cpu.reg.set_z();
-        let cycles = cpu.ret_cc(FlagCond::NZ);
+        let cycles = cpu.timed(|cpu| cpu.ret_cc(FlagCond::NZ));
         assert_eq!(cpu.pc, 0x0001);
         assert_eq!(cycles, 8);
 
         cpu.pc = 0x0000;
         cpu.reg.unset_z();
-        let cycles = cpu.ret_cc(FlagCond::NZ);
+        let cycles = cpu.timed(|cpu| cpu.ret_cc(FlagCond::NZ));
         assert_eq!(cpu.pc, 0x5080);
         assert_eq!(cycles, 20);
 
@@ -1834,13 +2047,13 @@ mod tests {
 
         cpu.pc = 0x0000;
         cpu.reg.unset_z();
-        let cycles = cpu.ret_cc(FlagCond::Z);
+        let cycles = cpu.timed(|cpu| cpu.ret_cc(FlagCond::Z));
         assert_eq!(cpu.pc, 0x0001);
         assert_eq!(cycles, 8);
 
         cpu.pc = 0x0000;
         cpu.reg.set_z();
-        let cycles = cpu.ret_cc(FlagCond::Z);
+        let cycles = cpu.timed(|cpu| cpu.ret_cc(FlagCond::Z));
         assert_eq!(cpu.pc, 0x5080);
         assert_eq!(cycles, 20);
 
@@ -1850,13 +2063,13 @@ mod tests {
 
         cpu.pc = 0x0000;
         cpu.reg.set_c();
-        let cycles = cpu.ret_cc(FlagCond::NC);
+        let cycles = cpu.timed(|cpu| cpu.ret_cc(FlagCond::NC));
         assert_eq!(cpu.pc, 0x0001);
         assert_eq!(cycles, 8);
 
         cpu.pc = 0x0000;
         cpu.reg.unset_c();
-        let cycles = cpu.ret_cc(FlagCond::NC);
+        let cycles = cpu.timed(|cpu| cpu.ret_cc(FlagCond::NC));
         assert_eq!(cpu.pc, 0x5080);
         assert_eq!(cycles, 20);
 
@@ -1866,21 +2079,21 @@ mod tests {
 
         cpu.pc = 0x0000;
         cpu.reg.unset_c();
-        let cycles = cpu.ret_cc(FlagCond::C);
+        let cycles = cpu.timed(|cpu| cpu.ret_cc(FlagCond::C));
         assert_eq!(cpu.pc, 0x0001);
         assert_eq!(cycles, 8);
 
         cpu.pc = 0x0000;
         cpu.reg.set_c();
-        let cycles = cpu.ret_cc(FlagCond::C);
+        let cycles = cpu.timed(|cpu| cpu.ret_cc(FlagCond::C));
         assert_eq!(cpu.pc, 0x5080);
         assert_eq!(cycles, 20);
     }
 
     #[test]
     fn pop_from_stack() {
-        let mut memmap = MemoryMap::default();
-        let mut cpu = Cpu::load(&mut memmap);
+        let memmap = MemoryMap::default();
+        let mut cpu = Cpu::load(&memmap);
 
         let lo = 0xFF;
         let hi = 0xDD;
@@ -1895,8 +2108,8 @@ mod tests {
 
     #[test]
     fn push_to_stack() {
-        let mut memmap = MemoryMap::default();
-        let mut cpu = Cpu::load(&mut memmap);
+        let memmap = MemoryMap::default();
+        let mut cpu = Cpu::load(&memmap);
 
         let pc1 = cpu.mem.read_byte(cpu.pc).unwrap();
         let pc2 = cpu.mem.read_byte(cpu.pc + 1).unwrap();
@@ -1912,37 +2125,37 @@ mod tests {
 
     #[test]
     fn rst() {
-        let mut memmap = MemoryMap::default();
-        let mut cpu = Cpu::load(&mut memmap);
+        let memmap = MemoryMap::default();
+        let mut cpu = Cpu::load(&memmap);
 
-        assert_eq!(cpu.rst_00(), 16);
+        assert_eq!(cpu.timed(|cpu| cpu.rst(0x0000)), 16);
         assert_eq!(cpu.pc, 0x0000);
-        assert_eq!(cpu.rst_08(), 16);
+        assert_eq!(cpu.timed(|cpu| cpu.rst(0x0008)), 16);
         assert_eq!(cpu.pc, 0x0008);
-        assert_eq!(cpu.rst_10(), 16);
+        assert_eq!(cpu.timed(|cpu| cpu.rst(0x0010)), 16);
         assert_eq!(cpu.pc, 0x0010);
-        assert_eq!(cpu.rst_18(), 16);
+        assert_eq!(cpu.timed(|cpu| cpu.rst(0x0018)), 16);
         assert_eq!(cpu.pc, 0x0018);
-        assert_eq!(cpu.rst_20(), 16);
+        assert_eq!(cpu.timed(|cpu| cpu.rst(0x0020)), 16);
         assert_eq!(cpu.pc, 0x0020);
-        assert_eq!(cpu.rst_28(), 16);
+        assert_eq!(cpu.timed(|cpu| cpu.rst(0x0028)), 16);
         assert_eq!(cpu.pc, 0x0028);
-        assert_eq!(cpu.rst_30(), 16);
+        assert_eq!(cpu.timed(|cpu| cpu.rst(0x0030)), 16);
         assert_eq!(cpu.pc, 0x0030);
-        assert_eq!(cpu.rst_38(), 16);
+        assert_eq!(cpu.timed(|cpu| cpu.rst(0x0038)), 16);
         assert_eq!(cpu.pc, 0x0038);
     }
 
     #[test]
     fn call() {
-        let mut memmap = MemoryMap::default();
-        let mut cpu = Cpu::load(&mut memmap);
+        let memmap = MemoryMap::default();
+        let mut cpu = Cpu::load(&memmap);
 
         cpu.pc = 0x8200;
-        cpu.mem.write_byte(cpu.pc + 1, 0x88).unwrap();
-        cpu.mem.write_byte(cpu.pc + 2, 0x99).unwrap();
+        cpu.mem.patch_byte(cpu.pc + 1, 0x88).unwrap();
+        cpu.mem.patch_byte(cpu.pc + 2, 0x99).unwrap();
 
-        assert_eq!(cpu.call(), 24);
+        assert_eq!(cpu.timed(|cpu| cpu.call()), 24);
         assert_eq!(cpu.pc, 0x9988);
         assert_eq!(cpu.mem.read_byte(cpu.sp + 1).unwrap(), 0x82);
         assert_eq!(cpu.mem.read_byte(cpu.sp + 2).unwrap(), 0x00);
@@ -1950,17 +2163,17 @@ mod tests {
 
     #[test]
     fn call_cc() {
-        let mut memmap = MemoryMap::default();
-        let mut cpu = Cpu::load(&mut memmap);
+        let memmap = MemoryMap::default();
+        let mut cpu = Cpu::load(&memmap);
 
         macro_rules! call_success {
             ($reg:expr) => {
                 cpu.pc = 0x8200;
                 cpu.sp = 0xDFFE;
-                cpu.mem.write_byte(cpu.pc + 1, 0x88).unwrap();
-                cpu.mem.write_byte(cpu.pc + 2, 0x99).unwrap();
+                cpu.mem.patch_byte(cpu.pc + 1, 0x88).unwrap();
+                cpu.mem.patch_byte(cpu.pc + 2, 0x99).unwrap();
 
-                assert_eq!(cpu.call_cc($reg), 24);
+                assert_eq!(cpu.timed(|cpu| cpu.call_cc($reg)), 24);
                 assert_eq!(cpu.pc, 0x9988);
                 assert_eq!(cpu.mem.read_byte(cpu.sp + 1).unwrap(), 0x82);
                 assert_eq!(cpu.mem.read_byte(cpu.sp + 2).unwrap(), 0x00);
@@ -1979,11 +2192,11 @@ mod tests {
         macro_rules! call_fail {
             ($reg:expr) => {
                 cpu.pc = 0x8200;
-                cpu.mem.write_byte(cpu.pc + 1, 0x88).unwrap();
-                cpu.mem.write_byte(cpu.pc + 2, 0x99).unwrap();
+                cpu.mem.patch_byte(cpu.pc + 1, 0x88).unwrap();
+                cpu.mem.patch_byte(cpu.pc + 2, 0x99).unwrap();
 
                 cpu.reg.unset_z();
-                assert_eq!(cpu.call_cc(FlagCond::Z), 12);
+                assert_eq!(cpu.timed(|cpu| cpu.call_cc(FlagCond::Z)), 12);
                 assert_eq!(cpu.pc, 0x8200 + 3);
             };
         }
@@ -2000,97 +2213,1495 @@ mod tests {
 
     #[test]
     fn cp_a_n() {
-        let mut memmap = MemoryMap::default();
-        let mut cpu = Cpu::load(&mut memmap);
+        let memmap = MemoryMap::default();
+        let mut cpu = Cpu::load(&memmap);
 
         cpu.reg.unset_h();
         cpu.reg.unset_n();
         cpu.reg.a = 0b0001_0000;
         cpu.pc = 0x8100;
-        cpu.mem.write_byte(cpu.pc + 0x0001, 0b0000_0001).unwrap();
+        cpu.mem.patch_byte(cpu.pc + 0x0001, 0b0000_0001).unwrap();
 
-        assert_eq!(cpu.cp_a_n(), 4);
-        assert_eq!(cpu.reg.is_h(), true);
-        assert_eq!(cpu.reg.is_n(), true);
+        assert_eq!(cpu.timed(|cpu| cpu.cp_a_n()), 8);
+        assert!(cpu.reg.is_h());
+        assert!(cpu.reg.is_n());
 
         cpu.reg.unset_z();
         cpu.reg.unset_n();
         cpu.reg.a = 0b0000_0001;
         cpu.pc = 0x8100;
-        cpu.mem.write_byte(cpu.pc + 0x0001, 0b0000_0001).unwrap();
+        cpu.mem.patch_byte(cpu.pc + 0x0001, 0b0000_0001).unwrap();
 
-        assert_eq!(cpu.cp_a_n(), 4);
-        assert_eq!(cpu.reg.is_z(), true);
-        assert_eq!(cpu.reg.is_n(), true);
+        assert_eq!(cpu.timed(|cpu| cpu.cp_a_n()), 8);
+        assert!(cpu.reg.is_z());
+        assert!(cpu.reg.is_n());
 
         cpu.reg.unset_c();
         cpu.reg.unset_n();
         cpu.reg.a = 0b1000_0000;
         cpu.pc = 0x8100;
-        cpu.mem.write_byte(cpu.pc + 0x0001, 0b1100_0000).unwrap();
+        cpu.mem.patch_byte(cpu.pc + 0x0001, 0b1100_0000).unwrap();
 
-        assert_eq!(cpu.cp_a_n(), 4);
-        assert_eq!(cpu.reg.is_c(), true);
-        assert_eq!(cpu.reg.is_n(), true);
+        assert_eq!(cpu.timed(|cpu| cpu.cp_a_n()), 8);
+        assert!(cpu.reg.is_c());
+        assert!(cpu.reg.is_n());
     }
 
     #[test]
     fn jr() {
-        let mut memmap = MemoryMap::default();
-        let mut cpu = Cpu::load(&mut memmap);
+        let memmap = MemoryMap::default();
+        let mut cpu = Cpu::load(&memmap);
 
         let relative_amt: i16 = -0x000A;
         let amt = relative_amt.to_le_bytes();
         let pc = 0x8200;
 
         cpu.pc = pc;
-        cpu.mem.write_byte(cpu.pc + 1, amt[0]).unwrap();
-        cpu.mem.write_byte(cpu.pc + 2, amt[1]).unwrap();
+        cpu.mem.patch_byte(cpu.pc + 1, amt[0]).unwrap();
+        cpu.mem.patch_byte(cpu.pc + 2, amt[1]).unwrap();
 
-        assert_eq!(cpu.jr(), 4 * 3);
+        assert_eq!(cpu.timed(|cpu| cpu.jr()), 4 * 3);
         assert_eq!(cpu.pc, pc - 0x000A + 2);
     }
 
     #[test]
     fn jr_cond() {
-        let mut memmap = MemoryMap::default();
-        let mut cpu = Cpu::load(&mut memmap);
+        let memmap = MemoryMap::default();
+        let mut cpu = Cpu::load(&memmap);
 
         let relative_amt: i16 = -0x000A;
         let amt = relative_amt.to_le_bytes();
         let pc = 0x8200;
 
         cpu.pc = pc;
-        cpu.mem.write_byte(cpu.pc + 1, amt[0]).unwrap();
-        cpu.mem.write_byte(cpu.pc + 2, amt[1]).unwrap();
+        cpu.mem.patch_byte(cpu.pc + 1, amt[0]).unwrap();
+        cpu.mem.patch_byte(cpu.pc + 2, amt[1]).unwrap();
 
         cpu.reg.set_z();
-        assert_eq!(cpu.jr_cond(FlagCond::NZ), 4 * 2);
+        assert_eq!(cpu.timed(|cpu| cpu.jr_cond(FlagCond::NZ)), 4 * 2);
         assert_eq!(cpu.pc, pc + 2);
 
         cpu.pc = pc;
         cpu.reg.unset_z();
-        assert_eq!(cpu.jr_cond(FlagCond::NZ), 4 * 3);
+        assert_eq!(cpu.timed(|cpu| cpu.jr_cond(FlagCond::NZ)), 4 * 3);
         assert_eq!(cpu.pc, pc - 0x000A + 2);
     }
 
     #[test]
     fn ld_mem_hl_a() {
-        let mut memmap = MemoryMap::default();
-        let mut cpu = Cpu::load(&mut memmap);
+        let memmap = MemoryMap::default();
+        let mut cpu = Cpu::load(&memmap);
 
         cpu.reg.set_hl(0x8200);
         cpu.reg.a = 0xEE;
 
-        assert_eq!(cpu.ld_mem_hl_a_dec(), 4 * 2);
+        assert_eq!(cpu.timed(|cpu| cpu.ld_mem_hl_a_dec()), 4 * 2);
         assert_eq!(cpu.mem.read_byte(cpu.reg.get_hl() + 1).unwrap(), 0xEE);
         assert_eq!(cpu.reg.get_hl(), 0x8200 - 1);
 
         cpu.reg.set_hl(0x8200);
         cpu.reg.a = 0xEE;
 
-        assert_eq!(cpu.ld_mem_hl_a_inc(), 4 * 2);
+        assert_eq!(cpu.timed(|cpu| cpu.ld_mem_hl_a_inc()), 4 * 2);
         assert_eq!(cpu.mem.read_byte(cpu.reg.get_hl() - 1).unwrap(), 0xEE);
         assert_eq!(cpu.reg.get_hl(), 0x8200 + 1);
     }
+
+    #[test]
+    fn rlca() {
+        let memmap = MemoryMap::default();
+        let mut cpu = Cpu::load(&memmap);
+
+        cpu.reg.a = 0b1000_0001;
+        cpu.reg.set_z();
+        assert_eq!(cpu.timed(|cpu| cpu.rlca()), 4);
+        assert_eq!(cpu.reg.a, 0b0000_0011);
+        assert!(cpu.reg.is_c());
+        assert!(!cpu.reg.is_z());
+
+        cpu.reg.a = 0b0100_0000;
+        assert_eq!(cpu.timed(|cpu| cpu.rlca()), 4);
+        assert_eq!(cpu.reg.a, 0b1000_0000);
+        assert!(!cpu.reg.is_c());
+    }
+
+    #[test]
+    fn rla() {
+        let memmap = MemoryMap::default();
+        let mut cpu = Cpu::load(&memmap);
+
+        cpu.reg.a = 0b1000_0000;
+        cpu.reg.unset_c();
+        assert_eq!(cpu.timed(|cpu| cpu.rla()), 4);
+        assert_eq!(cpu.reg.a, 0b0000_0000);
+        assert!(cpu.reg.is_c());
+        assert!(!cpu.reg.is_z());
+
+        assert_eq!(cpu.timed(|cpu| cpu.rla()), 4);
+        assert_eq!(cpu.reg.a, 0b0000_0001);
+        assert!(!cpu.reg.is_c());
+    }
+
+    #[test]
+    fn sbc_a_n() {
+        let memmap = MemoryMap::default();
+        let mut cpu = Cpu::load(&memmap);
+
+        cpu.reg.a = 0x10;
+        cpu.reg.b = 0x0F;
+        cpu.reg.set_c();
+        assert_eq!(cpu.timed(|cpu| cpu.sbc_a_n(StdRegN::B)), 4);
+        assert_eq!(cpu.reg.a, 0x00);
+        assert!(cpu.reg.is_z());
+        assert!(cpu.reg.is_n());
+        assert!(cpu.reg.is_h());
+        assert!(!cpu.reg.is_c());
+
+        cpu.reg.a = 0x00;
+        cpu.reg.set_hl(0x8100);
+        cpu.mem.write_byte(0x8100, 0xFF).unwrap();
+        cpu.reg.set_c();
+        assert_eq!(cpu.timed(|cpu| cpu.sbc_a_n(StdRegN::HL)), 8);
+        assert_eq!(cpu.reg.a, 0x00);
+        assert!(cpu.reg.is_z());
+        assert!(cpu.reg.is_h());
+        assert!(cpu.reg.is_c());
+
+        cpu.pc = 0x8200;
+        cpu.mem.patch_byte(cpu.pc + 1, 0x01).unwrap();
+        cpu.reg.a = 0x00;
+        cpu.reg.unset_c();
+        assert_eq!(cpu.timed(|cpu| cpu.sbc_a_n(StdRegN::N)), 8);
+        assert_eq!(cpu.reg.a, 0xFF);
+        assert!(!cpu.reg.is_z());
+        assert!(cpu.reg.is_h());
+        assert!(cpu.reg.is_c());
+        assert_eq!(cpu.pc, 0x8200 + 0x02);
+    }
+
+    #[test]
+    fn adc_a_n() {
+        let memmap = MemoryMap::default();
+        let mut cpu = Cpu::load(&memmap);
+
+        cpu.reg.a = 0xE1;
+        cpu.reg.e = 0x0F;
+        cpu.reg.set_c();
+        assert_eq!(cpu.timed(|cpu| cpu.adc_a_n(StdRegN::E)), 4);
+        assert_eq!(cpu.reg.a, 0xF1);
+        assert!(!cpu.reg.is_z());
+        assert!(!cpu.reg.is_n());
+        assert!(cpu.reg.is_h());
+        assert!(!cpu.reg.is_c());
+
+        cpu.reg.a = 0xE1;
+        cpu.reg.set_hl(0x8100);
+        cpu.mem.write_byte(0x8100, 0x1E).unwrap();
+        cpu.reg.set_c();
+        assert_eq!(cpu.timed(|cpu| cpu.adc_a_n(StdRegN::HL)), 8);
+        assert_eq!(cpu.reg.a, 0x00);
+        assert!(cpu.reg.is_z());
+        assert!(cpu.reg.is_h());
+        assert!(cpu.reg.is_c());
+    }
+
+    #[test]
+    fn add_sub_mem_hl() {
+        let memmap = MemoryMap::default();
+        let mut cpu = Cpu::load(&memmap);
+
+        cpu.reg.set_hl(0x8100);
+        cpu.mem.write_byte(0x8100, 0x3A).unwrap();
+        cpu.reg.a = 0xC6;
+        assert_eq!(cpu.timed(|cpu| cpu.add_a_r(StdRegN::HL)), 8);
+        assert_eq!(cpu.reg.a, 0x00);
+        assert!(cpu.reg.is_z());
+        assert!(cpu.reg.is_h());
+        assert!(cpu.reg.is_c());
+
+        cpu.reg.a = 0x3E;
+        cpu.mem.write_byte(0x8100, 0x40).unwrap();
+        assert_eq!(cpu.timed(|cpu| cpu.sub_a_r(StdRegN::HL)), 8);
+        assert_eq!(cpu.reg.a, 0xFE);
+        assert!(!cpu.reg.is_z());
+        assert!(!cpu.reg.is_h());
+        assert!(cpu.reg.is_n());
+        assert!(cpu.reg.is_c());
+    }
+
+    #[test]
+    fn logic_ops() {
+        let memmap = MemoryMap::default();
+        let mut cpu = Cpu::load(&memmap);
+
+        cpu.reg.a = 0x5A;
+        cpu.reg.b = 0x0F;
+        assert_eq!(cpu.timed(|cpu| cpu.and_a_r(StdRegN::B)), 4);
+        assert_eq!(cpu.reg.a, 0x0A);
+        assert_eq!(cpu.reg.f, 0b0010_0000);
+
+        cpu.reg.set_hl(0x8100);
+        cpu.mem.write_byte(0x8100, 0xF0).unwrap();
+        assert_eq!(cpu.timed(|cpu| cpu.and_a_r(StdRegN::HL)), 8);
+        assert_eq!(cpu.reg.a, 0x00);
+        assert_eq!(cpu.reg.f, 0b1010_0000);
+
+        cpu.reg.set_c();
+        assert_eq!(cpu.timed(|cpu| cpu.or_a_r(StdRegN::HL)), 8);
+        assert_eq!(cpu.reg.a, 0xF0);
+        assert_eq!(cpu.reg.f, 0b0000_0000);
+
+        cpu.pc = 0x8200;
+        cpu.mem.patch_byte(cpu.pc + 1, 0x0F).unwrap();
+        assert_eq!(cpu.timed(|cpu| cpu.or_a_r(StdRegN::N)), 8);
+        assert_eq!(cpu.reg.a, 0xFF);
+        assert_eq!(cpu.pc, 0x8200 + 0x02);
+
+        cpu.reg.b = 0xFF;
+        assert_eq!(cpu.timed(|cpu| cpu.xor_r(StdRegN::B)), 4);
+        assert_eq!(cpu.reg.a, 0x00);
+        assert_eq!(cpu.reg.f, 0b1000_0000);
+
+        macro_rules! or {
+            ($r:expr, $reg:expr) => {
+                cpu.reg.a = 0x00;
+                $r = 0x81;
+                assert_eq!(cpu.timed(|cpu| cpu.or_a_r($reg)), 4);
+                assert_eq!(cpu.reg.a, 0x81);
+                assert!(!cpu.reg.is_z());
+            };
+        }
+        or!(cpu.reg.b, StdRegN::B);
+        or!(cpu.reg.c, StdRegN::C);
+        or!(cpu.reg.d, StdRegN::D);
+        or!(cpu.reg.e, StdRegN::E);
+        or!(cpu.reg.h, StdRegN::H);
+        or!(cpu.reg.l, StdRegN::L);
+    }
+
+    #[test]
+    fn cp_a_r() {
+        let memmap = MemoryMap::default();
+        let mut cpu = Cpu::load(&memmap);
+
+        macro_rules! cp {
+            ($r:expr, $reg:expr) => {
+                cpu.reg.a = 0x3C;
+                $r = 0x2F;
+                assert_eq!(cpu.timed(|cpu| cpu.cp_a_r($reg)), 4);
+                assert_eq!(cpu.reg.a, 0x3C);
+                assert!(!cpu.reg.is_z());
+                assert!(cpu.reg.is_n());
+                assert!(cpu.reg.is_h());
+                assert!(!cpu.reg.is_c());
+            };
+        }
+        cp!(cpu.reg.b, StdReg::B);
+        cp!(cpu.reg.c, StdReg::C);
+        cp!(cpu.reg.d, StdReg::D);
+        cp!(cpu.reg.e, StdReg::E);
+        cp!(cpu.reg.h, StdReg::H);
+        cp!(cpu.reg.l, StdReg::L);
+
+        cpu.reg.a = 0x3C;
+        assert_eq!(cpu.timed(|cpu| cpu.cp_a_r(StdReg::A)), 4);
+        assert!(cpu.reg.is_z());
+
+        cpu.reg.set_hl(0x8100);
+        cpu.mem.write_byte(0x8100, 0x40).unwrap();
+        assert_eq!(cpu.timed(|cpu| cpu.cp_a_r(StdReg::HL)), 8);
+        assert!(!cpu.reg.is_z());
+        assert!(!cpu.reg.is_h());
+        assert!(cpu.reg.is_c());
+    }
+
+    #[test]
+    fn dec_mem_hl() {
+        let memmap = MemoryMap::default();
+        let mut cpu = Cpu::load(&memmap);
+
+        cpu.reg.set_hl(0x8100);
+        cpu.mem.write_byte(0x8100, 0x10).unwrap();
+        assert_eq!(cpu.timed(|cpu| cpu.dec_r(StdReg::HL)), 12);
+        assert_eq!(cpu.mem.read_byte(0x8100).unwrap(), 0x0F);
+        assert!(!cpu.reg.is_z());
+        assert!(cpu.reg.is_h());
+        assert!(cpu.reg.is_n());
+
+        cpu.mem.write_byte(0x8100, 0xFF).unwrap();
+        assert_eq!(cpu.timed(|cpu| cpu.inc_reg(IncDecReg::MemHL)), 12);
+        assert_eq!(cpu.mem.read_byte(0x8100).unwrap(), 0x00);
+        assert!(cpu.reg.is_z());
+        assert!(cpu.reg.is_h());
+        assert!(!cpu.reg.is_n());
+    }
+
+    #[test]
+    fn dec_rr() {
+        let memmap = MemoryMap::default();
+        let mut cpu = Cpu::load(&memmap);
+
+        cpu.reg.f = 0x00;
+        cpu.reg.set_bc(0x0000);
+        assert_eq!(cpu.timed(|cpu| cpu.dec_rr(LoadRegnnn::BC)), 8);
+        assert_eq!(cpu.reg.get_bc(), 0xFFFF);
+        cpu.reg.set_de(0x1000);
+        assert_eq!(cpu.timed(|cpu| cpu.dec_rr(LoadRegnnn::DE)), 8);
+        assert_eq!(cpu.reg.get_de(), 0x0FFF);
+        cpu.reg.set_hl(0x8000);
+        assert_eq!(cpu.timed(|cpu| cpu.dec_rr(LoadRegnnn::HL)), 8);
+        assert_eq!(cpu.reg.get_hl(), 0x7FFF);
+        cpu.sp = 0xFFFE;
+        assert_eq!(cpu.timed(|cpu| cpu.dec_rr(LoadRegnnn::SP)), 8);
+        assert_eq!(cpu.sp, 0xFFFD);
+        assert_eq!(cpu.reg.f, 0x00);
+    }
+
+    #[test]
+    fn add_hl_rr() {
+        let memmap = MemoryMap::default();
+        let mut cpu = Cpu::load(&memmap);
+
+        cpu.reg.set_z();
+        cpu.reg.set_n();
+        cpu.reg.set_hl(0x8A23);
+        cpu.reg.set_bc(0x0605);
+        assert_eq!(cpu.timed(|cpu| cpu.add_hl_rr(LoadRegnnn::BC)), 8);
+        assert_eq!(cpu.reg.get_hl(), 0x9028);
+        assert!(cpu.reg.is_z());
+        assert!(!cpu.reg.is_n());
+        assert!(cpu.reg.is_h());
+        assert!(!cpu.reg.is_c());
+
+        cpu.reg.set_hl(0x8A23);
+        assert_eq!(cpu.timed(|cpu| cpu.add_hl_rr(LoadRegnnn::HL)), 8);
+        assert_eq!(cpu.reg.get_hl(), 0x1446);
+        assert!(cpu.reg.is_h());
+        assert!(cpu.reg.is_c());
+
+        cpu.reg.set_hl(0x0001);
+        cpu.reg.set_de(0x0001);
+        assert_eq!(cpu.timed(|cpu| cpu.add_hl_rr(LoadRegnnn::DE)), 8);
+        assert_eq!(cpu.reg.get_hl(), 0x0002);
+        assert!(!cpu.reg.is_h());
+        assert!(!cpu.reg.is_c());
+
+        cpu.sp = 0xFFFF;
+        assert_eq!(cpu.timed(|cpu| cpu.add_hl_rr(LoadRegnnn::SP)), 8);
+        assert_eq!(cpu.reg.get_hl(), 0x0001);
+        assert!(cpu.reg.is_h());
+        assert!(cpu.reg.is_c());
+    }
+
+    #[test]
+    fn ld_sp() {
+        let memmap = MemoryMap::default();
+        let mut cpu = Cpu::load(&memmap);
+
+        cpu.pc = 0x8200;
+        cpu.sp = 0xFFF8;
+        cpu.mem.patch_byte(cpu.pc + 1, 0x00).unwrap();
+        cpu.mem.patch_byte(cpu.pc + 2, 0xC1).unwrap();
+        assert_eq!(cpu.timed(|cpu| cpu.ld_nn_sp()), 20);
+        assert_eq!(cpu.mem.read_byte(0xC100).unwrap(), 0xF8);
+        assert_eq!(cpu.mem.read_byte(0xC101).unwrap(), 0xFF);
+        assert_eq!(cpu.pc, 0x8200 + 3);
+
+        cpu.reg.set_hl(0xC0DE);
+        assert_eq!(cpu.timed(|cpu| cpu.ld_sp_hl()), 8);
+        assert_eq!(cpu.sp, 0xC0DE);
+        assert_eq!(cpu.pc, 0x8200 + 4);
+    }
+
+    #[test]
+    fn ld_ff00c() {
+        let memmap = MemoryMap::default();
+        let mut cpu = Cpu::load(&memmap);
+
+        cpu.reg.a = 0x42;
+        cpu.reg.c = 0x80;
+        assert_eq!(cpu.timed(|cpu| cpu.ld_ff00c_a()), 8);
+        assert_eq!(cpu.mem.read_byte(0xFF80).unwrap(), 0x42);
+
+        cpu.reg.a = 0x00;
+        assert_eq!(cpu.timed(|cpu| cpu.ld_a_ff00c()), 8);
+        assert_eq!(cpu.reg.a, 0x42);
+    }
+
+    #[test]
+    fn ld_a_mem_hl_inc_dec() {
+        let memmap = MemoryMap::default();
+        let mut cpu = Cpu::load(&memmap);
+
+        cpu.reg.set_hl(0x8200);
+        cpu.mem.write_byte(0x8200, 0x11).unwrap();
+        assert_eq!(cpu.timed(|cpu| cpu.ldi_a_memhl()), 8);
+        assert_eq!(cpu.reg.a, 0x11);
+        assert_eq!(cpu.reg.get_hl(), 0x8201);
+
+        cpu.reg.set_hl(0x8200);
+        cpu.mem.write_byte(0x8200, 0x22).unwrap();
+        assert_eq!(cpu.timed(|cpu| cpu.ldd_a_memhl()), 8);
+        assert_eq!(cpu.reg.a, 0x22);
+        assert_eq!(cpu.reg.get_hl(), 0x81FF);
+    }
+
+    #[test]
+    fn ld_r_n() {
+        let memmap = MemoryMap::default();
+        let mut cpu = Cpu::load(&memmap);
+
+        macro_rules! ld {
+            ($r:expr, $reg:expr) => {
+                cpu.pc = 0x8200;
+                cpu.mem.patch_byte(cpu.pc + 1, 0x5C).unwrap();
+                $r = 0x00;
+                assert_eq!(cpu.timed(|cpu| cpu.ld_r_n($reg)), 8);
+                assert_eq!($r, 0x5C);
+                assert_eq!(cpu.pc, 0x8200 + 2);
+            };
+        }
+        ld!(cpu.reg.b, StdReg::B);
+        ld!(cpu.reg.c, StdReg::C);
+        ld!(cpu.reg.d, StdReg::D);
+        ld!(cpu.reg.e, StdReg::E);
+        ld!(cpu.reg.h, StdReg::H);
+        ld!(cpu.reg.l, StdReg::L);
+
+        cpu.pc = 0x8200;
+        cpu.reg.set_hl(0x8300);
+        cpu.mem.patch_byte(cpu.pc + 1, 0x77).unwrap();
+        assert_eq!(cpu.timed(|cpu| cpu.ld_r_n(StdReg::HL)), 12);
+        assert_eq!(cpu.mem.read_byte(0x8300).unwrap(), 0x77);
+        assert_eq!(cpu.pc, 0x8200 + 2);
+    }
+
+    #[test]
+    fn jp_cc() {
+        let memmap = MemoryMap::default();
+        let mut cpu = Cpu::load(&memmap);
+
+        cpu.pc = 0x8200;
+        cpu.mem.patch_byte(cpu.pc + 1, 0x34).unwrap();
+        cpu.mem.patch_byte(cpu.pc + 2, 0x12).unwrap();
+
+        cpu.reg.set_z();
+        assert_eq!(cpu.timed(|cpu| cpu.jp_cc(FlagCond::NZ)), 12);
+        assert_eq!(cpu.pc, 0x8200 + 3);
+
+        cpu.pc = 0x8200;
+        assert_eq!(cpu.timed(|cpu| cpu.jp_cc(FlagCond::Z)), 16);
+        assert_eq!(cpu.pc, 0x1234);
+
+        cpu.pc = 0x8200;
+        cpu.reg.unset_c();
+        assert_eq!(cpu.timed(|cpu| cpu.jp_cc(FlagCond::C)), 12);
+        assert_eq!(cpu.pc, 0x8200 + 3);
+
+        cpu.pc = 0x8200;
+        assert_eq!(cpu.timed(|cpu| cpu.jp_cc(FlagCond::NC)), 16);
+        assert_eq!(cpu.pc, 0x1234);
+
+        cpu.reg.set_hl(0x4321);
+        assert_eq!(cpu.timed(|cpu| cpu.jp_hl()), 4);
+        assert_eq!(cpu.pc, 0x4321);
+    }
+
+    #[test]
+    fn rst_pushes_return_address() {
+        let memmap = MemoryMap::default();
+        let mut cpu = Cpu::load(&memmap);
+
+        cpu.pc = 0x8200;
+        assert_eq!(cpu.timed(|cpu| cpu.rst(0x0038)), 16);
+        assert_eq!(cpu.pc, 0x0038);
+        assert_eq!(cpu.pop(), 0x8201);
+    }
+
+    #[test]
+    fn pop_af_masks_flags() {
+        let memmap = MemoryMap::default();
+        let mut cpu = Cpu::load(&memmap);
+
+        cpu.push(0x12, 0xFF);
+        assert_eq!(cpu.timed(|cpu| cpu.pop_af()), 12);
+        assert_eq!(cpu.reg.a, 0x12);
+        assert_eq!(cpu.reg.f, 0xF0);
+    }
+
+    #[test]
+    fn step_runs_program() {
+        let memmap = MemoryMap::default();
+        let mut cpu = Cpu::load(&memmap);
+
+        // LD HL,$C000; LD (HL),$05; DEC (HL); JP NZ,$0103; LD SP,HL; ADD HL,SP
+        let program = [
+            0x21, 0x00, 0xC0, 0x36, 0x05, 0x35, 0xC2, 0x05, 0x01, 0xF9, 0x39,
+        ];
+        for (i, b) in program.iter().enumerate() {
+            memmap.patch_byte(0x0100 + i as u16, *b).unwrap();
+        }
+
+        let mut cycles = 0;
+        while cpu.pc != 0x010B {
+            cycles += cpu.step().unwrap() as u32;
+        }
+
+        assert_eq!(memmap.read_byte(0xC000).unwrap(), 0x00);
+        assert_eq!(cpu.sp, 0xC000);
+        assert_eq!(cpu.reg.get_hl(), 0x8000);
+        assert_eq!(cycles, 12 + 12 + 5 * 12 + 4 * 16 + 12 + 8 + 8);
+    }
+
+    #[test]
+    fn daa_after_bcd_add() {
+        let memmap = MemoryMap::default();
+        let mut cpu = Cpu::load(&memmap);
+
+        for x in 0..100u8 {
+            for y in 0..100u8 {
+                let bx = ((x / 10) << 4) | (x % 10);
+                let by = ((y / 10) << 4) | (y % 10);
+                let sum = x + y;
+                let expected = (((sum % 100) / 10) << 4) | (sum % 10);
+
+                cpu.reg.a = bx;
+                cpu.reg.b = by;
+                cpu.add_a_r(StdRegN::B);
+                assert_eq!(cpu.timed(|cpu| cpu.daa()), 4);
+                assert_eq!(cpu.reg.a, expected, "{:02X} + {:02X}", bx, by);
+                assert_eq!(cpu.reg.is_c(), sum >= 100, "{:02X} + {:02X}", bx, by);
+                assert_eq!(cpu.reg.is_z(), expected == 0);
+                assert!(!cpu.reg.is_h());
+                assert!(!cpu.reg.is_n());
+            }
+        }
+    }
+
+    #[test]
+    fn daa_after_bcd_sub() {
+        let memmap = MemoryMap::default();
+        let mut cpu = Cpu::load(&memmap);
+
+        for x in 0..100u8 {
+            for y in 0..100u8 {
+                let bx = ((x / 10) << 4) | (x % 10);
+                let by = ((y / 10) << 4) | (y % 10);
+                let diff = (x + 100 - y) % 100;
+                let expected = ((diff / 10) << 4) | (diff % 10);
+
+                cpu.reg.a = bx;
+                cpu.reg.b = by;
+                cpu.sub_a_r(StdRegN::B);
+                assert_eq!(cpu.timed(|cpu| cpu.daa()), 4);
+                assert_eq!(cpu.reg.a, expected, "{:02X} - {:02X}", bx, by);
+                assert_eq!(cpu.reg.is_c(), x < y, "{:02X} - {:02X}", bx, by);
+                assert_eq!(cpu.reg.is_z(), expected == 0);
+                assert!(!cpu.reg.is_h());
+                assert!(cpu.reg.is_n());
+            }
+        }
+    }
+
+    #[test]
+    fn daa_all_inputs() {
+        let memmap = MemoryMap::default();
+        let mut cpu = Cpu::load(&memmap);
+
+        for a in 0..=0xFFu8 {
+            for flags in 0..8u8 {
+                let (n, h, c) = (flags & 4 != 0, flags & 2 != 0, flags & 1 != 0);
+                let mut correction = 0u8;
+                let mut carry = c;
+                if h || (!n && a & 0x0F > 0x09) {
+                    correction |= 0x06;
+                }
+                if c || (!n && a > 0x99) {
+                    correction |= 0x60;
+                    carry = true;
+                }
+                let expected = if n {
+                    a.wrapping_sub(correction)
+                } else {
+                    a.wrapping_add(correction)
+                };
+
+                cpu.reg.a = a;
+                cpu.reg.f = (flags << 4) | 0b1000_0000;
+                cpu.daa();
+                assert_eq!(cpu.reg.a, expected);
+                assert_eq!(cpu.reg.is_z(), expected == 0);
+                assert_eq!(cpu.reg.is_n(), n);
+                assert!(!cpu.reg.is_h());
+                assert_eq!(cpu.reg.is_c(), carry);
+            }
+        }
+    }
+
+    #[test]
+    fn cpl_scf_ccf() {
+        let memmap = MemoryMap::default();
+        let mut cpu = Cpu::load(&memmap);
+
+        for flags in 0..16u8 {
+            let z = flags & 0b1000 != 0;
+            let c = flags & 0b0001 != 0;
+
+            cpu.reg.a = 0b1010_0101;
+            cpu.reg.f = flags << 4;
+            assert_eq!(cpu.timed(|cpu| cpu.cpl()), 4);
+            assert_eq!(cpu.reg.a, 0b0101_1010);
+            assert_eq!(cpu.reg.f, (flags << 4) | 0b0110_0000);
+
+            cpu.reg.f = flags << 4;
+            assert_eq!(cpu.timed(|cpu| cpu.scf()), 4);
+            assert_eq!(cpu.reg.is_z(), z);
+            assert!(!cpu.reg.is_n());
+            assert!(!cpu.reg.is_h());
+            assert!(cpu.reg.is_c());
+
+            cpu.reg.f = flags << 4;
+            assert_eq!(cpu.timed(|cpu| cpu.ccf()), 4);
+            assert_eq!(cpu.reg.is_z(), z);
+            assert!(!cpu.reg.is_n());
+            assert!(!cpu.reg.is_h());
+            assert_eq!(cpu.reg.is_c(), !c);
+        }
+    }
+
+    #[test]
+    fn add_hl_rr_flags() {
+        let memmap = MemoryMap::default();
+        let mut cpu = Cpu::load(&memmap);
+
+        for hl in (0..=0xFFFFu16).step_by(0x0111) {
+            for rr in (0..=0xFFFFu16).step_by(0x0101) {
+                cpu.reg.set_hl(hl);
+                cpu.reg.set_de(rr);
+                cpu.reg.f = 0b1100_0000;
+                cpu.add_hl_rr(LoadRegnnn::DE);
+                let sum = hl as u32 + rr as u32;
+                assert_eq!(cpu.reg.get_hl(), sum as u16);
+                assert!(cpu.reg.is_z());
+                assert!(!cpu.reg.is_n());
+                assert_eq!(cpu.reg.is_h(), (hl & 0x0FFF) + (rr & 0x0FFF) > 0x0FFF);
+                assert_eq!(cpu.reg.is_c(), sum > 0xFFFF);
+            }
+        }
+    }
+
+    #[test]
+    fn sp_plus_e8() {
+        let memmap = MemoryMap::default();
+        let mut cpu = Cpu::load(&memmap);
+
+        for sp in [0x0000u16, 0x00FF, 0x0F0F, 0x8000, 0xFFF8, 0xFFFF] {
+            for e in 0..=0xFFu8 {
+                let expected = sp.wrapping_add(e as i8 as u16);
+                let h = (sp & 0x0F) + (e as u16 & 0x0F) > 0x0F;
+                let c = (sp & 0xFF) + e as u16 > 0xFF;
+
+                cpu.pc = 0x8200;
+                cpu.mem.patch_byte(cpu.pc + 1, e).unwrap();
+                cpu.sp = sp;
+                cpu.reg.f = 0b1100_0000;
+                assert_eq!(cpu.timed(|cpu| cpu.add_sp_e8()), 16);
+                assert_eq!(cpu.sp, expected);
+                assert_eq!(cpu.pc, 0x8202);
+                assert!(!cpu.reg.is_z());
+                assert!(!cpu.reg.is_n());
+                assert_eq!(cpu.reg.is_h(), h);
+                assert_eq!(cpu.reg.is_c(), c);
+
+                cpu.pc = 0x8200;
+                cpu.sp = sp;
+                cpu.reg.f = 0b1100_0000;
+                assert_eq!(cpu.timed(|cpu| cpu.ld_hl_sp_e8()), 12);
+                assert_eq!(cpu.reg.get_hl(), expected);
+                assert_eq!(cpu.sp, sp);
+                assert_eq!(cpu.pc, 0x8202);
+                assert!(!cpu.reg.is_z());
+                assert!(!cpu.reg.is_n());
+                assert_eq!(cpu.reg.is_h(), h);
+                assert_eq!(cpu.reg.is_c(), c);
+            }
+        }
+    }
+
+    #[test]
+    fn ei_is_delayed_one_instruction() {
+        let memmap = MemoryMap::default();
+        let mut cpu = Cpu::load(&memmap);
+
+        // EI; NOP; NOP
+        memmap.patch_byte(0x0100, 0xFB).unwrap();
+        memmap.patch_byte(0x0101, 0x00).unwrap();
+        memmap.patch_byte(0x0102, 0x00).unwrap();
+        memmap.write_byte(IE_ADDR, 0x01).unwrap();
+        memmap.write_byte(IF_ADDR, 0x01).unwrap();
+
+        assert_eq!(cpu.step().unwrap(), 4);
+        assert!(!cpu.ime);
+        assert_eq!(cpu.step().unwrap(), 4);
+        assert!(cpu.ime);
+        assert_eq!(cpu.pc, 0x0102);
+
+        assert_eq!(cpu.step().unwrap(), 20);
+        assert_eq!(cpu.pc, 0x0040);
+        assert!(!cpu.ime);
+        assert_eq!(cpu.pop(), 0x0102);
+        assert_eq!(memmap.read_byte(IF_ADDR).unwrap(), 0xE0);
+    }
+
+    #[test]
+    fn di_cancels_pending_ei() {
+        let memmap = MemoryMap::default();
+        let mut cpu = Cpu::load(&memmap);
+
+        // EI; DI; NOP
+        memmap.patch_byte(0x0100, 0xFB).unwrap();
+        memmap.patch_byte(0x0101, 0xF3).unwrap();
+        memmap.patch_byte(0x0102, 0x00).unwrap();
+        memmap.write_byte(IE_ADDR, 0x1F).unwrap();
+        memmap.write_byte(IF_ADDR, 0x1F).unwrap();
+
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+        assert!(!cpu.ime);
+        assert_eq!(cpu.step().unwrap(), 4);
+        assert_eq!(cpu.pc, 0x0103);
+        assert_eq!(memmap.read_byte(IE_ADDR).unwrap(), 0x1F);
+    }
+
+    #[test]
+    fn interrupt_priority() {
+        let memmap = MemoryMap::default();
+        let mut cpu = Cpu::load(&memmap);
+
+        memmap.patch_byte(0x0100, 0x00).unwrap();
+        memmap.write_byte(IE_ADDR, 0b0001_1110).unwrap();
+        memmap.write_byte(IF_ADDR, 0b0001_1101).unwrap();
+
+        for (vector, remaining) in [
+            (0x0050, 0b0001_1001),
+            (0x0058, 0b0001_0001),
+            (0x0060, 0b0000_0001),
+        ] {
+            cpu.pc = 0x0100;
+            cpu.ime = true;
+            assert_eq!(cpu.step().unwrap(), 20);
+            assert_eq!(cpu.pc, vector);
+            assert_eq!(memmap.read_byte(IF_ADDR).unwrap(), 0xE0 | remaining);
+        }
+
+        // VBlank is requested but not enabled.
+        cpu.pc = 0x0100;
+        cpu.ime = true;
+        assert_eq!(cpu.step().unwrap(), 4);
+        assert_eq!(cpu.pc, 0x0101);
+    }
+
+    #[test]
+    fn interrupt_requires_ime() {
+        let memmap = MemoryMap::default();
+        let mut cpu = Cpu::load(&memmap);
+
+        memmap.patch_byte(0x0100, 0x00).unwrap();
+        memmap.write_byte(IE_ADDR, 0x01).unwrap();
+        memmap.write_byte(IF_ADDR, 0x01).unwrap();
+
+        assert_eq!(cpu.step().unwrap(), 4);
+        assert_eq!(cpu.pc, 0x0101);
+        assert_eq!(memmap.read_byte(IF_ADDR).unwrap(), 0xE1);
+    }
+
+    #[test]
+    fn reti() {
+        let memmap = MemoryMap::default();
+        let mut cpu = Cpu::load(&memmap);
+
+        cpu.push(0x12, 0x34);
+        cpu.pc = 0x0040;
+        memmap.patch_byte(0x0040, 0xD9).unwrap();
+
+        assert_eq!(cpu.step().unwrap(), 16);
+        assert_eq!(cpu.pc, 0x1234);
+        assert!(cpu.ime);
+    }
+
+    #[test]
+    fn halt_wakes_into_interrupt() {
+        let memmap = MemoryMap::default();
+        let mut cpu = Cpu::load(&memmap);
+
+        memmap.patch_byte(0x0100, 0x76).unwrap();
+        memmap.write_byte(IE_ADDR, 0x04).unwrap();
+        cpu.ime = true;
+
+        assert_eq!(cpu.step().unwrap(), 4);
+        assert!(cpu.halted);
+        for _ in 0..10 {
+            assert_eq!(cpu.step().unwrap(), 4);
+            assert_eq!(cpu.pc, 0x0101);
+        }
+
+        memmap.write_byte(IF_ADDR, 0x04).unwrap();
+        assert_eq!(cpu.step().unwrap(), 20);
+        assert!(!cpu.halted);
+        assert_eq!(cpu.pc, 0x0050);
+        assert_eq!(cpu.pop(), 0x0101);
+    }
+
+    #[test]
+    fn halt_without_ime_resumes() {
+        let memmap = MemoryMap::default();
+        let mut cpu = Cpu::load(&memmap);
+
+        // HALT; INC A
+        memmap.patch_byte(0x0100, 0x76).unwrap();
+        memmap.patch_byte(0x0101, 0x3C).unwrap();
+        memmap.write_byte(IE_ADDR, 0x01).unwrap();
+        cpu.reg.a = 0x00;
+
+        cpu.step().unwrap();
+        assert_eq!(cpu.step().unwrap(), 4);
+        assert!(cpu.halted);
+
+        memmap.write_byte(IF_ADDR, 0x01).unwrap();
+        assert_eq!(cpu.step().unwrap(), 4);
+        assert!(!cpu.halted);
+        assert_eq!(cpu.reg.a, 0x01);
+        assert_eq!(cpu.pc, 0x0102);
+        assert_eq!(memmap.read_byte(IF_ADDR).unwrap(), 0xE1);
+    }
+
+    #[test]
+    fn halt_bug() {
+        let memmap = MemoryMap::default();
+        let mut cpu = Cpu::load(&memmap);
+
+        // HALT; LD A,$14 -> executes as LD A,$3E; INC D
+        memmap.patch_byte(0x0100, 0x76).unwrap();
+        memmap.patch_byte(0x0101, 0x3E).unwrap();
+        memmap.patch_byte(0x0102, 0x14).unwrap();
+        memmap.write_byte(IE_ADDR, 0x01).unwrap();
+        memmap.write_byte(IF_ADDR, 0x01).unwrap();
+        cpu.reg.d = 0x00;
+
+        assert_eq!(cpu.step().unwrap(), 4);
+        assert!(!cpu.halted);
+        assert_eq!(cpu.step().unwrap(), 8);
+        assert_eq!(cpu.reg.a, 0x3E);
+        assert_eq!(cpu.pc, 0x0102);
+        assert_eq!(cpu.step().unwrap(), 4);
+        assert_eq!(cpu.reg.d, 0x01);
+        assert_eq!(cpu.pc, 0x0103);
+    }
+
+    #[test]
+    fn ei_halt_bug_returns_to_halt() {
+        let memmap = MemoryMap::default();
+        let mut cpu = Cpu::load(&memmap);
+
+        // EI; HALT
+        memmap.patch_byte(0x0100, 0xFB).unwrap();
+        memmap.patch_byte(0x0101, 0x76).unwrap();
+        memmap.write_byte(IE_ADDR, 0x01).unwrap();
+        memmap.write_byte(IF_ADDR, 0x01).unwrap();
+
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+        assert!(cpu.ime);
+        assert_eq!(cpu.step().unwrap(), 20);
+        assert_eq!(cpu.pc, 0x0040);
+        assert_eq!(cpu.pop(), 0x0101);
+    }
+
+    #[test]
+    fn stop_waits_for_joypad() {
+        let memmap = MemoryMap::default();
+        let mut cpu = Cpu::load(&memmap);
+
+        // STOP; NOP
+        memmap.patch_byte(0x0100, 0x10).unwrap();
+        memmap.patch_byte(0x0101, 0x00).unwrap();
+        memmap.patch_byte(0x0102, 0x00).unwrap();
+        for _ in 0..64 * 0xAB {
+            memmap.tick();
+        }
+
+        assert_eq!(cpu.step().unwrap(), 4);
+        assert!(cpu.stopped);
+        assert_eq!(cpu.pc, 0x0102);
+        assert_eq!(memmap.read_byte(DIV_ADDR).unwrap(), 0x00);
+
+        memmap.write_byte(IF_ADDR, 0x1F).unwrap();
+        memmap.write_byte(IE_ADDR, 0x1F).unwrap();
+        let mcycles = cpu.mcycles();
+        assert_eq!(cpu.step().unwrap(), 0);
+        assert_eq!(cpu.pc, 0x0102);
+        assert_eq!(cpu.mcycles(), mcycles);
+
+        memmap.write_byte(IE_ADDR, 0x00).unwrap();
+        memmap.write_byte(P1_ADDR, 0xC7).unwrap();
+        assert_eq!(cpu.step().unwrap(), 4);
+        assert!(!cpu.stopped);
+        assert_eq!(cpu.pc, 0x0103);
+    }
+
+    #[test]
+    fn stop_without_boot_state() {
+        let memmap = MemoryMap::default();
+        let mut cpu = Cpu::new(&memmap);
+
+        // STOP with no button pressed and nothing pending stops.
+        memmap.patch_byte(0x0000, 0x10).unwrap();
+        assert_eq!(cpu.step().unwrap(), 4);
+        assert!(cpu.stopped);
+        assert!(!cpu.halted);
+        assert_eq!(cpu.pc, 0x0002);
+    }
+
+    #[test]
+    fn stop_with_button_held() {
+        let memmap = MemoryMap::default();
+        let mut cpu = Cpu::load(&memmap);
+
+        memmap.patch_byte(0x0100, 0x10).unwrap();
+        memmap.write_byte(P1_ADDR, 0xCE).unwrap();
+        for _ in 0..64 * 0xAB {
+            memmap.tick();
+        }
+
+        // No interrupt pending: two bytes, enters HALT.
+        assert_eq!(cpu.step().unwrap(), 4);
+        assert!(cpu.halted);
+        assert!(!cpu.stopped);
+        assert_eq!(cpu.pc, 0x0102);
+        assert_eq!(memmap.read_byte(DIV_ADDR).unwrap(), 0xAB);
+
+        // Interrupt pending: one byte, nothing happens.
+        cpu.halted = false;
+        cpu.pc = 0x0100;
+        memmap.write_byte(IE_ADDR, 0x10).unwrap();
+        memmap.write_byte(IF_ADDR, 0x10).unwrap();
+        assert_eq!(cpu.step().unwrap(), 4);
+        assert!(!cpu.halted);
+        assert!(!cpu.stopped);
+        assert_eq!(cpu.pc, 0x0101);
+        assert_eq!(memmap.read_byte(DIV_ADDR).unwrap(), 0xAB);
+    }
+
+    #[test]
+    fn illegal_opcode_locks_up() {
+        for opcode in [
+            0xD3, 0xDB, 0xDD, 0xE3, 0xE4, 0xEB, 0xEC, 0xED, 0xF4, 0xFC, 0xFD,
+        ] {
+            let memmap = MemoryMap::default();
+            let mut cpu = Cpu::load(&memmap);
+
+            memmap.patch_byte(0x4123, opcode).unwrap();
+            memmap.write_byte(IE_ADDR, 0x1F).unwrap();
+            memmap.write_byte(IF_ADDR, 0x1F).unwrap();
+            cpu.pc = 0x4123;
+
+            match cpu.step() {
+                Err(CpuError::IllegalOpcode {
+                    opcode: op,
+                    addr,
+                    bank,
+                }) => {
+                    assert_eq!(op, opcode);
+                    assert_eq!(addr, 0x4123);
+                    assert_eq!(bank, Some(1));
+                }
+                other => panic!("expected an illegal opcode error, got {:?}", other),
+            }
+            assert!(cpu.is_locked());
+
+            cpu.ime = true;
+            for _ in 0..4 {
+                assert_eq!(cpu.step().unwrap(), 4);
+                assert_eq!(cpu.pc, 0x4123);
+            }
+
+            let state = cpu.state();
+            assert!(state.locked);
+            cpu.set_state(&CpuState {
+                locked: false,
+                ..state
+            });
+            assert!(!cpu.is_locked());
+            cpu.set_state(&state);
+            assert!(cpu.is_locked());
+        }
+    }
+
+    #[test]
+    fn decoded_timing_matches_execution() {
+        let run = |bytes: [u8; 3], flags: u8| {
+            let memmap = MemoryMap::default();
+            let mut cpu = Cpu::load(&memmap);
+            for (i, b) in bytes.iter().enumerate() {
+                memmap.write_byte(0xC000 + i as u16, *b).unwrap();
+            }
+            cpu.pc = 0xC000;
+            cpu.sp = 0xD000;
+            cpu.reg.set_hl(0xC800);
+            cpu.reg.f = flags;
+
+            let instr = cpu.next_instruction();
+            let cycles = cpu.step().unwrap_or(4);
+            let taken = Some(cycles) == instr.branch_cycles();
+            assert_eq!(cpu.mcycles() * 4, cycles as u64, "{}", instr);
+            assert!(
+                cycles == instr.cycles() || taken,
+                "{:02X?} {} took {} cycles",
+                bytes,
+                instr,
+                cycles
+            );
+
+            let jumps = matches!(
+                instr,
+                Instruction::Jp(_)
+                    | Instruction::JpHl
+                    | Instruction::Call(_)
+                    | Instruction::Ret
+                    | Instruction::Reti
+                    | Instruction::Rst(_)
+                    | Instruction::Illegal(_)
+            );
+            if !jumps && !taken {
+                assert_eq!(cpu.pc, 0xC000 + instr.length() as u16, "{}", instr);
+            }
+        };
+
+        for opcode in 0x00..=0xFF {
+            for flags in [0x00, 0xF0] {
+                if opcode == 0xCB {
+                    for cb in 0x00..=0xFF {
+                        run([0xCB, cb, 0x00], flags);
+                    }
+                } else {
+                    run([opcode, 0x00, 0x00], flags);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn next_instruction() {
+        let memmap = MemoryMap::default();
+        let mut cpu = Cpu::load(&memmap);
+
+        memmap.write_byte(0xC000, 0xFA).unwrap();
+        memmap.write_byte(0xC001, 0x34).unwrap();
+        memmap.write_byte(0xC002, 0x12).unwrap();
+        cpu.pc = 0xC000;
+
+        assert_eq!(cpu.next_instruction(), Instruction::LdAMemNn(0x1234));
+        assert_eq!(cpu.next_instruction().to_string(), "LD A,($1234)");
+        assert_eq!(cpu.pc, 0xC000);
+    }
+
+    #[test]
+    fn interrupt_dispatch_takes_five_mcycles() {
+        let memmap = MemoryMap::default();
+        let mut cpu = Cpu::load(&memmap);
+
+        memmap.write_byte(IE_ADDR, 0x01).unwrap();
+        memmap.write_byte(IF_ADDR, 0x01).unwrap();
+        cpu.ime = true;
+
+        assert_eq!(cpu.step().unwrap(), 20);
+        assert_eq!(cpu.mcycles(), 5);
+    }
+
+    #[test]
+    fn halt_keeps_the_system_running() {
+        let memmap = MemoryMap::default();
+        let mut cpu = Cpu::load(&memmap);
+
+        // HALT, woken by the timer.
+        memmap.write_byte(0xC000, 0x76).unwrap();
+        memmap.write_byte(IE_ADDR, 0x04).unwrap();
+        memmap.write_byte(TIMA_ADDR, 0xFF).unwrap();
+        memmap.write_byte(TAC_ADDR, 0b101).unwrap();
+        cpu.pc = 0xC000;
+
+        cpu.step().unwrap();
+        assert!(cpu.halted);
+        for _ in 0..4 {
+            cpu.step().unwrap();
+        }
+        assert!(cpu.halted);
+        assert_eq!(memmap.read_byte(IF_ADDR).unwrap() & 0x04, 0x04);
+
+        // IME is clear, so HALT exits into the following NOP.
+        cpu.step().unwrap();
+        assert!(!cpu.halted);
+        assert_eq!(cpu.pc, 0xC002);
+    }
+
+    #[test]
+    fn reads_observe_the_timer_mid_instruction() {
+        let memmap = MemoryMap::default();
+        let mut cpu = Cpu::load(&memmap);
+
+        // NOP; LDH A,($05)
+        memmap.write_byte(0xC000, 0x00).unwrap();
+        memmap.write_byte(0xC001, 0xF0).unwrap();
+        memmap.write_byte(0xC002, 0x05).unwrap();
+        memmap.write_byte(TAC_ADDR, 0b101).unwrap();
+        cpu.pc = 0xC000;
+
+        // TIMA increments on the fourth M-cycle, which is the
+        // cycle the LDH reads it on.
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+        assert_eq!(cpu.reg.a, 0x01);
+    }
+
+    #[test]
+    fn tima_write_on_overflow_cycle_cancels_reload() {
+        let memmap = MemoryMap::default();
+        let mut cpu = Cpu::load(&memmap);
+
+        // NOP; LDH ($05),A; NOP
+        memmap.write_byte(0xC000, 0x00).unwrap();
+        memmap.write_byte(0xC001, 0xE0).unwrap();
+        memmap.write_byte(0xC002, 0x05).unwrap();
+        memmap.write_byte(0xC003, 0x00).unwrap();
+        memmap.write_byte(TMA_ADDR, 0x42).unwrap();
+        memmap.write_byte(TIMA_ADDR, 0xFF).unwrap();
+        memmap.write_byte(TAC_ADDR, 0b101).unwrap();
+        memmap.write_byte(IF_ADDR, 0x00).unwrap();
+        cpu.pc = 0xC000;
+        cpu.reg.a = 0x10;
+
+        // TIMA overflows on the LDH write cycle.
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+        assert_eq!(memmap.read_byte(TIMA_ADDR).unwrap(), 0x10);
+        assert_eq!(memmap.read_byte(IF_ADDR).unwrap() & 0x04, 0x00);
+    }
+
+    #[test]
+    fn new_is_blank() {
+        let mut cpu = Cpu::new([0u8; 0x10000]);
+
+        assert_eq!(cpu.pc(), 0x0000);
+        assert_eq!(cpu.sp(), 0x0000);
+        assert_eq!(cpu.registers().get_af(), 0x0000);
+        assert!(!cpu.ime());
+        assert!(cpu.bus().iter().all(|b| *b == 0x00));
+
+        cpu.set_pc(0xC000);
+        cpu.set_sp(0xD000);
+        cpu.set_ime(true);
+        cpu.registers_mut().a = 0x12;
+        assert_eq!(cpu.pc(), 0xC000);
+        assert_eq!(cpu.sp(), 0xD000);
+        assert!(cpu.ime());
+        assert_eq!(cpu.registers().a, 0x12);
+    }
+
+    #[test]
+    fn runs_on_a_flat_bus() {
+        let mut mem = [0u8; 0x10000];
+        // LD A,$42; LD ($C100),A; LDH A,($04)
+        mem[0xC000..0xC007].copy_from_slice(&[0x3E, 0x42, 0xEA, 0x00, 0xC1, 0xF0, 0x04]);
+        mem[0xFF04] = 0x99;
+        let mut cpu = Cpu::new(&mut mem);
+        cpu.set_pc(0xC000);
+
+        for _ in 0..3 {
+            cpu.step().unwrap();
+        }
+        assert_eq!(cpu.registers().a, 0x99);
+        assert_eq!(cpu.mcycles(), 2 + 4 + 3);
+        drop(cpu);
+        assert_eq!(mem[0xC100], 0x42);
+    }
+
+    #[test]
+    fn state_round_trip() {
+        let memmap = MemoryMap::default();
+        let mut cpu = Cpu::load(&memmap);
+        let state = CpuState {
+            a: 0x12,
+            f: 0xA0,
+            b: 0x34,
+            c: 0x56,
+            d: 0x78,
+            e: 0x9A,
+            h: 0xBC,
+            l: 0xDE,
+            sp: 0xDFF0,
+            pc: 0xC123,
+            ime: true,
+            ime_scheduled: false,
+            halted: true,
+            stopped: false,
+            halt_bug: false,
+            locked: false,
+        };
+
+        cpu.set_state(&state);
+        assert_eq!(cpu.state(), state);
+        assert_eq!(cpu.registers().get_bc(), 0x3456);
+        assert_eq!(cpu.registers().get_hl(), 0xBCDE);
+        assert!(cpu.halted());
+        assert!(!cpu.stopped());
+
+        cpu.set_halted(false);
+        cpu.set_stopped(true);
+        assert!(!cpu.state().halted);
+        assert!(cpu.state().stopped);
+    }
+
+    #[test]
+    fn debug_data_at_the_top_of_memory() {
+        let memmap = MemoryMap::default();
+        let mut cpu = Cpu::load(&memmap);
+        memmap.write_byte(0xFFFE, 0x12).unwrap();
+        memmap.write_byte(0xFFFF, 0x34).unwrap();
+        memmap.patch_byte(0x0000, 0x56).unwrap();
+        cpu.set_pc(0xFFFE);
+
+        let data = cpu.get_cpu_data_debug();
+        assert_eq!(data.pc, 0xFFFE);
+        assert_eq!((data.mem0, data.mem1, data.mem2), (0x12, 0x34, 0x56));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn state_serializes() {
+        let memmap = MemoryMap::default();
+        let cpu = Cpu::load(&memmap);
+
+        let json = serde_json::to_string(&cpu.state()).unwrap();
+        let state: CpuState = serde_json::from_str(&json).unwrap();
+        assert_eq!(state, cpu.state());
+    }
+
+    #[test]
+    fn stop_switches_cgb_speed() {
+        let memmap = MemoryMap::cgb();
+        let mut cpu = Cpu::load(&memmap);
+
+        // STOP; NOP
+        memmap.patch_byte(0x0100, 0x10).unwrap();
+        memmap.patch_byte(0x0101, 0x00).unwrap();
+        memmap.patch_byte(0x0102, 0x00).unwrap();
+        memmap.write_byte(KEY1_ADDR, 0x01).unwrap();
+        for _ in 0..64 * 0xAB {
+            memmap.tick();
+        }
+
+        assert_eq!(cpu.step().unwrap(), 4);
+        assert!(!cpu.stopped);
+        assert_eq!(cpu.pc, 0x0102);
+        assert!(memmap.is_double_speed());
+        assert_eq!(memmap.read_byte(KEY1_ADDR).unwrap(), 0xFE);
+
+        // DIV is reset and held for the whole pause.
+        for _ in 0..SWITCH_MCYCLES {
+            assert_eq!(cpu.step().unwrap(), 4);
+            assert_eq!(cpu.pc, 0x0102);
+        }
+        assert_eq!(memmap.read_byte(DIV_ADDR).unwrap(), 0x00);
+        assert_eq!(cpu.step().unwrap(), 4);
+        assert_eq!(cpu.pc, 0x0103);
+    }
+
+    #[test]
+    fn stop_ignores_key1_on_dmg() {
+        let memmap = MemoryMap::default();
+        let mut cpu = Cpu::load(&memmap);
+
+        memmap.patch_byte(0x0100, 0x10).unwrap();
+        memmap.write_byte(KEY1_ADDR, 0x01).unwrap();
+
+        cpu.step().unwrap();
+        assert!(cpu.stopped);
+        assert!(!memmap.is_double_speed());
+    }
+
+    #[test]
+    fn double_speed_timer_runs_off_the_cpu_clock() {
+        for (memmap, double) in [(MemoryMap::default(), false), (MemoryMap::cgb(), true)] {
+            if double {
+                memmap.write_byte(KEY1_ADDR, 0x01).unwrap();
+                memmap.speed_switch();
+                for _ in 0..SWITCH_MCYCLES {
+                    memmap.tick();
+                }
+            }
+            memmap.write_byte(DIV_ADDR, 0x00).unwrap();
+            let start = memmap.clocks();
+
+            // DIV counts every 64 CPU M-cycles at either speed, which
+            // is twice as often against the PPU clock in double speed.
+            for _ in 0..64 {
+                memmap.tick();
+            }
+            assert_eq!(memmap.read_byte(DIV_ADDR).unwrap(), 0x01);
+            let clocks = if double { 128 } else { 256 };
+            assert_eq!(memmap.clocks() - start, clocks);
+        }
+    }
+
+    #[test]
+    fn load_model_sets_post_boot_state() {
+        for model in [HardwareModel::DMG0, HardwareModel::SGB, HardwareModel::CGB] {
+            let memmap = MemoryMap::for_model(model);
+            let cpu = Cpu::load_model(&memmap, model);
+            assert_eq!(*cpu.registers(), model.registers());
+            assert_eq!((cpu.pc, cpu.sp), (0x0100, 0xFFFE));
+            assert_eq!(memmap.read_byte(0xFF40).unwrap(), 0x91);
+        }
+
+        let memmap = MemoryMap::default();
+        let cpu = Cpu::load(&memmap);
+        assert_eq!(cpu.registers().get_hl(), 0x014D);
+        assert_eq!(memmap.read_byte(0xFF26).unwrap(), 0xF1);
+    }
+
+    #[test]
+    fn boot_rom_hands_over_to_the_cartridge() {
+        let memmap = MemoryMap::default();
+        memmap.load_cartridge(&Cartridge {
+            data: vec![0x00; 0x8000],
+        });
+        // JP 0x00FC, then LD A, 0x01; LDH (0x50), A at the very end
+        // so PC runs on into the cartridge.
+        let mut boot = vec![0x00; 0x100];
+        boot[..3].copy_from_slice(&[0xC3, 0xFC, 0x00]);
+        boot[0xFC..].copy_from_slice(&[0x3E, 0x01, 0xE0, 0x50]);
+        memmap.load_boot_rom(boot);
+        let mut cpu = Cpu::boot(&memmap);
+        assert_eq!(cpu.pc, 0x0000);
+
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+        assert!(memmap.boot_rom_mapped());
+        cpu.step().unwrap();
+        assert!(!memmap.boot_rom_mapped());
+        assert_eq!(memmap.read_byte(0x0000).unwrap(), 0x00);
+        assert_eq!(cpu.pc, 0x0100);
+    }
+
+    #[test]
+    fn hdma_stalls_the_cpu() {
+        let memmap = MemoryMap::cgb();
+        let mut cpu = Cpu::load(&memmap);
+
+        // LD (0xFF55), A with A = 0x01; NOP
+        memmap.patch_byte(0x0100, 0xE0).unwrap();
+        memmap.patch_byte(0x0101, 0x55).unwrap();
+        memmap.patch_byte(0x0102, 0x00).unwrap();
+        cpu.reg.a = 0x01;
+
+        assert_eq!(cpu.step().unwrap(), 12);
+        assert!(memmap.stalled());
+        // Two blocks of 8 M-cycles.
+        for _ in 0..2 * HDMA_BLOCK_MCYCLES {
+            assert_eq!(cpu.step().unwrap(), 4);
+            assert_eq!(cpu.pc, 0x0102);
+        }
+        assert!(!memmap.stalled());
+        cpu.step().unwrap();
+        assert_eq!(cpu.pc, 0x0103);
+    }
+
+    /// Flat memory that fails every access to one address.
+    struct Broken {
+        mem: [u8; 0x10000],
+        addr: u16,
+    }
+
+    impl Broken {
+        fn new(addr: u16) -> Self {
+            Self {
+                mem: [0x00; 0x10000],
+                addr,
+            }
+        }
+
+        fn check(&self, addr: u16) -> Result<(), io::Error> {
+            if addr == self.addr {
+                return Err(io::Error::other("unmapped"));
+            }
+            Ok(())
+        }
+    }
+
+    impl Bus for Broken {
+        fn read(&mut self, addr: u16) -> Result<u8, io::Error> {
+            self.peek(addr)
+        }
+
+        fn write(&mut self, addr: u16, v: u8) -> Result<(), io::Error> {
+            self.check(addr)?;
+            self.mem.write(addr, v)
+        }
+
+        fn tick(&mut self) {}
+
+        fn peek(&self, addr: u16) -> Result<u8, io::Error> {
+            self.check(addr)?;
+            self.mem.peek(addr)
+        }
+    }
+
+    #[test]
+    fn interrupt_bus_error() {
+        let mut cpu = Cpu::new(Broken::new(IE_ADDR));
+        cpu.ime = true;
+
+        assert!(matches!(
+            cpu.step(),
+            Err(CpuError::Bus { addr: IE_ADDR, .. })
+        ));
+
+        cpu.halted = true;
+        assert!(matches!(
+            cpu.step(),
+            Err(CpuError::Bus { addr: IE_ADDR, .. })
+        ));
+    }
+
+    #[test]
+    fn load_model_bus_error() {
+        let mut cpu = Cpu::load_model(Broken::new(0xFF40), HardwareModel::DMG);
+        assert_eq!(cpu.pc, 0x0100);
+
+        assert!(matches!(
+            cpu.step(),
+            Err(CpuError::Bus { addr: 0xFF40, .. })
+        ));
+        assert!(cpu.step().is_ok());
+    }
+
+    #[test]
+    fn cb_opcode_fetch_is_blocked_by_oam_dma() {
+        let memmap = MemoryMap::default();
+        let mut cpu = Cpu::load(&memmap);
+
+        // CB in IE, which stays readable, and SWAP A after it in ROM.
+        memmap.write_byte(IE_ADDR, 0xCB).unwrap();
+        memmap.patch_byte(0x0000, 0x37).unwrap();
+        memmap.write_byte(DMA_ADDR, 0xC1).unwrap();
+        memmap.tick();
+        cpu.pc = 0xFFFF;
+        cpu.reg.a = 0x01;
+
+        // The second byte reads as 0xFF, SET 7,A.
+        assert_eq!(cpu.step().unwrap(), 8);
+        assert_eq!(cpu.reg.a, 0x81);
+        assert_eq!(cpu.pc, 0x0001);
+    }
 }
@@ -0,0 +1,261 @@
+pub const DMA_ADDR: u16 = 0xFF46;
+
+/// Bytes copied by an OAM DMA, all of OAM.
+pub const OAM_DMA_LEN: u8 = 0xA0;
+
+/// OAM DMA, started by writing the source page to DMA at 0xFF46.
+///
+/// After one M-cycle of setup a byte is copied from XX00-XX9F to
+/// 0xFE00-0xFE9F every M-cycle. While bytes are being copied the CPU
+/// can only reach 0xFF00-0xFFFF. Writing DMA again restarts the
+/// transfer, the old one carries on copying through the new one's
+/// setup cycle.
+#[derive(Debug, Default)]
+pub struct OamDma {
+    /// The last value written to DMA.
+    source: u8,
+    /// The source of the transfer waiting out its setup cycle.
+    starting: Option<u16>,
+    /// The source of the transfer copying, and the next byte.
+    running: Option<(u16, u8)>,
+    /// A byte is copied on the current M-cycle.
+    copying: bool,
+}
+
+impl OamDma {
+    /// True for DMA at 0xFF46.
+    ///
+    /// # Examples
+    /// ```
+    /// use rust_boy::dma::OamDma;
+    /// assert!(OamDma::contains(0xFF46));
+    /// assert!(!OamDma::contains(0xFF47));
+    /// ```
+    pub fn contains(addr: u16) -> bool {
+        addr == DMA_ADDR
+    }
+
+    pub fn read(&self, _addr: u16) -> u8 {
+        self.source
+    }
+
+    pub fn write(&mut self, _addr: u16, v: u8) {
+        self.source = v;
+        self.starting = Some((v as u16) << 8);
+    }
+
+    /// True while the CPU is locked out of everything below 0xFF00.
+    pub fn is_blocking(&self) -> bool {
+        self.copying
+    }
+
+    /// Advance by one M-cycle. Returns the address to copy from and
+    /// the OAM offset to copy to on this cycle, if any.
+    pub fn tick(&mut self) -> Option<(u16, u8)> {
+        let copy = self.running.map(|(source, i)| (source + i as u16, i));
+        self.running = match self.running {
+            Some((source, i)) if i + 1 < OAM_DMA_LEN => Some((source, i + 1)),
+            _ => None,
+        };
+        if let Some(source) = self.starting.take() {
+            self.running = Some((source, 0));
+        }
+        self.copying = copy.is_some();
+        copy
+    }
+}
+
+pub const HDMA1_ADDR: u16 = 0xFF51;
+pub const HDMA2_ADDR: u16 = 0xFF52;
+pub const HDMA3_ADDR: u16 = 0xFF53;
+pub const HDMA4_ADDR: u16 = 0xFF54;
+pub const HDMA5_ADDR: u16 = 0xFF55;
+
+/// Bytes in an HDMA block.
+pub const HDMA_BLOCK_LEN: u16 = 0x10;
+/// M-cycles a block holds the CPU off the bus in single speed, in
+/// double speed it is twice as many.
+pub const HDMA_BLOCK_MCYCLES: u16 = 8;
+
+/// The CGB VRAM DMA, HDMA1-5.
+///
+/// HDMA1-2 hold the source and HDMA3-4 the destination in VRAM, both
+/// 16 byte aligned. Writing the length in blocks, minus one, to HDMA5
+/// starts a transfer. With bit 7 clear it is a general purpose DMA
+/// that copies everything at once, with bit 7 set an HBlank DMA that
+/// copies a block every HBlank until it is done or stopped by
+/// writing HDMA5 with bit 7 clear. HDMA5 reads the blocks left,
+/// minus one, with bit 7 set when no transfer is running. On a DMG
+/// the registers read 0xFF and ignore writes.
+#[derive(Debug, Default)]
+pub struct Hdma {
+    cgb: bool,
+    source: u16,
+    /// Offset of the destination in VRAM.
+    dest: u16,
+    /// Blocks left minus one.
+    remaining: u8,
+    /// An HBlank transfer is running.
+    active: bool,
+}
+
+impl Hdma {
+    pub fn cgb() -> Self {
+        Self {
+            cgb: true,
+            remaining: 0x7F,
+            ..Self::default()
+        }
+    }
+
+    /// True for HDMA1-5 at 0xFF51-0xFF55.
+    ///
+    /// # Examples
+    /// ```
+    /// use rust_boy::dma::Hdma;
+    /// assert!(Hdma::contains(0xFF51));
+    /// assert!(Hdma::contains(0xFF55));
+    /// assert!(!Hdma::contains(0xFF56));
+    /// ```
+    pub fn contains(addr: u16) -> bool {
+        (HDMA1_ADDR..=HDMA5_ADDR).contains(&addr)
+    }
+
+    pub fn read(&self, addr: u16) -> u8 {
+        match addr {
+            HDMA5_ADDR if self.cgb => ((!self.active as u8) << 7) | self.remaining,
+            _ => 0xFF,
+        }
+    }
+
+    /// Write a register. Returns the number of blocks to copy now
+    /// when a general purpose DMA is started.
+    pub fn write(&mut self, addr: u16, v: u8) -> Option<u16> {
+        if !self.cgb {
+            return None;
+        }
+        match addr {
+            HDMA1_ADDR => self.source = (self.source & 0x00F0) | ((v as u16) << 8),
+            HDMA2_ADDR => self.source = (self.source & 0xFF00) | (v as u16 & 0xF0),
+            HDMA3_ADDR => self.dest = (self.dest & 0x00F0) | ((v as u16 & 0x1F) << 8),
+            HDMA4_ADDR => self.dest = (self.dest & 0x1F00) | (v as u16 & 0xF0),
+            _ if self.active && v & 0x80 == 0 => self.active = false,
+            _ if v & 0x80 != 0 => {
+                self.remaining = v & 0x7F;
+                self.active = true;
+            }
+            _ => {
+                self.remaining = 0x7F;
+                return Some((v & 0x7F) as u16 + 1);
+            }
+        }
+        None
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// The source and VRAM address of the next block, moving both on.
+    pub fn next_block(&mut self) -> (u16, u16) {
+        let block = (self.source, 0x8000 | self.dest);
+        self.source = self.source.wrapping_add(HDMA_BLOCK_LEN);
+        self.dest = (self.dest + HDMA_BLOCK_LEN) & 0x1FF0;
+        block
+    }
+
+    /// Called at the start of HBlank. Returns the next block of an
+    /// HBlank transfer.
+    pub fn hblank(&mut self) -> Option<(u16, u16)> {
+        if !self.active {
+            return None;
+        }
+        let block = self.next_block();
+        self.remaining = self.remaining.wrapping_sub(1) & 0x7F;
+        self.active = self.remaining != 0x7F;
+        Some(block)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn copies_after_a_setup_cycle() {
+        let mut dma = OamDma::default();
+        assert_eq!(dma.tick(), None);
+
+        dma.write(DMA_ADDR, 0xC1);
+        assert_eq!(dma.read(DMA_ADDR), 0xC1);
+        assert_eq!(dma.tick(), None);
+        assert!(!dma.is_blocking());
+        for i in 0..OAM_DMA_LEN {
+            assert_eq!(dma.tick(), Some((0xC100 + i as u16, i)));
+            assert!(dma.is_blocking());
+        }
+        assert_eq!(dma.tick(), None);
+        assert!(!dma.is_blocking());
+    }
+
+    #[test]
+    fn restart_overlaps_the_setup_cycle() {
+        let mut dma = OamDma::default();
+        dma.write(DMA_ADDR, 0xC1);
+        dma.tick();
+        for _ in 0..10 {
+            dma.tick();
+        }
+
+        dma.write(DMA_ADDR, 0xD2);
+        assert_eq!(dma.tick(), Some((0xC10A, 0x0A)));
+        assert_eq!(dma.tick(), Some((0xD200, 0x00)));
+        for _ in 1..OAM_DMA_LEN {
+            assert!(dma.tick().is_some());
+        }
+        assert_eq!(dma.tick(), None);
+    }
+
+    #[test]
+    fn hdma_registers() {
+        let mut dma = Hdma::default();
+        assert_eq!(dma.write(HDMA5_ADDR, 0x00), None);
+        assert_eq!(dma.read(HDMA5_ADDR), 0xFF);
+
+        let mut dma = Hdma::cgb();
+        assert_eq!(dma.read(HDMA5_ADDR), 0xFF);
+        dma.write(HDMA1_ADDR, 0xC1);
+        dma.write(HDMA2_ADDR, 0x2F);
+        dma.write(HDMA3_ADDR, 0xF8);
+        dma.write(HDMA4_ADDR, 0x4F);
+        assert_eq!(dma.read(HDMA1_ADDR), 0xFF);
+        assert_eq!(dma.write(HDMA5_ADDR, 0x02), Some(3));
+        assert_eq!(dma.read(HDMA5_ADDR), 0xFF);
+        assert_eq!(dma.next_block(), (0xC120, 0x9840));
+        assert_eq!(dma.next_block(), (0xC130, 0x9850));
+    }
+
+    #[test]
+    fn hblank_transfer() {
+        let mut dma = Hdma::cgb();
+        dma.write(HDMA1_ADDR, 0xD0);
+        dma.write(HDMA3_ADDR, 0x00);
+        assert_eq!(dma.write(HDMA5_ADDR, 0x81), None);
+        assert!(dma.is_active());
+        assert_eq!(dma.read(HDMA5_ADDR), 0x01);
+
+        assert_eq!(dma.hblank(), Some((0xD000, 0x8000)));
+        assert_eq!(dma.read(HDMA5_ADDR), 0x00);
+        assert_eq!(dma.hblank(), Some((0xD010, 0x8010)));
+        assert_eq!(dma.read(HDMA5_ADDR), 0xFF);
+        assert_eq!(dma.hblank(), None);
+
+        // Stopped early, the length left stays readable.
+        dma.write(HDMA5_ADDR, 0x85);
+        dma.hblank();
+        dma.write(HDMA5_ADDR, 0x00);
+        assert!(!dma.is_active());
+        assert_eq!(dma.read(HDMA5_ADDR), 0x84);
+        assert_eq!(dma.hblank(), None);
+    }
+}
@@ -16,6 +16,9 @@ pub enum CartridgeType {
     MBC5,
     MBC6,
     MBC7,
+    PocketCamera,
+    HuC3,
+    HuC1,
     Unknown,
 }
 
@@ -30,9 +33,55 @@ pub fn parse_cartridge_type(value: u8) -> CartridgeType {
         0x19..=0x1E => CartridgeType::MBC5,
         0x20 => CartridgeType::MBC6,
         0x22 => CartridgeType::MBC7,
+        0xFC => CartridgeType::PocketCamera,
+        0xFE => CartridgeType::HuC3,
+        0xFF => CartridgeType::HuC1,
         _ => CartridgeType::Unknown,
     }
 }
+
+/// The logo at 0x0104-0x0133 the boot ROM checks.
+pub const NINTENDO_LOGO: [u8; 48] = [
+    0xCE, 0xED, 0x66, 0x66, 0xCC, 0x0D, 0x00, 0x0B, 0x03, 0x73, 0x00, 0x83, 0x00, 0x0C, 0x00, 0x0D,
+    0x00, 0x08, 0x11, 0x1F, 0x88, 0x89, 0x00, 0x0E, 0xDC, 0xCC, 0x6E, 0xE6, 0xDD, 0xDD, 0xD9, 0x99,
+    0xBB, 0xBB, 0x67, 0x63, 0x6E, 0x0E, 0xEC, 0xCC, 0xDD, 0xDC, 0x99, 0x9F, 0xBB, 0xB9, 0x33, 0x3E,
+];
+
+/// Cartridge RAM in bytes for the RAM size byte at 0x0149.
+///
+/// # Examples
+/// ```
+/// use rust_boy::header::parse_ram_size;
+/// assert_eq!(parse_ram_size(0x00), 0);
+/// assert_eq!(parse_ram_size(0x03), 0x8000);
+/// ```
+pub fn parse_ram_size(value: u8) -> usize {
+    match value {
+        0x01 => 0x0800,
+        0x02 => 0x2000,
+        0x03 => 0x8000,
+        0x04 => 0x2_0000,
+        0x05 => 0x1_0000,
+        _ => 0,
+    }
+}
+
+/// True for the cartridge type bytes at 0x0147 of carts with a
+/// battery, whose RAM, or clock, should be kept between sessions.
+///
+/// # Examples
+/// ```
+/// use rust_boy::header::has_battery;
+/// assert!(has_battery(0x03));
+/// assert!(!has_battery(0x02));
+/// ```
+pub fn has_battery(value: u8) -> bool {
+    matches!(
+        value,
+        0x03 | 0x06 | 0x09 | 0x0D | 0x0F | 0x10 | 0x13 | 0x1B | 0x1E | 0x22 | 0xFC | 0xFE | 0xFF
+    )
+}
+
 pub struct Header {
     pub logo: Vec<u8>,
     pub title: String,
@@ -40,6 +89,7 @@ pub struct Header {
     pub old_licensee_code: u8,
     pub sgb_flag: bool,
     pub cartridge_type: CartridgeType,
+    pub battery: bool,
     pub rom_size: u8,
     pub ram_size: u8,
     pub destination_code: DestinationCode, // Should be enum
@@ -70,6 +120,7 @@ impl Header {
             old_licensee_code: 0, // TODO
             sgb_flag: false,
             cartridge_type: parse_cartridge_type(data[0x0147]),
+            battery: has_battery(data[0x0147]),
             rom_size: data[0x0148], // Make this an enum?
             ram_size: data[0x0149], // Make this an enum?
             destination_code,
@@ -80,7 +131,19 @@ impl Header {
     }
 
     pub fn is_compatible(&self) -> bool {
-        matches!(self.cartridge_type, CartridgeType::RomOnly)
+        matches!(
+            self.cartridge_type,
+            CartridgeType::RomOnly
+                | CartridgeType::MBC1
+                | CartridgeType::MBC2
+                | CartridgeType::MMM01
+                | CartridgeType::MBC3
+                | CartridgeType::MBC5
+                | CartridgeType::MBC7
+                | CartridgeType::PocketCamera
+                | CartridgeType::HuC1
+                | CartridgeType::HuC3
+        )
     }
 
     pub fn print_logo(&self) {
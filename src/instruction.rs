@@ -0,0 +1,640 @@
+use crate::registers::*;
+use std::fmt;
+
+/// The source operand of an 8 bit ALU instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operand {
+    Reg(StdReg),
+    Imm(u8),
+}
+
+impl From<Operand> for StdRegN {
+    fn from(op: Operand) -> Self {
+        match op {
+            Operand::Reg(reg) => reg.into(),
+            Operand::Imm(_) => StdRegN::N,
+        }
+    }
+}
+
+/// A single decoded SM83 instruction and its operands.
+///
+/// `StdReg::HL` is the `(HL)` memory operand, as it is
+/// everywhere else in the crate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+    Nop,
+    Halt,
+    Stop,
+    Di,
+    Ei,
+    Rlca,
+    Rrca,
+    Rla,
+    Rra,
+    Daa,
+    Cpl,
+    Scf,
+    Ccf,
+    LdRR(StdReg, StdReg),
+    LdRN(StdReg, u8),
+    LdRrNn(LoadRegnnn, u16),
+    LdAMemRr(LoadRegnnn),
+    LdMemRrA(LoadRegnnn),
+    LdAMemNn(u16),
+    LdMemNnA(u16),
+    LdiAMemHl,
+    LddAMemHl,
+    LdiMemHlA,
+    LddMemHlA,
+    LdhNA(u8),
+    LdhAN(u8),
+    LdhCA,
+    LdhAC,
+    LdNnSp(u16),
+    LdSpHl,
+    LdHlSpE(i8),
+    AddSpE(i8),
+    Inc8(StdReg),
+    Dec8(StdReg),
+    Inc16(LoadRegnnn),
+    Dec16(LoadRegnnn),
+    AddHl(LoadRegnnn),
+    Add(Operand),
+    Adc(Operand),
+    Sub(Operand),
+    Sbc(Operand),
+    And(Operand),
+    Xor(Operand),
+    Or(Operand),
+    Cp(Operand),
+    Rlc(StdReg),
+    Rrc(StdReg),
+    Rl(StdReg),
+    Rr(StdReg),
+    Sla(StdReg),
+    Sra(StdReg),
+    Swap(StdReg),
+    Srl(StdReg),
+    Bit(u8, StdReg),
+    Res(u8, StdReg),
+    Set(u8, StdReg),
+    Jp(u16),
+    JpCc(FlagCond, u16),
+    JpHl,
+    Jr(i8),
+    JrCc(FlagCond, i8),
+    Call(u16),
+    CallCc(FlagCond, u16),
+    Ret,
+    RetCc(FlagCond),
+    Reti,
+    Rst(u8),
+    Push(StackReg),
+    Pop(StackReg),
+    /// One of the eleven unused opcodes that hang the CPU.
+    Illegal(u8),
+}
+
+/// Decode the instruction at the start of bytes.
+///
+/// Operand bytes past the end of the slice read as zero,
+/// so a short slice still decodes to the right instruction.
+///
+/// # Examples
+/// ```
+/// use rust_boy::instruction::{decode, Instruction};
+/// use rust_boy::registers::{LoadRegnnn, StdReg};
+/// assert_eq!(decode(&[0x00]), Instruction::Nop);
+/// assert_eq!(decode(&[0x21, 0x34, 0x12]), Instruction::LdRrNn(LoadRegnnn::HL, 0x1234));
+/// assert_eq!(decode(&[0xCB, 0x7E]), Instruction::Bit(7, StdReg::HL));
+/// ```
+pub fn decode(bytes: &[u8]) -> Instruction {
+    use Instruction::*;
+
+    let opcode = bytes.first().copied().unwrap_or(0x00);
+    let n = bytes.get(1).copied().unwrap_or(0x00);
+    let nn = u16::from_le_bytes([n, bytes.get(2).copied().unwrap_or(0x00)]);
+    let e = n as i8;
+
+    let r = StdReg::from_bits(opcode);
+    let r_dst = StdReg::from_bits(opcode >> 3);
+    let rr = match (opcode >> 4) & 0b11 {
+        0 => LoadRegnnn::BC,
+        1 => LoadRegnnn::DE,
+        2 => LoadRegnnn::HL,
+        _ => LoadRegnnn::SP,
+    };
+    let qq = match (opcode >> 4) & 0b11 {
+        0 => StackReg::BC,
+        1 => StackReg::DE,
+        2 => StackReg::HL,
+        _ => StackReg::AF,
+    };
+    let cc = match (opcode >> 3) & 0b11 {
+        0 => FlagCond::NZ,
+        1 => FlagCond::Z,
+        2 => FlagCond::NC,
+        _ => FlagCond::C,
+    };
+
+    match opcode {
+        0x00 => Nop,
+        0x10 => Stop,
+        0x76 => Halt,
+        0xF3 => Di,
+        0xFB => Ei,
+        0x07 => Rlca,
+        0x0F => Rrca,
+        0x17 => Rla,
+        0x1F => Rra,
+        0x27 => Daa,
+        0x2F => Cpl,
+        0x37 => Scf,
+        0x3F => Ccf,
+        0x01 | 0x11 | 0x21 | 0x31 => LdRrNn(rr, nn),
+        0x02 | 0x12 => LdMemRrA(rr),
+        0x0A | 0x1A => LdAMemRr(rr),
+        0x22 => LdiMemHlA,
+        0x32 => LddMemHlA,
+        0x2A => LdiAMemHl,
+        0x3A => LddAMemHl,
+        0x03 | 0x13 | 0x23 | 0x33 => Inc16(rr),
+        0x0B | 0x1B | 0x2B | 0x3B => Dec16(rr),
+        0x09 | 0x19 | 0x29 | 0x39 => AddHl(rr),
+        0x04 | 0x0C | 0x14 | 0x1C | 0x24 | 0x2C | 0x34 | 0x3C => Inc8(r_dst),
+        0x05 | 0x0D | 0x15 | 0x1D | 0x25 | 0x2D | 0x35 | 0x3D => Dec8(r_dst),
+        0x06 | 0x0E | 0x16 | 0x1E | 0x26 | 0x2E | 0x36 | 0x3E => LdRN(r_dst, n),
+        0x08 => LdNnSp(nn),
+        0x18 => Jr(e),
+        0x20 | 0x28 | 0x30 | 0x38 => JrCc(cc, e),
+        0x40..=0x7F => LdRR(r_dst, r),
+        0x80..=0x87 => Add(Operand::Reg(r)),
+        0x88..=0x8F => Adc(Operand::Reg(r)),
+        0x90..=0x97 => Sub(Operand::Reg(r)),
+        0x98..=0x9F => Sbc(Operand::Reg(r)),
+        0xA0..=0xA7 => And(Operand::Reg(r)),
+        0xA8..=0xAF => Xor(Operand::Reg(r)),
+        0xB0..=0xB7 => Or(Operand::Reg(r)),
+        0xB8..=0xBF => Cp(Operand::Reg(r)),
+        0xC6 => Add(Operand::Imm(n)),
+        0xCE => Adc(Operand::Imm(n)),
+        0xD6 => Sub(Operand::Imm(n)),
+        0xDE => Sbc(Operand::Imm(n)),
+        0xE6 => And(Operand::Imm(n)),
+        0xEE => Xor(Operand::Imm(n)),
+        0xF6 => Or(Operand::Imm(n)),
+        0xFE => Cp(Operand::Imm(n)),
+        0xC0 | 0xC8 | 0xD0 | 0xD8 => RetCc(cc),
+        0xC2 | 0xCA | 0xD2 | 0xDA => JpCc(cc, nn),
+        0xC4 | 0xCC | 0xD4 | 0xDC => CallCc(cc, nn),
+        0xC1 | 0xD1 | 0xE1 | 0xF1 => Pop(qq),
+        0xC5 | 0xD5 | 0xE5 | 0xF5 => Push(qq),
+        0xC7 | 0xCF | 0xD7 | 0xDF | 0xE7 | 0xEF | 0xF7 | 0xFF => Rst(opcode & 0x38),
+        0xC3 => Jp(nn),
+        0xC9 => Ret,
+        0xCD => Call(nn),
+        0xD9 => Reti,
+        0xE0 => LdhNA(n),
+        0xF0 => LdhAN(n),
+        0xE2 => LdhCA,
+        0xF2 => LdhAC,
+        0xE8 => AddSpE(e),
+        0xF8 => LdHlSpE(e),
+        0xE9 => JpHl,
+        0xF9 => LdSpHl,
+        0xEA => LdMemNnA(nn),
+        0xFA => LdAMemNn(nn),
+        0xCB => decode_cb(n),
+        0xD3 | 0xDB | 0xDD | 0xE3 | 0xE4 | 0xEB | 0xEC | 0xED | 0xF4 | 0xFC | 0xFD => {
+            Illegal(opcode)
+        }
+    }
+}
+
+/// Decode the opcode that follows a 0xCB prefix.
+///
+/// # Examples
+/// ```
+/// use rust_boy::instruction::{decode_cb, Instruction};
+/// use rust_boy::registers::StdReg;
+/// assert_eq!(decode_cb(0x37), Instruction::Swap(StdReg::A));
+/// ```
+pub fn decode_cb(opcode: u8) -> Instruction {
+    use Instruction::*;
+
+    let reg = StdReg::from_bits(opcode);
+    let bit = (opcode >> 3) & 0b111;
+    match opcode {
+        0x00..=0x07 => Rlc(reg),
+        0x08..=0x0F => Rrc(reg),
+        0x10..=0x17 => Rl(reg),
+        0x18..=0x1F => Rr(reg),
+        0x20..=0x27 => Sla(reg),
+        0x28..=0x2F => Sra(reg),
+        0x30..=0x37 => Swap(reg),
+        0x38..=0x3F => Srl(reg),
+        0x40..=0x7F => Bit(bit, reg),
+        0x80..=0xBF => Res(bit, reg),
+        0xC0..=0xFF => Set(bit, reg),
+    }
+}
+
+/// Decode consecutive instructions from bytes, pairing each
+/// with its address when bytes is loaded at origin.
+///
+/// # Examples
+/// ```
+/// use rust_boy::instruction::disassemble;
+/// let listing: Vec<String> = disassemble(&[0x3E, 0x01, 0xC3, 0x50, 0x01], 0x0100)
+///     .iter()
+///     .map(|(addr, instr)| format!("{:04X} {}", addr, instr))
+///     .collect();
+/// assert_eq!(listing, ["0100 LD A,$01", "0102 JP $0150"]);
+/// ```
+pub fn disassemble(bytes: &[u8], origin: u16) -> Vec<(u16, Instruction)> {
+    let mut listing = Vec::new();
+    let mut offset = 0;
+    while offset < bytes.len() {
+        let instr = decode(&bytes[offset..]);
+        listing.push((origin.wrapping_add(offset as u16), instr));
+        offset += instr.length() as usize;
+    }
+    listing
+}
+
+impl Instruction {
+    /// Length of the instruction in bytes, including any prefix.
+    pub fn length(&self) -> u8 {
+        use Instruction::*;
+
+        match self {
+            LdRN(..) | LdhNA(_) | LdhAN(_) | LdHlSpE(_) | AddSpE(_) | Jr(_) | JrCc(..) => 2,
+            Add(Operand::Imm(_)) | Adc(Operand::Imm(_)) | Sub(Operand::Imm(_))
+            | Sbc(Operand::Imm(_)) | And(Operand::Imm(_)) | Xor(Operand::Imm(_))
+            | Or(Operand::Imm(_)) | Cp(Operand::Imm(_)) => 2,
+            Stop => 2,
+            LdRrNn(..) | LdAMemNn(_) | LdMemNnA(_) | LdNnSp(_) | Jp(_) | JpCc(..) | Call(_)
+            | CallCc(..) => 3,
+            _ if self.is_prefixed() => 2,
+            _ => 1,
+        }
+    }
+
+    /// True for the 0xCB prefixed instructions.
+    pub fn is_prefixed(&self) -> bool {
+        use Instruction::*;
+
+        matches!(
+            self,
+            Rlc(_)
+                | Rrc(_)
+                | Rl(_)
+                | Rr(_)
+                | Sla(_)
+                | Sra(_)
+                | Swap(_)
+                | Srl(_)
+                | Bit(..)
+                | Res(..)
+                | Set(..)
+        )
+    }
+
+    /// Clock cycles taken when a conditional branch is not
+    /// taken, or by any other instruction.
+    ///
+    /// # Examples
+    /// ```
+    /// use rust_boy::instruction::decode;
+    /// assert_eq!(decode(&[0x20, 0x05]).cycles(), 8);
+    /// assert_eq!(decode(&[0x34]).cycles(), 12);
+    /// ```
+    pub fn cycles(&self) -> u8 {
+        use Instruction::*;
+
+        let hl = |r: &StdReg| *r == StdReg::HL;
+        let alu = |op: &Operand| match op {
+            Operand::Reg(r) if !hl(r) => 4,
+            _ => 8,
+        };
+
+        match self {
+            Nop | Halt | Stop | Di | Ei => 4,
+            Rlca | Rrca | Rla | Rra | Daa | Cpl | Scf | Ccf => 4,
+            LdRR(r1, r2) if hl(r1) || hl(r2) => 8,
+            LdRR(..) => 4,
+            LdRN(r, _) if hl(r) => 12,
+            LdRN(..) => 8,
+            LdRrNn(..) => 12,
+            LdAMemRr(_) | LdMemRrA(_) => 8,
+            LdAMemNn(_) | LdMemNnA(_) => 16,
+            LdiAMemHl | LddAMemHl | LdiMemHlA | LddMemHlA => 8,
+            LdhNA(_) | LdhAN(_) => 12,
+            LdhCA | LdhAC => 8,
+            LdNnSp(_) => 20,
+            LdSpHl => 8,
+            LdHlSpE(_) => 12,
+            AddSpE(_) => 16,
+            Inc8(r) | Dec8(r) if hl(r) => 12,
+            Inc8(_) | Dec8(_) => 4,
+            Inc16(_) | Dec16(_) | AddHl(_) => 8,
+            Add(op) | Adc(op) | Sub(op) | Sbc(op) | And(op) | Xor(op) | Or(op) | Cp(op) => alu(op),
+            Bit(_, r) if hl(r) => 12,
+            Rlc(r)
+            | Rrc(r)
+            | Rl(r)
+            | Rr(r)
+            | Sla(r)
+            | Sra(r)
+            | Swap(r)
+            | Srl(r)
+            | Res(_, r)
+            | Set(_, r) => {
+                if hl(r) {
+                    16
+                } else {
+                    8
+                }
+            }
+            Bit(..) => 8,
+            Jp(_) => 16,
+            JpCc(..) => 12,
+            JpHl => 4,
+            Jr(_) => 12,
+            JrCc(..) => 8,
+            Call(_) => 24,
+            CallCc(..) => 12,
+            Ret | Reti => 16,
+            RetCc(_) => 8,
+            Rst(_) => 16,
+            Push(_) => 16,
+            Pop(_) => 12,
+            Illegal(_) => 4,
+        }
+    }
+
+    /// Clock cycles taken when a conditional branch is taken,
+    /// None for instructions that do not branch on a condition.
+    ///
+    /// # Examples
+    /// ```
+    /// use rust_boy::instruction::decode;
+    /// assert_eq!(decode(&[0xC0]).branch_cycles(), Some(20));
+    /// assert_eq!(decode(&[0xC9]).branch_cycles(), None);
+    /// ```
+    pub fn branch_cycles(&self) -> Option<u8> {
+        match self {
+            Instruction::JrCc(..) => Some(12),
+            Instruction::JpCc(..) => Some(16),
+            Instruction::CallCc(..) => Some(24),
+            Instruction::RetCc(_) => Some(20),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Operand::Reg(r) => write!(f, "{}", r),
+            Operand::Imm(n) => write!(f, "${:02X}", n),
+        }
+    }
+}
+
+impl fmt::Display for StdReg {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            StdReg::A => "A",
+            StdReg::B => "B",
+            StdReg::C => "C",
+            StdReg::D => "D",
+            StdReg::E => "E",
+            StdReg::H => "H",
+            StdReg::L => "L",
+            StdReg::HL => "(HL)",
+        };
+        f.write_str(name)
+    }
+}
+
+impl fmt::Display for LoadRegnnn {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            LoadRegnnn::BC => "BC",
+            LoadRegnnn::DE => "DE",
+            LoadRegnnn::HL => "HL",
+            LoadRegnnn::SP => "SP",
+        };
+        f.write_str(name)
+    }
+}
+
+impl fmt::Display for StackReg {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            StackReg::BC => "BC",
+            StackReg::DE => "DE",
+            StackReg::HL => "HL",
+            StackReg::AF => "AF",
+        };
+        f.write_str(name)
+    }
+}
+
+impl fmt::Display for FlagCond {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            FlagCond::NZ => "NZ",
+            FlagCond::Z => "Z",
+            FlagCond::NC => "NC",
+            FlagCond::C => "C",
+        };
+        f.write_str(name)
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use Instruction::*;
+
+        match self {
+            Nop => write!(f, "NOP"),
+            Halt => write!(f, "HALT"),
+            Stop => write!(f, "STOP"),
+            Di => write!(f, "DI"),
+            Ei => write!(f, "EI"),
+            Rlca => write!(f, "RLCA"),
+            Rrca => write!(f, "RRCA"),
+            Rla => write!(f, "RLA"),
+            Rra => write!(f, "RRA"),
+            Daa => write!(f, "DAA"),
+            Cpl => write!(f, "CPL"),
+            Scf => write!(f, "SCF"),
+            Ccf => write!(f, "CCF"),
+            LdRR(r1, r2) => write!(f, "LD {},{}", r1, r2),
+            LdRN(r, n) => write!(f, "LD {},${:02X}", r, n),
+            LdRrNn(rr, nn) => write!(f, "LD {},${:04X}", rr, nn),
+            LdAMemRr(rr) => write!(f, "LD A,({})", rr),
+            LdMemRrA(rr) => write!(f, "LD ({}),A", rr),
+            LdAMemNn(nn) => write!(f, "LD A,(${:04X})", nn),
+            LdMemNnA(nn) => write!(f, "LD (${:04X}),A", nn),
+            LdiAMemHl => write!(f, "LD A,(HL+)"),
+            LddAMemHl => write!(f, "LD A,(HL-)"),
+            LdiMemHlA => write!(f, "LD (HL+),A"),
+            LddMemHlA => write!(f, "LD (HL-),A"),
+            LdhNA(n) => write!(f, "LDH ($FF{:02X}),A", n),
+            LdhAN(n) => write!(f, "LDH A,($FF{:02X})", n),
+            LdhCA => write!(f, "LDH (C),A"),
+            LdhAC => write!(f, "LDH A,(C)"),
+            LdNnSp(nn) => write!(f, "LD (${:04X}),SP", nn),
+            LdSpHl => write!(f, "LD SP,HL"),
+            LdHlSpE(e) => write!(f, "LD HL,SP{:+}", e),
+            AddSpE(e) => write!(f, "ADD SP,{}", e),
+            Inc8(r) => write!(f, "INC {}", r),
+            Dec8(r) => write!(f, "DEC {}", r),
+            Inc16(rr) => write!(f, "INC {}", rr),
+            Dec16(rr) => write!(f, "DEC {}", rr),
+            AddHl(rr) => write!(f, "ADD HL,{}", rr),
+            Add(op) => write!(f, "ADD A,{}", op),
+            Adc(op) => write!(f, "ADC A,{}", op),
+            Sub(op) => write!(f, "SUB A,{}", op),
+            Sbc(op) => write!(f, "SBC A,{}", op),
+            And(op) => write!(f, "AND A,{}", op),
+            Xor(op) => write!(f, "XOR A,{}", op),
+            Or(op) => write!(f, "OR A,{}", op),
+            Cp(op) => write!(f, "CP A,{}", op),
+            Rlc(r) => write!(f, "RLC {}", r),
+            Rrc(r) => write!(f, "RRC {}", r),
+            Rl(r) => write!(f, "RL {}", r),
+            Rr(r) => write!(f, "RR {}", r),
+            Sla(r) => write!(f, "SLA {}", r),
+            Sra(r) => write!(f, "SRA {}", r),
+            Swap(r) => write!(f, "SWAP {}", r),
+            Srl(r) => write!(f, "SRL {}", r),
+            Bit(b, r) => write!(f, "BIT {},{}", b, r),
+            Res(b, r) => write!(f, "RES {},{}", b, r),
+            Set(b, r) => write!(f, "SET {},{}", b, r),
+            Jp(nn) => write!(f, "JP ${:04X}", nn),
+            JpCc(cc, nn) => write!(f, "JP {},${:04X}", cc, nn),
+            JpHl => write!(f, "JP HL"),
+            Jr(e) => write!(f, "JR {}", e),
+            JrCc(cc, e) => write!(f, "JR {},{}", cc, e),
+            Call(nn) => write!(f, "CALL ${:04X}", nn),
+            CallCc(cc, nn) => write!(f, "CALL {},${:04X}", cc, nn),
+            Ret => write!(f, "RET"),
+            RetCc(cc) => write!(f, "RET {}", cc),
+            Reti => write!(f, "RETI"),
+            Rst(v) => write!(f, "RST ${:02X}", v),
+            Push(qq) => write!(f, "PUSH {}", qq),
+            Pop(qq) => write!(f, "POP {}", qq),
+            Illegal(op) => write!(f, "ILLEGAL ${:02X}", op),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_cb_groups() {
+        assert_eq!(decode_cb(0x00), Instruction::Rlc(StdReg::B));
+        assert_eq!(decode_cb(0x0E), Instruction::Rrc(StdReg::HL));
+        assert_eq!(decode_cb(0x17), Instruction::Rl(StdReg::A));
+        assert_eq!(decode_cb(0x19), Instruction::Rr(StdReg::C));
+        assert_eq!(decode_cb(0x22), Instruction::Sla(StdReg::D));
+        assert_eq!(decode_cb(0x2B), Instruction::Sra(StdReg::E));
+        assert_eq!(decode_cb(0x34), Instruction::Swap(StdReg::H));
+        assert_eq!(decode_cb(0x3D), Instruction::Srl(StdReg::L));
+        assert_eq!(decode_cb(0x40), Instruction::Bit(0, StdReg::B));
+        assert_eq!(decode_cb(0x7E), Instruction::Bit(7, StdReg::HL));
+        assert_eq!(decode_cb(0x9F), Instruction::Res(3, StdReg::A));
+        assert_eq!(decode_cb(0xC6), Instruction::Set(0, StdReg::HL));
+        assert_eq!(decode_cb(0xFF), Instruction::Set(7, StdReg::A));
+
+        for opcode in 0..=0xFF {
+            assert!(decode_cb(opcode).is_prefixed());
+            assert_eq!(decode(&[0xCB, opcode]), decode_cb(opcode));
+        }
+    }
+
+    #[test]
+    fn length_of_every_opcode() {
+        let three = [
+            0x01, 0x08, 0x11, 0x21, 0x31, 0xC2, 0xC3, 0xC4, 0xCA, 0xCC, 0xCD, 0xD2, 0xD4, 0xDA,
+            0xDC, 0xEA, 0xFA,
+        ];
+        let two = [
+            0x06, 0x0E, 0x10, 0x16, 0x18, 0x1E, 0x20, 0x26, 0x28, 0x2E, 0x30, 0x36, 0x38, 0x3E,
+            0xC6, 0xCB, 0xCE, 0xD6, 0xDE, 0xE0, 0xE6, 0xE8, 0xEE, 0xF0, 0xF6, 0xF8, 0xFE,
+        ];
+
+        for opcode in 0..=0xFF {
+            let expected = if three.contains(&opcode) {
+                3
+            } else if two.contains(&opcode) {
+                2
+            } else {
+                1
+            };
+            assert_eq!(
+                decode(&[opcode]).length(),
+                expected,
+                "opcode {:#04X}",
+                opcode
+            );
+        }
+    }
+
+    #[test]
+    fn display() {
+        let text = |bytes: &[u8]| decode(bytes).to_string();
+
+        assert_eq!(text(&[0x00]), "NOP");
+        assert_eq!(text(&[0x78]), "LD A,B");
+        assert_eq!(text(&[0x36, 0x42]), "LD (HL),$42");
+        assert_eq!(text(&[0x31, 0xFE, 0xFF]), "LD SP,$FFFE");
+        assert_eq!(text(&[0x1A]), "LD A,(DE)");
+        assert_eq!(text(&[0xEA, 0x00, 0xC0]), "LD ($C000),A");
+        assert_eq!(text(&[0x2A]), "LD A,(HL+)");
+        assert_eq!(text(&[0xE0, 0x40]), "LDH ($FF40),A");
+        assert_eq!(text(&[0xF8, 0xFE]), "LD HL,SP-2");
+        assert_eq!(text(&[0xF8, 0x02]), "LD HL,SP+2");
+        assert_eq!(text(&[0xE8, 0xFF]), "ADD SP,-1");
+        assert_eq!(text(&[0xFE, 0x90]), "CP A,$90");
+        assert_eq!(text(&[0xAF]), "XOR A,A");
+        assert_eq!(text(&[0xCB, 0x7E]), "BIT 7,(HL)");
+        assert_eq!(text(&[0x20, 0xFB]), "JR NZ,-5");
+        assert_eq!(text(&[0xCC, 0x34, 0x12]), "CALL Z,$1234");
+        assert_eq!(text(&[0xD8]), "RET C");
+        assert_eq!(text(&[0xFF]), "RST $38");
+        assert_eq!(text(&[0xF5]), "PUSH AF");
+        assert_eq!(text(&[0xDD]), "ILLEGAL $DD");
+    }
+
+    #[test]
+    fn disassemble_truncated_buffer() {
+        // The JP is missing its high byte, and the LD its operand.
+        let listing = disassemble(&[0x00, 0xC3, 0x50], 0x0100);
+        assert_eq!(
+            listing,
+            [
+                (0x0100, Instruction::Nop),
+                (0x0101, Instruction::Jp(0x0050))
+            ]
+        );
+
+        let listing = disassemble(&[0xAF, 0x3E], 0xFFFF);
+        assert_eq!(
+            listing,
+            [
+                (0xFFFF, Instruction::Xor(Operand::Reg(StdReg::A))),
+                (0x0000, Instruction::LdRN(StdReg::A, 0x00)),
+            ]
+        );
+
+        assert!(disassemble(&[], 0x0000).is_empty());
+    }
+}
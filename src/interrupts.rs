@@ -1,4 +1,64 @@
-use crate::memorymap::MemoryMap;
+use crate::bus::Bus;
+use std::io;
+
+pub const IF_ADDR: u16 = 0xFF0F;
+pub const IE_ADDR: u16 = 0xFFFF;
+
+/// The five interrupt sources, in priority order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InterruptKind {
+    VBlank,
+    LcdStat,
+    Timer,
+    Serial,
+    Joypad,
+}
+
+impl InterruptKind {
+    pub const ALL: [InterruptKind; 5] = [
+        InterruptKind::VBlank,
+        InterruptKind::LcdStat,
+        InterruptKind::Timer,
+        InterruptKind::Serial,
+        InterruptKind::Joypad,
+    ];
+
+    /// The bit of this interrupt in IE and IF.
+    ///
+    /// # Examples
+    /// ```
+    /// use rust_boy::interrupts::InterruptKind;
+    /// assert_eq!(InterruptKind::VBlank.bit(), 0b0000_0001);
+    /// assert_eq!(InterruptKind::Joypad.bit(), 0b0001_0000);
+    /// ```
+    pub fn bit(&self) -> u8 {
+        match self {
+            InterruptKind::VBlank => 0b0000_0001,
+            InterruptKind::LcdStat => 0b0000_0010,
+            InterruptKind::Timer => 0b0000_0100,
+            InterruptKind::Serial => 0b0000_1000,
+            InterruptKind::Joypad => 0b0001_0000,
+        }
+    }
+
+    /// The address the CPU jumps to when servicing this interrupt.
+    ///
+    /// # Examples
+    /// ```
+    /// use rust_boy::interrupts::InterruptKind;
+    /// assert_eq!(InterruptKind::VBlank.vector(), 0x0040);
+    /// assert_eq!(InterruptKind::Joypad.vector(), 0x0060);
+    /// ```
+    pub fn vector(&self) -> u16 {
+        match self {
+            InterruptKind::VBlank => 0x0040,
+            InterruptKind::LcdStat => 0x0048,
+            InterruptKind::Timer => 0x0050,
+            InterruptKind::Serial => 0x0058,
+            InterruptKind::Joypad => 0x0060,
+        }
+    }
+}
 
 #[derive(Default, Debug)]
 pub struct Interrupt {
@@ -15,41 +75,88 @@ impl Interrupt {
     }
 
     pub fn decode(&mut self, byte: &u8) {
-        let mask = 0b0000_0001;
-        self.vblank = self.is_hot(byte, mask << 0);
-        self.lcd_stat = self.is_hot(byte, mask << 1);
-        self.timer = self.is_hot(byte, mask << 2);
-        self.serial = self.is_hot(byte, mask << 3);
-        self.joypad = self.is_hot(byte, mask << 4);
+        self.vblank = self.is_hot(byte, InterruptKind::VBlank.bit());
+        self.lcd_stat = self.is_hot(byte, InterruptKind::LcdStat.bit());
+        self.timer = self.is_hot(byte, InterruptKind::Timer.bit());
+        self.serial = self.is_hot(byte, InterruptKind::Serial.bit());
+        self.joypad = self.is_hot(byte, InterruptKind::Joypad.bit());
+    }
+
+    /// Pack the flags back into the low five bits of a register.
+    pub fn encode(&self) -> u8 {
+        InterruptKind::ALL
+            .iter()
+            .filter(|kind| self.is_set(**kind))
+            .fold(0x00, |acc, kind| acc | kind.bit())
+    }
+
+    pub fn is_set(&self, kind: InterruptKind) -> bool {
+        match kind {
+            InterruptKind::VBlank => self.vblank,
+            InterruptKind::LcdStat => self.lcd_stat,
+            InterruptKind::Timer => self.timer,
+            InterruptKind::Serial => self.serial,
+            InterruptKind::Joypad => self.joypad,
+        }
     }
 }
 
-pub struct InterruptHandler<'m> {
-    mem: &'m MemoryMap,
+/// Models the IE (0xFFFF) and IF (0xFF0F) registers.
+#[derive(Default)]
+pub struct InterruptHandler {
     interrupt_enable: Interrupt,
     interrupt_flags: Interrupt,
 }
 
-impl<'m> InterruptHandler<'m> {
-    pub fn new(mem: &'m MemoryMap) -> Self {
-        Self {
-            mem,
-            interrupt_enable: Interrupt::default(),
-            interrupt_flags: Interrupt::default(),
+impl InterruptHandler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the highest priority interrupt that is both
+    /// requested and enabled, without acknowledging it.
+    pub fn pending<B: Bus>(&mut self, mem: &B) -> io::Result<Option<InterruptKind>> {
+        self.update_ie(mem)?;
+        self.update_if(mem)?;
+        let kind = InterruptKind::ALL
+            .into_iter()
+            .find(|kind| self.interrupt_enable.is_set(*kind) && self.interrupt_flags.is_set(*kind));
+        Ok(kind)
+    }
+
+    /// Acknowledge the highest priority pending interrupt and
+    /// return the vector to jump to, if there is one.
+    pub fn service<B: Bus>(&mut self, mem: &mut B) -> io::Result<Option<u16>> {
+        match self.pending(mem)? {
+            Some(kind) => {
+                self.acknowledge(mem, kind)?;
+                Ok(Some(kind.vector()))
+            }
+            None => Ok(None),
         }
     }
 
-    pub fn service(&self) -> u16 {
-        0
+    /// Set the IF bit for kind.
+    pub fn request<B: Bus>(&mut self, mem: &mut B, kind: InterruptKind) -> io::Result<()> {
+        let flags = mem.peek(IF_ADDR)?;
+        mem.write(IF_ADDR, flags | kind.bit())?;
+        self.update_if(mem)
+    }
+
+    /// Clear the IF bit for kind.
+    pub fn acknowledge<B: Bus>(&mut self, mem: &mut B, kind: InterruptKind) -> io::Result<()> {
+        let flags = mem.peek(IF_ADDR)?;
+        mem.write(IF_ADDR, flags & !kind.bit())?;
+        self.update_if(mem)
     }
 
-    pub fn update_ie(&mut self) {
-        self.interrupt_enable.decode(&self.mem.read_byte(0xFFFF).unwrap());
-        println!("interrupt_enable == {:?}", self.interrupt_enable);
+    pub fn update_ie<B: Bus>(&mut self, mem: &B) -> io::Result<()> {
+        self.interrupt_enable.decode(&mem.peek(IE_ADDR)?);
+        Ok(())
     }
 
-    pub fn update_if(&mut self) {
-        self.interrupt_flags.decode(&self.mem.read_byte(0xFF0F).unwrap());
-        println!("interrupt_flags == {:?}", self.interrupt_flags);
+    pub fn update_if<B: Bus>(&mut self, mem: &B) -> io::Result<()> {
+        self.interrupt_flags.decode(&mem.peek(IF_ADDR)?);
+        Ok(())
     }
 }
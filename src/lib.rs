@@ -1,8 +1,17 @@
+pub mod alu;
+pub mod bus;
 pub mod cartridge;
 pub mod cpu;
+pub mod dma;
 pub mod header;
+pub mod instruction;
 pub mod interrupts;
+pub mod mbc;
 pub mod memorymap;
+pub mod model;
 // pub mod oam;
 pub mod registers;
+pub mod save;
+pub mod speed;
 pub mod tile;
+pub mod timer;
@@ -1,8 +1,12 @@
-use std::env::{self, args};
 use rust_boy::cartridge::Cartridge;
 use rust_boy::cpu::Cpu;
-use rust_boy::interrupts::InterruptHandler;
+use rust_boy::mbc::{ImageFiles, MapperOptions};
 use rust_boy::memorymap::MemoryMap;
+use rust_boy::model::HardwareModel;
+use rust_boy::save::SaveFile;
+use std::env;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 
 fn main() {
     // LOAD CARTRIDGE
@@ -10,14 +14,58 @@ fn main() {
     let rom_path = &args[1];
     println!("{}", rom_path);
     let cartridge = Cartridge::load(rom_path);
-    let memmap = MemoryMap::default();
-    memmap.load_cartridge(&cartridge);
-    let mut cpu = Cpu::load(&memmap);
-    // let mut interrupt = InterruptHandler::new(&memmap);
-    //
-    let mut cycles : u64 = 0;
-
-    loop {
+    let option = |name: &str| {
+        args.iter()
+            .position(|a| a == name)
+            .and_then(|i| args.get(i + 1))
+    };
+
+    // --model <dmg0|dmg|mgb|sgb|sgb2|cgb|agb> picks the hardware.
+    let model = match option("--model").map(|m| m.parse::<HardwareModel>()) {
+        Some(Ok(model)) => model,
+        Some(Err(e)) => {
+            eprintln!("{}", e);
+            HardwareModel::default()
+        }
+        None => HardwareModel::default(),
+    };
+
+    let mut options = MapperOptions::default();
+    // --camera <image or directory of images> feeds the Pocket Camera.
+    if let Some(path) = option("--camera") {
+        match ImageFiles::open(path) {
+            Ok(images) => options.camera = Some(Box::new(images)),
+            Err(e) => eprintln!("Could not load camera images: {}", e),
+        }
+    }
+    let memmap = MemoryMap::for_model(model);
+    memmap.load_cartridge_with(&cartridge, options);
+    let mut save = SaveFile::for_rom(rom_path);
+    if let Err(e) = save.load(&memmap) {
+        eprintln!("Could not load {}: {}", save.path().display(), e);
+    }
+    // --boot <boot ROM> runs it, otherwise the CPU starts where the
+    // model's boot ROM would leave it.
+    let mut cpu = match option("--boot").map(std::fs::read) {
+        Some(Ok(boot_rom)) => {
+            memmap.load_boot_rom(boot_rom);
+            Cpu::boot(&memmap)
+        }
+        Some(Err(e)) => {
+            eprintln!("Could not load boot ROM: {}", e);
+            Cpu::load_model(&memmap, model)
+        }
+        None => Cpu::load_model(&memmap, model),
+    };
+
+    // Ctrl-C or SIGTERM ends the loop so the save below is written.
+    let quit = Arc::new(AtomicBool::new(false));
+    let handler_quit = Arc::clone(&quit);
+    if let Err(e) = ctrlc::set_handler(move || handler_quit.store(true, Ordering::SeqCst)) {
+        eprintln!("Could not set the quit handler: {}", e);
+    }
+
+    while !quit.load(Ordering::SeqCst) {
         let cpud = cpu.get_cpu_data_debug();
 
         println!(
@@ -38,8 +86,16 @@ fn main() {
             cpud.mem3,
         );
 
-        cycles += cpu.step() as u64;
-        // interrupt.update_ie();
-        // interrupt.update_if();
+        if let Err(e) = cpu.step() {
+            eprintln!("{}", e);
+            break;
+        }
+        if let Err(e) = save.update(&memmap) {
+            eprintln!("Could not save {}: {}", save.path().display(), e);
+        }
+    }
+
+    if let Err(e) = save.flush(&memmap) {
+        eprintln!("Could not save {}: {}", save.path().display(), e);
     }
 }
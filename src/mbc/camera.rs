@@ -0,0 +1,446 @@
+use super::{banked_rom, load_into, ram_offset, wrap_rom_bank, Mapper, RAM_BANK_SIZE};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+pub const SENSOR_WIDTH: usize = 128;
+pub const SENSOR_HEIGHT: usize = 112;
+
+/// Where captured images are written in RAM bank 0, as 16x14 tiles.
+const IMAGE_ADDR: usize = 0x0100;
+/// Registers at 0xA000-0xA035, repeating every 0x80 bytes.
+const REGISTERS: usize = 0x36;
+const DITHER_MATRIX: usize = 0x06;
+/// The exposure that leaves the source image as it is.
+const NEUTRAL_EXPOSURE: u32 = 0x0800;
+
+/// What the Pocket Camera's sensor sees.
+pub trait CameraSource {
+    /// A SENSOR_WIDTH by SENSOR_HEIGHT frame of 8 bit brightness, row
+    /// by row, called once per capture.
+    fn frame(&mut self) -> Vec<u8>;
+}
+
+/// A camera feed from images on disk, binary PGM or PPM files of any
+/// size. A still image is seen on every capture, a sequence moves on
+/// a frame per capture and loops.
+pub struct ImageFiles {
+    frames: Vec<Vec<u8>>,
+    next: usize,
+}
+
+impl ImageFiles {
+    /// Load the image at path, or every .pgm and .ppm file in it, in
+    /// name order, if it is a directory.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref();
+        let paths = if path.is_dir() {
+            let mut paths: Vec<PathBuf> = fs::read_dir(path)?
+                .map(|entry| entry.map(|e| e.path()))
+                .collect::<io::Result<_>>()?;
+            paths.retain(|p| {
+                p.extension()
+                    .is_some_and(|e| e.eq_ignore_ascii_case("pgm") || e.eq_ignore_ascii_case("ppm"))
+            });
+            paths.sort();
+            paths
+        } else {
+            vec![path.to_path_buf()]
+        };
+        if paths.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::NotFound, "No images found"));
+        }
+
+        let frames = paths
+            .iter()
+            .map(|p| decode_pnm(&fs::read(p)?))
+            .collect::<io::Result<_>>()?;
+        Ok(Self { frames, next: 0 })
+    }
+}
+
+impl CameraSource for ImageFiles {
+    fn frame(&mut self) -> Vec<u8> {
+        let frame = self.frames[self.next].clone();
+        self.next = (self.next + 1) % self.frames.len();
+        frame
+    }
+}
+
+/// Decode a binary PGM (P5) or PPM (P6) with 8 bit samples and scale
+/// it to the sensor size.
+fn decode_pnm(data: &[u8]) -> io::Result<Vec<u8>> {
+    let invalid = |msg| io::Error::new(io::ErrorKind::InvalidData, msg);
+
+    // The header is the magic, width, height and maxval separated by
+    // whitespace and comments, then one whitespace byte.
+    let mut fields = Vec::new();
+    let mut i = 0;
+    while fields.len() < 4 {
+        match data.get(i) {
+            None => return Err(invalid("Truncated image header")),
+            Some(b'#') => {
+                while data.get(i).is_some_and(|&b| b != b'\n') {
+                    i += 1;
+                }
+            }
+            Some(b) if b.is_ascii_whitespace() => i += 1,
+            Some(_) => {
+                let start = i;
+                while data.get(i).is_some_and(|b| !b.is_ascii_whitespace()) {
+                    i += 1;
+                }
+                fields.push(String::from_utf8_lossy(&data[start..i]).into_owned());
+            }
+        }
+    }
+    let channels = match fields[0].as_str() {
+        "P5" => 1,
+        "P6" => 3,
+        _ => return Err(invalid("Not a binary PGM or PPM image")),
+    };
+    let number = |s: &str| s.parse::<usize>().map_err(|_| invalid("Bad image header"));
+    let (width, height, maxval) = (
+        number(&fields[1])?,
+        number(&fields[2])?,
+        number(&fields[3])?,
+    );
+    if width == 0 || height == 0 || maxval == 0 || maxval > 255 {
+        return Err(invalid("Unsupported image size or depth"));
+    }
+    let end = width
+        .checked_mul(height)
+        .and_then(|n| n.checked_mul(channels))
+        .and_then(|n| n.checked_add(i + 1))
+        .ok_or_else(|| invalid("Unsupported image size or depth"))?;
+    let pixels = data
+        .get(i + 1..end)
+        .ok_or_else(|| invalid("Truncated image data"))?;
+
+    let mut frame = Vec::with_capacity(SENSOR_WIDTH * SENSOR_HEIGHT);
+    for y in 0..SENSOR_HEIGHT {
+        for x in 0..SENSOR_WIDTH {
+            let at = ((y * height / SENSOR_HEIGHT) * width + x * width / SENSOR_WIDTH) * channels;
+            let luma = match channels {
+                1 => pixels[at] as usize,
+                _ => {
+                    let (r, g, b) = (pixels[at], pixels[at + 1], pixels[at + 2]);
+                    (299 * r as usize + 587 * g as usize + 114 * b as usize) / 1000
+                }
+            };
+            frame.push((luma * 255 / maxval) as u8);
+        }
+    }
+    Ok(frame)
+}
+
+/// The Pocket Camera, 1MB of ROM, 128KB of battery backed RAM and a
+/// 128x112 image sensor.
+///
+/// RAM bank values with bit 4 set map the sensor registers over RAM.
+/// Writing 1 to bit 0 of 0xA000 starts a capture, which takes time
+/// depending on the exposure in 0xA002-0xA003, then the image is
+/// dithered through the 4x4 matrix of thresholds at 0xA006-0xA035
+/// and written to RAM bank 0 as tiles. Bit 3 of 0xA004 inverts the
+/// image, the gain and edge enhancement registers are not emulated.
+pub struct Camera {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    source: Option<Box<dyn CameraSource>>,
+    ram_enabled: bool,
+    rom_bank: u8,
+    ram_bank: u8,
+    registers: [u8; REGISTERS],
+    /// Clocks left until the capture running finishes.
+    busy: u64,
+}
+
+impl Camera {
+    pub fn new(rom: Vec<u8>, source: Option<Box<dyn CameraSource>>) -> Self {
+        Self {
+            rom,
+            ram: vec![0x00; 16 * RAM_BANK_SIZE],
+            source,
+            ram_enabled: false,
+            rom_bank: 0x01,
+            ram_bank: 0x00,
+            registers: [0x00; REGISTERS],
+            busy: 0,
+        }
+    }
+
+    fn registers_mapped(&self) -> bool {
+        self.ram_bank & 0x10 != 0
+    }
+
+    fn ram_offset(&self, addr: u16) -> Option<usize> {
+        ram_offset(&self.ram, self.ram_bank as usize & 0x0F, addr)
+    }
+
+    fn exposure(&self) -> u32 {
+        ((self.registers[0x02] as u32) << 8) | self.registers[0x03] as u32
+    }
+
+    /// Clocks at the fixed 4.19MHz rate a capture takes.
+    fn capture_clocks(&self) -> u64 {
+        let n = if self.registers[0x01] & 0x80 != 0 {
+            0
+        } else {
+            512
+        };
+        32446 + n + 16 * self.exposure() as u64
+    }
+
+    fn capture(&mut self) {
+        let frame = match &mut self.source {
+            Some(source) => source.frame(),
+            None => vec![0x80; SENSOR_WIDTH * SENSOR_HEIGHT],
+        };
+        let invert = self.registers[0x04] & 0x08 != 0;
+
+        for y in 0..SENSOR_HEIGHT {
+            for x in 0..SENSOR_WIDTH {
+                let seen = frame.get(y * SENSOR_WIDTH + x).copied().unwrap_or(0x00) as u32;
+                let mut v = (seen * self.exposure() / NEUTRAL_EXPOSURE).min(0xFF) as u8;
+                if invert {
+                    v = !v;
+                }
+
+                let at = DITHER_MATRIX + ((y % 4) * 4 + x % 4) * 3;
+                let thresholds = &self.registers[at..at + 3];
+                let color = 3 - thresholds.iter().take_while(|&&t| v >= t).count() as u8;
+
+                let tile = (y / 8) * (SENSOR_WIDTH / 8) + x / 8;
+                let row = IMAGE_ADDR + tile * 16 + (y % 8) * 2;
+                let bit = 0x80 >> (x % 8);
+                for (plane, set) in [(0, color & 0x01 != 0), (1, color & 0x02 != 0)] {
+                    if set {
+                        self.ram[row + plane] |= bit;
+                    } else {
+                        self.ram[row + plane] &= !bit;
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Mapper for Camera {
+    fn read_rom(&self, addr: u16) -> u8 {
+        banked_rom(&self.rom, self.rom_bank(addr), addr)
+    }
+
+    fn write_rom(&mut self, addr: u16, v: u8) {
+        match addr {
+            0x0000..=0x1FFF => self.ram_enabled = v & 0x0F == 0x0A,
+            // Bank 0 can be mapped at 0x4000.
+            0x2000..=0x3FFF => self.rom_bank = v & 0x3F,
+            0x4000..=0x5FFF => self.ram_bank = v & 0x1F,
+            _ => {}
+        }
+    }
+
+    /// RAM reads need no enable, but read 0x00 during a capture. Of the
+    /// registers only 0xA000 can be read.
+    fn read_ram(&self, addr: u16) -> u8 {
+        if self.registers_mapped() {
+            return match (addr - 0xA000) & 0x7F {
+                0x00 => (self.registers[0x00] & 0x06) | (self.busy > 0) as u8,
+                _ => 0x00,
+            };
+        }
+        if self.busy > 0 {
+            return 0x00;
+        }
+        self.ram_offset(addr).map_or(0xFF, |i| self.ram[i])
+    }
+
+    fn write_ram(&mut self, addr: u16, v: u8) -> bool {
+        if self.registers_mapped() {
+            let register = ((addr - 0xA000) & 0x7F) as usize;
+            if register == 0x00 {
+                self.registers[0x00] = v & 0x07;
+                self.busy = match (v & 0x01 != 0, self.busy) {
+                    (true, 0) => self.capture_clocks(),
+                    (true, busy) => busy,
+                    (false, _) => 0,
+                };
+            } else if register < REGISTERS {
+                self.registers[register] = v;
+            }
+            return false;
+        }
+        if !self.ram_enabled || self.busy != 0 {
+            return false;
+        }
+        match self.ram_offset(addr) {
+            Some(i) => {
+                self.ram[i] = v;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn rom_bank(&self, addr: u16) -> u16 {
+        let bank = if addr < 0x4000 {
+            0
+        } else {
+            self.rom_bank as usize
+        };
+        wrap_rom_bank(&self.rom, bank)
+    }
+
+    fn rom_mut(&mut self) -> &mut [u8] {
+        &mut self.rom
+    }
+
+    fn save_data(&self) -> Option<Vec<u8>> {
+        Some(self.ram.clone())
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        load_into(&mut self.ram, data);
+    }
+
+    fn tick(&mut self, clocks: u64) {
+        if self.busy == 0 {
+            return;
+        }
+        self.busy = self.busy.saturating_sub(clocks);
+        if self.busy == 0 {
+            self.capture();
+            self.registers[0x00] &= !0x01;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mbc::numbered_rom;
+
+    /// A frame dark on the left half and light on the right.
+    struct Halves;
+
+    impl CameraSource for Halves {
+        fn frame(&mut self) -> Vec<u8> {
+            (0..SENSOR_WIDTH * SENSOR_HEIGHT)
+                .map(|i| if i % SENSOR_WIDTH < 64 { 0x10 } else { 0xF0 })
+                .collect()
+        }
+    }
+
+    /// The color of a pixel in the captured tiles.
+    fn pixel(mapper: &Camera, x: usize, y: usize) -> u8 {
+        let row = IMAGE_ADDR + ((y / 8) * 16 + x / 8) * 16 + (y % 8) * 2;
+        let bit = 7 - x % 8;
+        ((mapper.ram[row] >> bit) & 1) | (((mapper.ram[row + 1] >> bit) & 1) << 1)
+    }
+
+    #[test]
+    fn banking() {
+        let mut mapper = Camera::new(numbered_rom(64), None);
+        mapper.write_rom(0x2000, 0x00);
+        assert_eq!(mapper.read_rom(0x4000), 0);
+        mapper.write_rom(0x2000, 0x3F);
+        assert_eq!(mapper.read_rom(0x4000), 0x3F);
+
+        mapper.write_ram(0xA000, 0x42);
+        assert_eq!(mapper.read_ram(0xA000), 0x00);
+        mapper.write_rom(0x0000, 0x0A);
+        for bank in 0..16 {
+            mapper.write_rom(0x4000, bank);
+            mapper.write_ram(0xBFFF, bank);
+        }
+        for bank in 0..16 {
+            mapper.write_rom(0x4000, bank);
+            assert_eq!(mapper.read_ram(0xBFFF), bank);
+        }
+    }
+
+    #[test]
+    fn capture_is_dithered_into_ram() {
+        let mut mapper = Camera::new(numbered_rom(64), Some(Box::new(Halves)));
+        mapper.write_rom(0x4000, 0x10);
+        mapper.write_ram(0xA002, 0x08);
+        mapper.write_ram(0xA003, 0x00);
+        for i in 0..16 {
+            mapper.write_ram(0xA006 + i * 3, 0x40);
+            mapper.write_ram(0xA007 + i * 3, 0x80);
+            mapper.write_ram(0xA008 + i * 3, 0xC0);
+        }
+        mapper.write_ram(0xA000, 0x01);
+        assert_eq!(mapper.read_ram(0xA000), 0x01);
+        // Registers repeat every 0x80 bytes.
+        assert_eq!(mapper.read_ram(0xA080), 0x01);
+
+        let clocks = mapper.capture_clocks();
+        assert_eq!(clocks, 32446 + 512 + 16 * 0x0800);
+        mapper.tick(clocks - 1);
+        assert_eq!(mapper.read_ram(0xA000), 0x01);
+        mapper.tick(1);
+        assert_eq!(mapper.read_ram(0xA000), 0x00);
+
+        mapper.write_rom(0x4000, 0x00);
+        assert_eq!(pixel(&mapper, 0, 0), 3);
+        assert_eq!(pixel(&mapper, 63, 111), 3);
+        assert_eq!(pixel(&mapper, 64, 0), 0);
+        assert_eq!(pixel(&mapper, 127, 111), 0);
+
+        // Inverted and twice the exposure.
+        mapper.write_rom(0x4000, 0x10);
+        mapper.write_ram(0xA004, 0x08);
+        mapper.write_ram(0xA002, 0x10);
+        mapper.write_ram(0xA000, 0x01);
+        mapper.tick(mapper.capture_clocks());
+        mapper.write_rom(0x4000, 0x00);
+        // 0x20 inverted is 0xDF, 0xF0 saturates to 0xFF then 0x00.
+        assert_eq!(pixel(&mapper, 0, 0), 0);
+        assert_eq!(pixel(&mapper, 64, 0), 3);
+    }
+
+    #[test]
+    fn images_from_disk() {
+        let dir = std::env::temp_dir().join(format!("rust_boy_camera_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        // A 2x1 PGM with a comment, then a 1x1 PPM.
+        fs::write(dir.join("a.pgm"), b"P5\n# comment\n2 1\n255\n\x00\xFF").unwrap();
+        fs::write(dir.join("b.ppm"), b"P6 1 1 15 \x0F\x0F\x0F").unwrap();
+        fs::write(dir.join("notes.txt"), b"not an image").unwrap();
+
+        let mut images = ImageFiles::open(&dir).unwrap();
+        let first = images.frame();
+        assert_eq!(first.len(), SENSOR_WIDTH * SENSOR_HEIGHT);
+        assert_eq!(
+            (first[0], first[63], first[64], first[127]),
+            (0x00, 0x00, 0xFF, 0xFF)
+        );
+        assert!(images.frame().iter().all(|&v| v == 0xFF));
+        assert_eq!(images.frame(), first);
+
+        let still = ImageFiles::open(dir.join("b.ppm")).unwrap();
+        assert_eq!(still.frames.len(), 1);
+        assert!(ImageFiles::open(dir.join("notes.txt")).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn bad_images_are_invalid_data() {
+        let huge = format!("P6 {} 3 255 ", usize::MAX / 2);
+        let widest = format!("P5 {} 1 255 ", usize::MAX);
+        for data in [
+            &b"P5 2 1"[..],
+            b"P3 1 1 255 \x00",
+            b"P5 0 1 255 ",
+            b"P5 1 1 256 \x00",
+            b"P5 x 1 255 \x00",
+            b"P5 2 2 255 \x00\x00\x00",
+            huge.as_bytes(),
+            widest.as_bytes(),
+        ] {
+            let err = decode_pnm(data).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        }
+    }
+}
@@ -0,0 +1,282 @@
+/// A 93LC56 serial EEPROM, 128 16 bit words.
+///
+/// While CS is high, DI is sampled on each rising edge of CLK. A
+/// command is a start bit, a 2 bit opcode and 8 address bits, of which
+/// the top one is ignored. Reads shift the data out on DO after a
+/// dummy 0 bit, writes shift 16 data bits in. Writes and erases only
+/// work after EWEN.
+#[derive(Debug)]
+pub struct Eeprom {
+    words: [u16; 128],
+    write_enabled: bool,
+    cs: bool,
+    clk: bool,
+    di: bool,
+    dout: bool,
+    state: State,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// Waiting for a start bit.
+    Idle,
+    Command {
+        bits: u16,
+        count: u8,
+    },
+    Read {
+        addr: u8,
+        count: u8,
+    },
+    /// addr is None for WRAL.
+    Write {
+        addr: Option<u8>,
+        value: u16,
+        count: u8,
+    },
+}
+
+impl Default for Eeprom {
+    fn default() -> Self {
+        Self {
+            words: [0xFFFF; 128],
+            write_enabled: false,
+            cs: false,
+            clk: false,
+            di: false,
+            dout: true,
+            state: State::Idle,
+        }
+    }
+}
+
+impl Eeprom {
+    /// The pins as the MBC7 register packs them: CS in bit 7, CLK in
+    /// bit 6, DI in bit 1 and DO in bit 0.
+    pub fn read(&self) -> u8 {
+        ((self.cs as u8) << 7) | ((self.clk as u8) << 6) | ((self.di as u8) << 1) | self.dout as u8
+    }
+
+    /// Set the pins, true if a write or erase changed the contents.
+    pub fn write(&mut self, v: u8) -> bool {
+        let cs = v & 0x80 != 0;
+        let clk = v & 0x40 != 0;
+        self.di = v & 0x02 != 0;
+
+        let mut changed = false;
+        if !cs {
+            self.state = State::Idle;
+            self.dout = true;
+        } else if clk && !self.clk {
+            let words = self.words;
+            self.clock_in();
+            changed = self.words != words;
+        }
+        self.cs = cs;
+        self.clk = clk;
+        changed
+    }
+
+    fn clock_in(&mut self) {
+        let di = self.di as u16;
+        self.state = match self.state {
+            State::Idle if self.di => State::Command { bits: 0, count: 0 },
+            State::Idle => State::Idle,
+            State::Command { bits, count } => {
+                let bits = (bits << 1) | di;
+                if count + 1 < 10 {
+                    State::Command {
+                        bits,
+                        count: count + 1,
+                    }
+                } else {
+                    self.command(bits)
+                }
+            }
+            State::Read { addr, count } => {
+                let addr = if count == 16 {
+                    addr.wrapping_add(1) & 0x7F
+                } else {
+                    addr
+                };
+                let count = count % 16;
+                self.dout = self.words[addr as usize] & (0x8000 >> count) != 0;
+                State::Read {
+                    addr,
+                    count: count + 1,
+                }
+            }
+            State::Write { addr, value, count } => {
+                let value = (value << 1) | di;
+                if count + 1 < 16 {
+                    State::Write {
+                        addr,
+                        value,
+                        count: count + 1,
+                    }
+                } else {
+                    if self.write_enabled {
+                        match addr {
+                            Some(addr) => self.words[addr as usize] = value,
+                            None => self.words.fill(value),
+                        }
+                    }
+                    self.dout = true;
+                    State::Idle
+                }
+            }
+        };
+    }
+
+    fn command(&mut self, bits: u16) -> State {
+        let addr = (bits & 0x7F) as u8;
+        match (bits >> 8) & 0b11 {
+            0b10 => {
+                // Dummy bit before the data.
+                self.dout = false;
+                State::Read { addr, count: 0 }
+            }
+            0b01 => State::Write {
+                addr: Some(addr),
+                value: 0,
+                count: 0,
+            },
+            0b11 => {
+                if self.write_enabled {
+                    self.words[addr as usize] = 0xFFFF;
+                }
+                State::Idle
+            }
+            _ => match (bits >> 6) & 0b11 {
+                0b11 => {
+                    self.write_enabled = true;
+                    State::Idle
+                }
+                0b00 => {
+                    self.write_enabled = false;
+                    State::Idle
+                }
+                0b10 => {
+                    if self.write_enabled {
+                        self.words.fill(0xFFFF);
+                    }
+                    State::Idle
+                }
+                _ => State::Write {
+                    addr: None,
+                    value: 0,
+                    count: 0,
+                },
+            },
+        }
+    }
+
+    /// The contents as 256 bytes, each word little endian.
+    pub fn save(&self) -> Vec<u8> {
+        self.words.iter().flat_map(|w| w.to_le_bytes()).collect()
+    }
+
+    pub fn load(&mut self, data: &[u8]) {
+        for (word, bytes) in self.words.iter_mut().zip(data.chunks_exact(2)) {
+            *word = u16::from_le_bytes([bytes[0], bytes[1]]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CS: u8 = 0x80;
+    const CLK: u8 = 0x40;
+    const DI: u8 = 0x02;
+
+    fn clock_bits(eeprom: &mut Eeprom, bits: u32, count: u32) {
+        for i in (0..count).rev() {
+            let di = if bits & (1 << i) != 0 { DI } else { 0 };
+            eeprom.write(CS | di);
+            eeprom.write(CS | CLK | di);
+        }
+    }
+
+    fn command(eeprom: &mut Eeprom, op: u32, addr: u32) {
+        eeprom.write(0x00);
+        clock_bits(eeprom, (0b1 << 10) | (op << 8) | addr, 11);
+    }
+
+    fn read_word(eeprom: &mut Eeprom, addr: u32) -> u16 {
+        command(eeprom, 0b10, addr);
+        assert_eq!(eeprom.read() & 0x01, 0);
+        let mut word = 0;
+        for _ in 0..16 {
+            eeprom.write(CS);
+            eeprom.write(CS | CLK);
+            word = (word << 1) | (eeprom.read() & 0x01) as u16;
+        }
+        word
+    }
+
+    fn write_word(eeprom: &mut Eeprom, addr: u32, value: u16) {
+        command(eeprom, 0b01, addr);
+        clock_bits(eeprom, value as u32, 16);
+    }
+
+    #[test]
+    fn writes_need_ewen() {
+        let mut eeprom = Eeprom::default();
+        write_word(&mut eeprom, 0x05, 0x1234);
+        assert_eq!(read_word(&mut eeprom, 0x05), 0xFFFF);
+
+        command(&mut eeprom, 0b00, 0b1100_0000);
+        write_word(&mut eeprom, 0x05, 0x1234);
+        assert_eq!(read_word(&mut eeprom, 0x05), 0x1234);
+        // The top address bit is ignored.
+        assert_eq!(read_word(&mut eeprom, 0x85), 0x1234);
+
+        command(&mut eeprom, 0b00, 0b0000_0000);
+        write_word(&mut eeprom, 0x05, 0x5678);
+        assert_eq!(read_word(&mut eeprom, 0x05), 0x1234);
+    }
+
+    #[test]
+    fn erase_and_write_all() {
+        let mut eeprom = Eeprom::default();
+        command(&mut eeprom, 0b00, 0b1100_0000);
+
+        command(&mut eeprom, 0b00, 0b0100_0000);
+        clock_bits(&mut eeprom, 0xABCD, 16);
+        assert_eq!(read_word(&mut eeprom, 0x00), 0xABCD);
+        assert_eq!(read_word(&mut eeprom, 0x7F), 0xABCD);
+
+        command(&mut eeprom, 0b11, 0x10);
+        assert_eq!(read_word(&mut eeprom, 0x10), 0xFFFF);
+        assert_eq!(read_word(&mut eeprom, 0x11), 0xABCD);
+
+        command(&mut eeprom, 0b00, 0b1000_0000);
+        assert_eq!(read_word(&mut eeprom, 0x11), 0xFFFF);
+    }
+
+    #[test]
+    fn pins_read_back() {
+        let mut eeprom = Eeprom::default();
+        eeprom.write(CS | CLK | DI);
+        assert_eq!(eeprom.read(), CS | CLK | DI | 0x01);
+        eeprom.write(0x00);
+        assert_eq!(eeprom.read(), 0x01);
+    }
+
+    #[test]
+    fn save_round_trip() {
+        let mut eeprom = Eeprom::default();
+        command(&mut eeprom, 0b00, 0b1100_0000);
+        write_word(&mut eeprom, 0x01, 0x1234);
+
+        let save = eeprom.save();
+        assert_eq!(save.len(), 256);
+        assert_eq!(&save[2..4], &[0x34, 0x12]);
+
+        let mut loaded = Eeprom::default();
+        loaded.load(&save);
+        assert_eq!(read_word(&mut loaded, 0x01), 0x1234);
+    }
+}
@@ -0,0 +1,140 @@
+use super::ir::{self, IrLink};
+use super::{banked_rom, load_into, ram_offset, wrap_rom_bank, Mapper};
+
+/// Hudson's HuC1, up to 1MB of ROM, 32KB of battery backed RAM and
+/// an infrared port.
+///
+/// There is no RAM enable, writing 0x0E to 0x0000-0x1FFF maps the IR
+/// register over RAM and anything else maps RAM back.
+pub struct Huc1 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    ir: Option<Box<dyn IrLink>>,
+    ir_mode: bool,
+    rom_bank: u8,
+    ram_bank: u8,
+}
+
+impl Huc1 {
+    pub fn new(rom: Vec<u8>, ram_size: usize, ir: Option<Box<dyn IrLink>>) -> Self {
+        Self {
+            rom,
+            ram: vec![0x00; ram_size],
+            ir,
+            ir_mode: false,
+            rom_bank: 0x01,
+            ram_bank: 0x00,
+        }
+    }
+
+    fn ram_offset(&self, addr: u16) -> Option<usize> {
+        ram_offset(&self.ram, self.ram_bank as usize, addr)
+    }
+}
+
+impl Mapper for Huc1 {
+    fn read_rom(&self, addr: u16) -> u8 {
+        banked_rom(&self.rom, self.rom_bank(addr), addr)
+    }
+
+    fn write_rom(&mut self, addr: u16, v: u8) {
+        match addr {
+            0x0000..=0x1FFF => self.ir_mode = v == 0x0E,
+            0x2000..=0x3FFF => self.rom_bank = (v & 0x3F).max(1),
+            0x4000..=0x5FFF => self.ram_bank = v & 0x03,
+            _ => {}
+        }
+    }
+
+    fn read_ram(&self, addr: u16) -> u8 {
+        if self.ir_mode {
+            return ir::read(&self.ir);
+        }
+        self.ram_offset(addr).map_or(0xFF, |i| self.ram[i])
+    }
+
+    fn write_ram(&mut self, addr: u16, v: u8) -> bool {
+        if self.ir_mode {
+            ir::write(&mut self.ir, v);
+            return false;
+        }
+        match self.ram_offset(addr) {
+            Some(i) => {
+                self.ram[i] = v;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn rom_bank(&self, addr: u16) -> u16 {
+        let bank = if addr < 0x4000 {
+            0
+        } else {
+            self.rom_bank as usize
+        };
+        wrap_rom_bank(&self.rom, bank)
+    }
+
+    fn rom_mut(&mut self) -> &mut [u8] {
+        &mut self.rom
+    }
+
+    /// Every HuC1 cart has a battery.
+    fn save_data(&self) -> Option<Vec<u8>> {
+        Some(self.ram.clone())
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        load_into(&mut self.ram, data);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mbc::ir::Loopback;
+    use crate::mbc::{numbered_rom, RAM_BANK_SIZE};
+
+    #[test]
+    fn banking() {
+        let mut mapper = Huc1::new(numbered_rom(64), 4 * RAM_BANK_SIZE, None);
+        assert_eq!(mapper.read_rom(0x4000), 1);
+        mapper.write_rom(0x2000, 0x00);
+        assert_eq!(mapper.read_rom(0x4000), 1);
+        mapper.write_rom(0x2000, 0x3F);
+        assert_eq!(mapper.read_rom(0x7FFF), 0x00);
+        assert_eq!(mapper.read_rom(0x4000), 0x3F);
+
+        // RAM needs no enable.
+        for bank in 0..4 {
+            mapper.write_rom(0x4000, bank);
+            mapper.write_ram(0xA000, 0x10 + bank);
+        }
+        for bank in 0..4 {
+            mapper.write_rom(0x4000, bank);
+            assert_eq!(mapper.read_ram(0xA000), 0x10 + bank);
+        }
+        assert_eq!(mapper.save_data().unwrap().len(), 4 * RAM_BANK_SIZE);
+    }
+
+    #[test]
+    fn ir_register_replaces_ram() {
+        let mut mapper = Huc1::new(numbered_rom(4), RAM_BANK_SIZE, None);
+        mapper.write_ram(0xA000, 0x42);
+        mapper.write_rom(0x0000, 0x0E);
+        assert_eq!(mapper.read_ram(0xA000), 0xC0);
+        mapper.write_ram(0xA000, 0x01);
+        // No link, the light goes nowhere.
+        assert_eq!(mapper.read_ram(0xA000), 0xC0);
+        mapper.write_rom(0x0000, 0x00);
+        assert_eq!(mapper.read_ram(0xA000), 0x42);
+
+        let mut mapper = Huc1::new(numbered_rom(4), 0, Some(Box::new(Loopback::default())));
+        mapper.write_rom(0x0000, 0x0E);
+        mapper.write_ram(0xA000, 0x01);
+        assert_eq!(mapper.read_ram(0xA000), 0xC1);
+        mapper.write_ram(0xA000, 0x00);
+        assert_eq!(mapper.read_ram(0xA000), 0xC0);
+    }
+}
@@ -0,0 +1,383 @@
+use super::ir::{self, IrLink};
+use super::rtc::{self, RtcClock, SecondCounter};
+use super::{banked_rom, load_into, ram_offset, wrap_rom_bank, Mapper};
+
+/// Bytes the clock adds to a battery save.
+const CLOCK_SAVE_SIZE: usize = 16;
+
+const MINUTES_PER_DAY: u64 = 1440;
+/// The day counter is 12 bits, the time wraps after this many seconds.
+const PERIOD: u64 = 0x1000 * 86400;
+
+/// Hudson's HuC3, up to 2MB of ROM, 32KB of battery backed RAM, a
+/// real time clock, a tone generator and an infrared port.
+///
+/// 0x0000-0x1FFF selects what 0xA000-0xBFFF is: 0x0 RAM read only,
+/// 0xA RAM, 0xB the RTC command, 0xC the RTC response, 0xD the RTC
+/// semaphore and 0xE the IR register.
+///
+/// The RTC is driven through 256 nibbles of memory. A command is
+/// written with the opcode in the high nibble and runs when 0 is
+/// written to bit 0 of the semaphore:
+/// 0x1 reads the nibble at the address and 0x3 writes the argument
+/// there, both then increment the address, 0x4 and 0x5 set the low
+/// and high nibble of the address, 0x6 is extended: 0x60 copies the
+/// time to 0x00-0x05, minutes then days as 12 bit values low nibble
+/// first, 0x61 sets the time from there, 0x62 reports 1 and 0x6E
+/// plays the tone in 0x26 if 0x27 is 1, or stops it.
+pub struct Huc3 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    ir: Option<Box<dyn IrLink>>,
+    mode: u8,
+    rom_bank: u8,
+    ram_bank: u8,
+    /// The time as seconds since day 0, as of the last counter sync.
+    seconds: u64,
+    counter: SecondCounter,
+    memory: [u8; 0x100],
+    address: u8,
+    command: u8,
+    response: u8,
+    tone: Option<u8>,
+}
+
+impl Huc3 {
+    pub fn new(
+        rom: Vec<u8>,
+        ram_size: usize,
+        clock: RtcClock,
+        ir: Option<Box<dyn IrLink>>,
+    ) -> Self {
+        Self {
+            rom,
+            ram: vec![0x00; ram_size],
+            ir,
+            mode: 0x00,
+            rom_bank: 0x01,
+            ram_bank: 0x00,
+            seconds: 0,
+            counter: SecondCounter::new(clock),
+            memory: [0x00; 0x100],
+            address: 0x00,
+            command: 0x00,
+            response: 0x00,
+            tone: None,
+        }
+    }
+
+    fn ram_offset(&self, addr: u16) -> Option<usize> {
+        ram_offset(&self.ram, self.ram_bank as usize, addr)
+    }
+
+    fn now(&self) -> u64 {
+        (self.seconds + self.counter.elapsed()) % PERIOD
+    }
+
+    fn set_time(&mut self, seconds: u64) {
+        self.seconds = seconds % PERIOD;
+        self.counter.restart();
+    }
+
+    fn run(&mut self, command: u8) {
+        let arg = command & 0x0F;
+        let result = match command >> 4 {
+            0x1 => {
+                let v = self.memory[self.address as usize];
+                self.address = self.address.wrapping_add(1);
+                v
+            }
+            0x3 => {
+                self.memory[self.address as usize] = arg;
+                self.address = self.address.wrapping_add(1);
+                arg
+            }
+            0x4 => {
+                self.address = (self.address & 0xF0) | arg;
+                arg
+            }
+            0x5 => {
+                self.address = (self.address & 0x0F) | (arg << 4);
+                arg
+            }
+            0x6 => self.extended(arg),
+            _ => arg,
+        };
+        self.response = (command & 0x70) | (result & 0x0F);
+    }
+
+    fn extended(&mut self, arg: u8) -> u8 {
+        match arg {
+            0x0 => {
+                let now = self.now();
+                self.store(0x00, now / 60 % MINUTES_PER_DAY);
+                self.store(0x03, now / 86400);
+                arg
+            }
+            0x1 => {
+                let minutes = self.load(0x00) % MINUTES_PER_DAY;
+                let days = self.load(0x03);
+                self.set_time(days * 86400 + minutes * 60);
+                arg
+            }
+            0x2 => 0x1,
+            0xE => {
+                self.tone = (self.memory[0x27] == 0x1).then_some(self.memory[0x26]);
+                arg
+            }
+            _ => arg,
+        }
+    }
+
+    /// Write a 12 bit value to three nibbles of memory.
+    fn store(&mut self, at: usize, value: u64) {
+        for i in 0..3 {
+            self.memory[at + i] = (value >> (i * 4)) as u8 & 0x0F;
+        }
+    }
+
+    fn load(&self, at: usize) -> u64 {
+        (0..3).fold(0, |value, i| {
+            value | (self.memory[at + i] as u64 & 0x0F) << (i * 4)
+        })
+    }
+}
+
+impl Mapper for Huc3 {
+    fn read_rom(&self, addr: u16) -> u8 {
+        banked_rom(&self.rom, self.rom_bank(addr), addr)
+    }
+
+    fn write_rom(&mut self, addr: u16, v: u8) {
+        match addr {
+            0x0000..=0x1FFF => self.mode = v & 0x0F,
+            0x2000..=0x3FFF => self.rom_bank = v & 0x7F,
+            0x4000..=0x5FFF => self.ram_bank = v & 0x03,
+            _ => {}
+        }
+    }
+
+    fn read_ram(&self, addr: u16) -> u8 {
+        match self.mode {
+            0x0 | 0xA => self.ram_offset(addr).map_or(0xFF, |i| self.ram[i]),
+            0xC => 0x80 | self.response,
+            // The RTC is always ready.
+            0xD => 0xFF,
+            0xE => ir::read(&self.ir),
+            _ => 0xFF,
+        }
+    }
+
+    fn write_ram(&mut self, addr: u16, v: u8) -> bool {
+        match self.mode {
+            0xA => match self.ram_offset(addr) {
+                Some(i) => {
+                    self.ram[i] = v;
+                    true
+                }
+                None => false,
+            },
+            0xB => {
+                self.command = v & 0x7F;
+                false
+            }
+            // The clock's memory is not saved, only the time 0x61 sets.
+            0xD if v & 0x01 == 0 => {
+                self.run(self.command);
+                self.command == 0x61
+            }
+            0xE => {
+                ir::write(&mut self.ir, v);
+                false
+            }
+            _ => false,
+        }
+    }
+
+    fn rom_bank(&self, addr: u16) -> u16 {
+        let bank = if addr < 0x4000 {
+            0
+        } else {
+            self.rom_bank as usize
+        };
+        wrap_rom_bank(&self.rom, bank)
+    }
+
+    fn rom_mut(&mut self) -> &mut [u8] {
+        &mut self.rom
+    }
+
+    /// The RAM, then the time and the host time as 64 bit words.
+    fn save_data(&self) -> Option<Vec<u8>> {
+        let mut data = self.ram.clone();
+        data.extend_from_slice(&self.now().to_le_bytes());
+        data.extend_from_slice(&rtc::host_now().to_le_bytes());
+        Some(data)
+    }
+
+    /// Restore the RAM and time, plus the time the save sat on disk.
+    fn load_save_data(&mut self, data: &[u8]) {
+        load_into(&mut self.ram, data);
+        if data.len() < self.ram.len() + CLOCK_SAVE_SIZE {
+            return;
+        }
+        let clock = &data[self.ram.len()..];
+        self.set_time(rtc::read_u64(clock) + rtc::seconds_since(&clock[8..]));
+    }
+
+    fn tick(&mut self, clocks: u64) {
+        self.seconds = (self.seconds + self.counter.tick(clocks)) % PERIOD;
+    }
+
+    fn tone(&self) -> Option<u8> {
+        self.tone
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mbc::ir::Loopback;
+    use crate::mbc::rtc::CLOCKS_PER_SECOND;
+    use crate::mbc::{numbered_rom, RAM_BANK_SIZE};
+
+    fn huc3() -> Huc3 {
+        Huc3::new(
+            numbered_rom(128),
+            4 * RAM_BANK_SIZE,
+            RtcClock::Emulated,
+            None,
+        )
+    }
+
+    /// Run command and return the response.
+    fn command(mapper: &mut Huc3, command: u8) -> u8 {
+        mapper.write_rom(0x0000, 0x0B);
+        mapper.write_ram(0xA000, command);
+        mapper.write_rom(0x0000, 0x0D);
+        mapper.write_ram(0xA000, 0xFE);
+        mapper.write_rom(0x0000, 0x0C);
+        mapper.read_ram(0xA000)
+    }
+
+    fn read_time(mapper: &mut Huc3) -> (u16, u16) {
+        command(mapper, 0x60);
+        command(mapper, 0x40);
+        command(mapper, 0x50);
+        let mut nibbles = [0u16; 6];
+        for nibble in &mut nibbles {
+            *nibble = (command(mapper, 0x10) & 0x0F) as u16;
+        }
+        let value = |n: &[u16]| n[0] | (n[1] << 4) | (n[2] << 8);
+        (value(&nibbles[..3]), value(&nibbles[3..]))
+    }
+
+    #[test]
+    fn ram_modes() {
+        let mut mapper = huc3();
+        mapper.write_rom(0x0000, 0x0A);
+        for bank in 0..4 {
+            mapper.write_rom(0x4000, bank);
+            mapper.write_ram(0xA000, 0x10 + bank);
+        }
+        mapper.write_rom(0x0000, 0x00);
+        mapper.write_ram(0xA000, 0x00);
+        for bank in 0..4 {
+            mapper.write_rom(0x4000, bank);
+            assert_eq!(mapper.read_ram(0xA000), 0x10 + bank);
+        }
+
+        mapper.write_rom(0x2000, 0x45);
+        assert_eq!(mapper.read_rom(0x4000), 0x45);
+    }
+
+    #[test]
+    fn clock_commands() {
+        let mut mapper = huc3();
+        assert_eq!(command(&mut mapper, 0x62), 0xE1);
+        assert_eq!(read_time(&mut mapper), (0, 0));
+
+        mapper.tick(CLOCKS_PER_SECOND * 61);
+        assert_eq!(read_time(&mut mapper), (1, 0));
+        mapper.tick(CLOCKS_PER_SECOND * 86400);
+        assert_eq!(read_time(&mut mapper), (1, 1));
+
+        // Set 0x123 minutes on day 0x456.
+        command(&mut mapper, 0x40);
+        command(&mut mapper, 0x50);
+        for nibble in [0x3, 0x2, 0x1, 0x6, 0x5, 0x4] {
+            assert_eq!(command(&mut mapper, 0x30 | nibble), 0xB0 | nibble);
+        }
+        command(&mut mapper, 0x61);
+        assert_eq!(read_time(&mut mapper), (0x123, 0x456));
+    }
+
+    #[test]
+    fn only_setting_the_time_is_saved() {
+        let mut mapper = huc3();
+        for (mode, v) in [
+            (0x0B, 0x30),
+            (0x0D, 0xFE),
+            (0x0B, 0x60),
+            (0x0D, 0xFE),
+            (0x0D, 0xFF),
+        ] {
+            mapper.write_rom(0x0000, mode);
+            assert!(!mapper.write_ram(0xA000, v));
+        }
+        mapper.write_rom(0x0000, 0x0B);
+        mapper.write_ram(0xA000, 0x61);
+        mapper.write_rom(0x0000, 0x0D);
+        assert!(mapper.write_ram(0xA000, 0xFE));
+    }
+
+    #[test]
+    fn tone_generator() {
+        let mut mapper = huc3();
+        command(&mut mapper, 0x46);
+        command(&mut mapper, 0x52);
+        command(&mut mapper, 0x35);
+        command(&mut mapper, 0x31);
+        command(&mut mapper, 0x6E);
+        assert_eq!(mapper.tone(), Some(0x5));
+
+        command(&mut mapper, 0x47);
+        command(&mut mapper, 0x30);
+        command(&mut mapper, 0x6E);
+        assert_eq!(mapper.tone(), None);
+    }
+
+    #[test]
+    fn ir_mode() {
+        let mut mapper = Huc3::new(
+            numbered_rom(4),
+            0,
+            RtcClock::Emulated,
+            Some(Box::new(Loopback::default())),
+        );
+        mapper.write_rom(0x0000, 0x0E);
+        assert_eq!(mapper.read_ram(0xA000), 0xC0);
+        mapper.write_ram(0xA000, 0x01);
+        assert_eq!(mapper.read_ram(0xA000), 0xC1);
+    }
+
+    #[test]
+    fn save_keeps_ram_and_time() {
+        let mut mapper = huc3();
+        mapper.write_rom(0x0000, 0x0A);
+        mapper.write_ram(0xA000, 0x42);
+        mapper.tick(CLOCKS_PER_SECOND * 120);
+        let mut save = mapper.save_data().unwrap();
+        assert_eq!(save.len(), 4 * RAM_BANK_SIZE + CLOCK_SAVE_SIZE);
+
+        // Saved an hour ago.
+        let at = 4 * RAM_BANK_SIZE + 8;
+        let saved = rtc::host_now() - 3600;
+        save[at..].copy_from_slice(&saved.to_le_bytes());
+        let mut loaded = huc3();
+        loaded.load_save_data(&save);
+        loaded.write_rom(0x0000, 0x0A);
+        assert_eq!(loaded.read_ram(0xA000), 0x42);
+        assert_eq!(read_time(&mut loaded), (62, 0));
+    }
+}
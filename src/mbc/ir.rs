@@ -0,0 +1,55 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// The other end of the infrared port on HuC1 and HuC3 carts, another
+/// Game Boy, a toy or a remote on the host. Without one the LED
+/// lights nothing and the receiver sees no light.
+pub trait IrLink {
+    /// The cartridge turned its LED on or off.
+    fn transmit(&mut self, on: bool);
+
+    /// True while light reaches the cartridge's receiver.
+    fn receive(&self) -> bool;
+}
+
+/// A link the frontend keeps a handle to.
+impl<T: IrLink + ?Sized> IrLink for Rc<RefCell<T>> {
+    fn transmit(&mut self, on: bool) {
+        self.borrow_mut().transmit(on)
+    }
+
+    fn receive(&self) -> bool {
+        self.borrow().receive()
+    }
+}
+
+/// The IR register as HuC1 and HuC3 map it over RAM, bit 0 reads
+/// the receiver and writes the LED.
+pub(super) fn read(link: &Option<Box<dyn IrLink>>) -> u8 {
+    let light = link.as_ref().is_some_and(|l| l.receive());
+    0xC0 | light as u8
+}
+
+pub(super) fn write(link: &mut Option<Box<dyn IrLink>>, v: u8) {
+    if let Some(link) = link {
+        link.transmit(v & 0x01 != 0);
+    }
+}
+
+/// Loops the LED back to the receiver, for tests.
+#[cfg(test)]
+#[derive(Default)]
+pub(super) struct Loopback {
+    pub led: bool,
+}
+
+#[cfg(test)]
+impl IrLink for Loopback {
+    fn transmit(&mut self, on: bool) {
+        self.led = on;
+    }
+
+    fn receive(&self) -> bool {
+        self.led
+    }
+}
@@ -0,0 +1,250 @@
+use super::{banked_rom, load_into, ram_offset, wrap_rom_bank, Mapper, ROM_BANK_SIZE};
+use crate::header::NINTENDO_LOGO;
+
+/// MBC1, up to 2MB of ROM and 32KB of RAM.
+///
+/// BANK1 is the 5 bit ROM bank register, BANK2 the 2 bit register
+/// that selects the upper ROM bank bits or the RAM bank. In mode 0
+/// BANK2 only applies to 0x4000-0x7FFF, in mode 1 it also applies to
+/// 0x0000-0x3FFF and cartridge RAM.
+///
+/// MBC1M multicarts wire BANK2 one bit lower, so each game sees
+/// sixteen 16KB banks and BANK1 bit 4 is not connected.
+pub struct Mbc1 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    battery: bool,
+    ram_enabled: bool,
+    bank1: u8,
+    bank2: u8,
+    mode: bool,
+    multicart: bool,
+}
+
+impl Mbc1 {
+    pub fn new(rom: Vec<u8>, ram_size: usize, battery: bool) -> Self {
+        let multicart = is_multicart(&rom);
+        Self {
+            rom,
+            ram: vec![0x00; ram_size],
+            battery,
+            ram_enabled: false,
+            bank1: 0x01,
+            bank2: 0x00,
+            mode: false,
+            multicart,
+        }
+    }
+
+    pub fn is_multicart(&self) -> bool {
+        self.multicart
+    }
+
+    fn bank2_shift(&self) -> u32 {
+        if self.multicart {
+            4
+        } else {
+            5
+        }
+    }
+
+    fn bank1_mask(&self) -> u8 {
+        if self.multicart {
+            0x0F
+        } else {
+            0x1F
+        }
+    }
+
+    fn ram_offset(&self, addr: u16) -> Option<usize> {
+        if !self.ram_enabled {
+            return None;
+        }
+        let bank = if self.mode { self.bank2 as usize } else { 0 };
+        ram_offset(&self.ram, bank, addr)
+    }
+}
+
+/// MBC1M carts are 1MB and have a second Nintendo logo at the start
+/// of the second game, bank 0x10.
+fn is_multicart(rom: &[u8]) -> bool {
+    let logo = 0x10 * ROM_BANK_SIZE + 0x0104;
+    rom.len() == 0x10_0000 && rom[logo..logo + NINTENDO_LOGO.len()] == NINTENDO_LOGO
+}
+
+impl Mapper for Mbc1 {
+    fn read_rom(&self, addr: u16) -> u8 {
+        banked_rom(&self.rom, self.rom_bank(addr), addr)
+    }
+
+    fn write_rom(&mut self, addr: u16, v: u8) {
+        match addr {
+            0x0000..=0x1FFF => self.ram_enabled = v & 0x0F == 0x0A,
+            // Zero is seen as one, checked on all 5 bits even when
+            // fewer are connected.
+            0x2000..=0x3FFF => self.bank1 = (v & 0x1F).max(1),
+            0x4000..=0x5FFF => self.bank2 = v & 0x03,
+            _ => self.mode = v & 0x01 != 0,
+        }
+    }
+
+    fn read_ram(&self, addr: u16) -> u8 {
+        match self.ram_offset(addr) {
+            Some(offset) => self.ram[offset],
+            None => 0xFF,
+        }
+    }
+
+    fn write_ram(&mut self, addr: u16, v: u8) -> bool {
+        match self.ram_offset(addr) {
+            Some(offset) => {
+                self.ram[offset] = v;
+                self.battery
+            }
+            None => false,
+        }
+    }
+
+    fn rom_bank(&self, addr: u16) -> u16 {
+        let upper = self.bank2 << self.bank2_shift();
+        let bank = if addr < 0x4000 {
+            if self.mode {
+                upper
+            } else {
+                0
+            }
+        } else {
+            upper | (self.bank1 & self.bank1_mask())
+        };
+        wrap_rom_bank(&self.rom, bank as usize)
+    }
+
+    fn rom_mut(&mut self) -> &mut [u8] {
+        &mut self.rom
+    }
+
+    fn save_data(&self) -> Option<Vec<u8>> {
+        self.battery.then(|| self.ram.clone())
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        load_into(&mut self.ram, data);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mbc::{numbered_rom, RAM_BANK_SIZE};
+
+    fn bank_at(mapper: &Mbc1, addr: u16) -> u8 {
+        mapper.read_rom(addr & 0xC000)
+    }
+
+    #[test]
+    fn rom_banking() {
+        let mut mapper = Mbc1::new(numbered_rom(128), 0, false);
+        assert_eq!(bank_at(&mapper, 0x0000), 0);
+        assert_eq!(bank_at(&mapper, 0x4000), 1);
+
+        mapper.write_rom(0x2000, 0x05);
+        assert_eq!(bank_at(&mapper, 0x4000), 5);
+        mapper.write_rom(0x4000, 0x02);
+        assert_eq!(bank_at(&mapper, 0x4000), 0x45);
+        assert_eq!(bank_at(&mapper, 0x0000), 0);
+
+        // Mode 1 maps BANK2 into 0x0000-0x3FFF too.
+        mapper.write_rom(0x6000, 0x01);
+        assert_eq!(bank_at(&mapper, 0x0000), 0x40);
+        assert_eq!(mapper.rom_bank(0x0000), 0x40);
+        assert_eq!(mapper.rom_bank(0x4000), 0x45);
+    }
+
+    #[test]
+    fn bank_zero_quirk() {
+        let mut mapper = Mbc1::new(numbered_rom(128), 0, false);
+
+        for (bank2, expected) in [(0, 0x01), (1, 0x21), (2, 0x41), (3, 0x61)] {
+            mapper.write_rom(0x4000, bank2);
+            mapper.write_rom(0x2000, 0x00);
+            assert_eq!(bank_at(&mapper, 0x4000), expected);
+            mapper.write_rom(0x2000, 0x20);
+            assert_eq!(bank_at(&mapper, 0x4000), expected);
+        }
+    }
+
+    #[test]
+    fn small_roms_mask_the_bank() {
+        let mut mapper = Mbc1::new(numbered_rom(8), 0, false);
+
+        mapper.write_rom(0x2000, 0x09);
+        assert_eq!(bank_at(&mapper, 0x4000), 1);
+        // A masked bank of zero is not corrected.
+        mapper.write_rom(0x2000, 0x10);
+        assert_eq!(bank_at(&mapper, 0x4000), 0);
+    }
+
+    #[test]
+    fn ram_enable_and_banking() {
+        let mut mapper = Mbc1::new(numbered_rom(4), 4 * RAM_BANK_SIZE, false);
+
+        mapper.write_ram(0xA000, 0x12);
+        assert_eq!(mapper.read_ram(0xA000), 0xFF);
+
+        mapper.write_rom(0x0000, 0x0A);
+        mapper.write_ram(0xA000, 0x12);
+        assert_eq!(mapper.read_ram(0xA000), 0x12);
+
+        // BANK2 only selects the RAM bank in mode 1.
+        mapper.write_rom(0x4000, 0x02);
+        assert_eq!(mapper.read_ram(0xA000), 0x12);
+        mapper.write_rom(0x6000, 0x01);
+        assert_eq!(mapper.read_ram(0xA000), 0x00);
+        mapper.write_ram(0xBFFF, 0x34);
+        mapper.write_rom(0x4000, 0x00);
+        assert_eq!(mapper.read_ram(0xA000), 0x12);
+        mapper.write_rom(0x4000, 0x02);
+        assert_eq!(mapper.read_ram(0xBFFF), 0x34);
+
+        mapper.write_rom(0x0000, 0x00);
+        assert_eq!(mapper.read_ram(0xBFFF), 0xFF);
+    }
+
+    #[test]
+    fn no_ram() {
+        let mut mapper = Mbc1::new(numbered_rom(4), 0, false);
+        mapper.write_rom(0x0000, 0x0A);
+        mapper.write_ram(0xA000, 0x12);
+        assert_eq!(mapper.read_ram(0xA000), 0xFF);
+    }
+
+    #[test]
+    fn multicart() {
+        let mut rom = numbered_rom(64);
+        for game in [0x00, 0x10, 0x20, 0x30] {
+            let logo = game * ROM_BANK_SIZE + 0x0104;
+            rom[logo..logo + 0x30].copy_from_slice(&NINTENDO_LOGO);
+        }
+        let mut mapper = Mbc1::new(rom, 0, false);
+        assert!(mapper.is_multicart());
+
+        mapper.write_rom(0x2000, 0x13);
+        assert_eq!(bank_at(&mapper, 0x4000), 0x03);
+        mapper.write_rom(0x4000, 0x01);
+        assert_eq!(bank_at(&mapper, 0x4000), 0x13);
+        assert_eq!(bank_at(&mapper, 0x0000), 0x00);
+
+        mapper.write_rom(0x6000, 0x01);
+        mapper.write_rom(0x4000, 0x02);
+        assert_eq!(bank_at(&mapper, 0x0000), 0x20);
+        // BANK1 of 0x10 reads as bank 0 of the game.
+        mapper.write_rom(0x2000, 0x10);
+        assert_eq!(bank_at(&mapper, 0x4000), 0x20);
+    }
+
+    #[test]
+    fn plain_1mb_rom_is_not_multicart() {
+        let mapper = Mbc1::new(numbered_rom(64), 0, false);
+        assert!(!mapper.is_multicart());
+    }
+}
@@ -0,0 +1,142 @@
+use super::{banked_rom, load_into, wrap_rom_bank, Mapper};
+
+/// MBC2, up to 256KB of ROM and 512 half bytes of built in RAM.
+///
+/// Bit 8 of the address picks the register written in
+/// 0x0000-0x3FFF, clear for RAM enable and set for the ROM bank. The
+/// RAM repeats through 0xA000-0xBFFF and its upper nibbles read as 1.
+pub struct Mbc2 {
+    rom: Vec<u8>,
+    ram: [u8; 0x200],
+    ram_enabled: bool,
+    bank: u8,
+    battery: bool,
+}
+
+impl Mbc2 {
+    pub fn new(rom: Vec<u8>, battery: bool) -> Self {
+        Self {
+            rom,
+            ram: [0x00; 0x200],
+            ram_enabled: false,
+            bank: 0x01,
+            battery,
+        }
+    }
+}
+
+impl Mapper for Mbc2 {
+    fn read_rom(&self, addr: u16) -> u8 {
+        banked_rom(&self.rom, self.rom_bank(addr), addr)
+    }
+
+    fn write_rom(&mut self, addr: u16, v: u8) {
+        match addr {
+            0x0000..=0x3FFF if addr & 0x0100 == 0 => self.ram_enabled = v & 0x0F == 0x0A,
+            0x0000..=0x3FFF => self.bank = (v & 0x0F).max(1),
+            _ => {}
+        }
+    }
+
+    fn read_ram(&self, addr: u16) -> u8 {
+        if self.ram_enabled {
+            0xF0 | self.ram[addr as usize & 0x01FF]
+        } else {
+            0xFF
+        }
+    }
+
+    fn write_ram(&mut self, addr: u16, v: u8) -> bool {
+        if !self.ram_enabled {
+            return false;
+        }
+        self.ram[addr as usize & 0x01FF] = v & 0x0F;
+        self.battery
+    }
+
+    fn rom_bank(&self, addr: u16) -> u16 {
+        let bank = if addr < 0x4000 { 0 } else { self.bank as usize };
+        wrap_rom_bank(&self.rom, bank)
+    }
+
+    fn rom_mut(&mut self) -> &mut [u8] {
+        &mut self.rom
+    }
+
+    fn save_data(&self) -> Option<Vec<u8>> {
+        self.battery.then(|| self.ram.to_vec())
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        load_into(&mut self.ram, data);
+        for v in &mut self.ram {
+            *v &= 0x0F;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mbc::numbered_rom;
+
+    #[test]
+    fn address_bit_8_selects_the_register() {
+        let mut mapper = Mbc2::new(numbered_rom(16), false);
+        assert_eq!(mapper.read_rom(0x4000), 1);
+
+        // Bit 8 clear, RAM enable, the bank is untouched.
+        mapper.write_rom(0x2000, 0x0A);
+        assert_eq!(mapper.read_rom(0x4000), 1);
+        mapper.write_ram(0xA000, 0x05);
+        assert_eq!(mapper.read_ram(0xA000), 0xF5);
+
+        mapper.write_rom(0x2100, 0x07);
+        assert_eq!(mapper.read_rom(0x4000), 7);
+        assert_eq!(mapper.rom_bank(0x4000), 7);
+        mapper.write_rom(0x0100, 0x00);
+        assert_eq!(mapper.read_rom(0x4000), 1);
+        assert_eq!(mapper.read_rom(0x0000), 0);
+
+        // Writes to 0x4000-0x7FFF do nothing.
+        mapper.write_rom(0x4100, 0x03);
+        assert_eq!(mapper.read_rom(0x4000), 1);
+    }
+
+    #[test]
+    fn ram_is_512_nibbles_repeated() {
+        let mut mapper = Mbc2::new(numbered_rom(2), false);
+        mapper.write_ram(0xA000, 0x0C);
+        assert_eq!(mapper.read_ram(0xA000), 0xFF);
+
+        mapper.write_rom(0x0000, 0x0A);
+        mapper.write_ram(0xA000, 0xAC);
+        assert_eq!(mapper.read_ram(0xA000), 0xFC);
+        assert_eq!(mapper.read_ram(0xA200), 0xFC);
+        assert_eq!(mapper.read_ram(0xBE00), 0xFC);
+
+        mapper.write_ram(0xBFFF, 0x03);
+        assert_eq!(mapper.read_ram(0xA1FF), 0xF3);
+
+        mapper.write_rom(0x0000, 0x00);
+        assert_eq!(mapper.read_ram(0xA000), 0xFF);
+    }
+
+    #[test]
+    fn saved_with_a_battery() {
+        let mut mapper = Mbc2::new(numbered_rom(2), true);
+        mapper.write_rom(0x0000, 0x0A);
+        mapper.write_ram(0xA001, 0x09);
+
+        let save = mapper.save_data().unwrap();
+        assert_eq!(save.len(), 0x200);
+        assert_eq!(save[1], 0x09);
+
+        let mut loaded = Mbc2::new(numbered_rom(2), true);
+        loaded.load_save_data(&save);
+        loaded.write_rom(0x0000, 0x0A);
+        assert_eq!(loaded.read_ram(0xA001), 0xF9);
+
+        assert_eq!(Mbc2::new(numbered_rom(2), false).save_data(), None);
+    }
+}
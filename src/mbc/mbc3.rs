@@ -0,0 +1,240 @@
+use super::rtc::{self, Rtc, RtcClock};
+use super::{banked_rom, load_into, ram_offset, wrap_rom_bank, Mapper};
+
+/// MBC3, up to 2MB of ROM, 32KB of RAM and an optional real time
+/// clock.
+///
+/// 0x4000-0x5FFF selects a RAM bank with 0x00-0x03 or an RTC register
+/// with 0x08-0x0C, which then appears at 0xA000-0xBFFF. Writing 0x00
+/// then 0x01 to 0x6000-0x7FFF latches the clock.
+pub struct Mbc3 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    rtc: Option<Rtc>,
+    battery: bool,
+    ram_enabled: bool,
+    rom_bank: u8,
+    /// RAM bank, or RTC register when 0x08-0x0C.
+    ram_bank: u8,
+    /// Last value written to the latch register.
+    latch: u8,
+}
+
+impl Mbc3 {
+    pub fn new(rom: Vec<u8>, ram_size: usize, rtc: Option<RtcClock>, battery: bool) -> Self {
+        Self {
+            rom,
+            ram: vec![0x00; ram_size],
+            rtc: rtc.map(Rtc::new),
+            battery,
+            ram_enabled: false,
+            rom_bank: 0x01,
+            ram_bank: 0x00,
+            latch: 0xFF,
+        }
+    }
+
+    fn ram_offset(&self, addr: u16) -> Option<usize> {
+        ram_offset(&self.ram, self.ram_bank as usize, addr)
+    }
+}
+
+impl Mapper for Mbc3 {
+    fn read_rom(&self, addr: u16) -> u8 {
+        banked_rom(&self.rom, self.rom_bank(addr), addr)
+    }
+
+    fn write_rom(&mut self, addr: u16, v: u8) {
+        match addr {
+            0x0000..=0x1FFF => self.ram_enabled = v & 0x0F == 0x0A,
+            0x2000..=0x3FFF => self.rom_bank = (v & 0x7F).max(1),
+            0x4000..=0x5FFF => self.ram_bank = v & 0x0F,
+            _ => {
+                if self.latch == 0x00 && v == 0x01 {
+                    if let Some(rtc) = &mut self.rtc {
+                        rtc.latch();
+                    }
+                }
+                self.latch = v;
+            }
+        }
+    }
+
+    fn read_ram(&self, addr: u16) -> u8 {
+        if !self.ram_enabled {
+            return 0xFF;
+        }
+        match (self.ram_bank, &self.rtc) {
+            (0x00..=0x03, _) => self.ram_offset(addr).map_or(0xFF, |i| self.ram[i]),
+            (0x08..=0x0C, Some(rtc)) => rtc.read(self.ram_bank),
+            _ => 0xFF,
+        }
+    }
+
+    fn write_ram(&mut self, addr: u16, v: u8) -> bool {
+        if !self.ram_enabled {
+            return false;
+        }
+        match self.ram_bank {
+            0x00..=0x03 => match self.ram_offset(addr) {
+                Some(i) => {
+                    self.ram[i] = v;
+                    self.battery
+                }
+                None => false,
+            },
+            0x08..=0x0C => match &mut self.rtc {
+                Some(rtc) => {
+                    rtc.write(self.ram_bank, v);
+                    self.battery
+                }
+                None => false,
+            },
+            _ => false,
+        }
+    }
+
+    fn rom_bank(&self, addr: u16) -> u16 {
+        let bank = if addr < 0x4000 {
+            0
+        } else {
+            self.rom_bank as usize
+        };
+        wrap_rom_bank(&self.rom, bank)
+    }
+
+    fn rom_mut(&mut self) -> &mut [u8] {
+        &mut self.rom
+    }
+
+    /// The RAM, followed by the clock if there is one.
+    fn save_data(&self) -> Option<Vec<u8>> {
+        if !self.battery {
+            return None;
+        }
+        let mut data = self.ram.clone();
+        if let Some(rtc) = &self.rtc {
+            data.extend_from_slice(&rtc.save());
+        }
+        Some(data)
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        load_into(&mut self.ram, data);
+        if let Some(rtc) = &mut self.rtc {
+            if data.len() >= self.ram.len() + rtc::SAVE_SIZE {
+                rtc.load(&data[self.ram.len()..]);
+            }
+        }
+    }
+
+    fn tick(&mut self, clocks: u64) {
+        if let Some(rtc) = &mut self.rtc {
+            rtc.tick(clocks);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mbc::{numbered_rom, RAM_BANK_SIZE};
+
+    #[test]
+    fn rom_banking() {
+        let mut mapper = Mbc3::new(numbered_rom(128), 0, None, false);
+        assert_eq!(mapper.read_rom(0x4000), 1);
+
+        mapper.write_rom(0x2000, 0x00);
+        assert_eq!(mapper.read_rom(0x4000), 1);
+        // All 7 bits, no holes at 0x20, 0x40 and 0x60.
+        for bank in [0x20, 0x41, 0x7F] {
+            mapper.write_rom(0x2000, bank);
+            assert_eq!(mapper.read_rom(0x4000), bank);
+            assert_eq!(mapper.rom_bank(0x4000), bank as u16);
+        }
+        assert_eq!(mapper.read_rom(0x0000), 0);
+    }
+
+    #[test]
+    fn ram_banking() {
+        let mut mapper = Mbc3::new(numbered_rom(4), 4 * RAM_BANK_SIZE, None, false);
+        mapper.write_ram(0xA000, 0x12);
+        assert_eq!(mapper.read_ram(0xA000), 0xFF);
+
+        mapper.write_rom(0x0000, 0x0A);
+        for bank in 0..4 {
+            mapper.write_rom(0x4000, bank);
+            mapper.write_ram(0xA000, 0x10 + bank);
+        }
+        for bank in 0..4 {
+            mapper.write_rom(0x4000, bank);
+            assert_eq!(mapper.read_ram(0xA000), 0x10 + bank);
+        }
+    }
+
+    #[test]
+    fn rtc_registers() {
+        let mut mapper = Mbc3::new(numbered_rom(4), 0, Some(RtcClock::Emulated), true);
+        mapper.write_rom(0x0000, 0x0A);
+        mapper.write_rom(0x4000, 0x09);
+        mapper.write_ram(0xA000, 42);
+        assert_eq!(mapper.read_ram(0xA000), 0);
+
+        // Only a 0 then 1 latches.
+        mapper.write_rom(0x6000, 0x01);
+        assert_eq!(mapper.read_ram(0xA000), 0);
+        mapper.write_rom(0x6000, 0x00);
+        mapper.write_rom(0x6000, 0x01);
+        assert_eq!(mapper.read_ram(0xA000), 42);
+
+        mapper.tick(rtc::CLOCKS_PER_SECOND * 60);
+        mapper.write_rom(0x6000, 0x00);
+        mapper.write_rom(0x6000, 0x01);
+        assert_eq!(mapper.read_ram(0xBFFF), 43);
+        mapper.write_rom(0x4000, 0x08);
+        assert_eq!(mapper.read_ram(0xA000), 0);
+    }
+
+    #[test]
+    fn no_rtc() {
+        let mut mapper = Mbc3::new(numbered_rom(4), 0, None, false);
+        mapper.write_rom(0x0000, 0x0A);
+        mapper.write_rom(0x4000, 0x08);
+        mapper.write_ram(0xA000, 0x12);
+        assert_eq!(mapper.read_ram(0xA000), 0xFF);
+    }
+
+    #[test]
+    fn save_holds_ram_and_clock() {
+        let mut mapper = Mbc3::new(
+            numbered_rom(4),
+            RAM_BANK_SIZE,
+            Some(RtcClock::Emulated),
+            true,
+        );
+        mapper.write_rom(0x0000, 0x0A);
+        mapper.write_ram(0xA000, 0x12);
+        mapper.write_rom(0x4000, 0x0A);
+        mapper.write_ram(0xA000, 7);
+
+        let save = mapper.save_data().unwrap();
+        assert_eq!(save.len(), RAM_BANK_SIZE + rtc::SAVE_SIZE);
+
+        let mut loaded = Mbc3::new(
+            numbered_rom(4),
+            RAM_BANK_SIZE,
+            Some(RtcClock::Emulated),
+            true,
+        );
+        loaded.load_save_data(&save);
+        loaded.write_rom(0x0000, 0x0A);
+        assert_eq!(loaded.read_ram(0xA000), 0x12);
+        loaded.write_rom(0x4000, 0x0A);
+        loaded.write_rom(0x6000, 0x00);
+        loaded.write_rom(0x6000, 0x01);
+        assert_eq!(loaded.read_ram(0xA000), 7);
+
+        assert_eq!(Mbc3::new(numbered_rom(4), 0, None, false).save_data(), None);
+    }
+}
@@ -0,0 +1,173 @@
+use super::{banked_rom, load_into, ram_offset, wrap_rom_bank, Mapper};
+
+/// MBC5, up to 8MB of ROM and 128KB of RAM.
+///
+/// The ROM bank is 9 bits, the low 8 written at 0x2000-0x2FFF and the
+/// ninth at 0x3000-0x3FFF, and bank 0 can be mapped at 0x4000. On
+/// rumble carts bit 3 of the RAM bank register drives the motor
+/// instead of selecting a bank.
+pub struct Mbc5 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    battery: bool,
+    has_rumble: bool,
+    ram_enabled: bool,
+    rom_bank: u16,
+    ram_bank: u8,
+    rumble: bool,
+}
+
+impl Mbc5 {
+    pub fn new(rom: Vec<u8>, ram_size: usize, battery: bool, has_rumble: bool) -> Self {
+        Self {
+            rom,
+            ram: vec![0x00; ram_size],
+            battery,
+            has_rumble,
+            ram_enabled: false,
+            rom_bank: 0x001,
+            ram_bank: 0x00,
+            rumble: false,
+        }
+    }
+
+    fn ram_offset(&self, addr: u16) -> Option<usize> {
+        if !self.ram_enabled {
+            return None;
+        }
+        ram_offset(&self.ram, self.ram_bank as usize, addr)
+    }
+}
+
+impl Mapper for Mbc5 {
+    fn read_rom(&self, addr: u16) -> u8 {
+        banked_rom(&self.rom, self.rom_bank(addr), addr)
+    }
+
+    fn write_rom(&mut self, addr: u16, v: u8) {
+        match addr {
+            0x0000..=0x1FFF => self.ram_enabled = v & 0x0F == 0x0A,
+            0x2000..=0x2FFF => self.rom_bank = (self.rom_bank & 0x100) | v as u16,
+            0x3000..=0x3FFF => self.rom_bank = (self.rom_bank & 0x0FF) | ((v as u16 & 0x01) << 8),
+            0x4000..=0x5FFF if self.has_rumble => {
+                self.rumble = v & 0x08 != 0;
+                self.ram_bank = v & 0x07;
+            }
+            0x4000..=0x5FFF => self.ram_bank = v & 0x0F,
+            _ => {}
+        }
+    }
+
+    fn read_ram(&self, addr: u16) -> u8 {
+        self.ram_offset(addr).map_or(0xFF, |i| self.ram[i])
+    }
+
+    fn write_ram(&mut self, addr: u16, v: u8) -> bool {
+        match self.ram_offset(addr) {
+            Some(i) => {
+                self.ram[i] = v;
+                self.battery
+            }
+            None => false,
+        }
+    }
+
+    fn rom_bank(&self, addr: u16) -> u16 {
+        let bank = if addr < 0x4000 {
+            0
+        } else {
+            self.rom_bank as usize
+        };
+        wrap_rom_bank(&self.rom, bank)
+    }
+
+    fn rom_mut(&mut self) -> &mut [u8] {
+        &mut self.rom
+    }
+
+    fn save_data(&self) -> Option<Vec<u8>> {
+        self.battery.then(|| self.ram.clone())
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        load_into(&mut self.ram, data);
+    }
+
+    fn rumble(&self) -> bool {
+        self.rumble
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mbc::{numbered_rom, RAM_BANK_SIZE, ROM_BANK_SIZE};
+
+    #[test]
+    fn nine_bit_rom_bank() {
+        let mut rom = numbered_rom(512);
+        rom[0x100 * ROM_BANK_SIZE + 1] = 0xAA;
+        let mut mapper = Mbc5::new(rom, 0, false, false);
+        assert_eq!(mapper.read_rom(0x4000), 1);
+
+        mapper.write_rom(0x2000, 0x00);
+        assert_eq!(mapper.read_rom(0x4000), 0);
+        mapper.write_rom(0x3000, 0x01);
+        assert_eq!(mapper.read_rom(0x4001), 0xAA);
+        assert_eq!(mapper.rom_bank(0x4000), 0x100);
+        mapper.write_rom(0x2FFF, 0x23);
+        assert_eq!(mapper.rom_bank(0x4000), 0x123);
+        assert_eq!(mapper.read_rom(0x4000), 0x23);
+        mapper.write_rom(0x3FFF, 0x00);
+        assert_eq!(mapper.rom_bank(0x4000), 0x023);
+        assert_eq!(mapper.read_rom(0x0000), 0);
+    }
+
+    #[test]
+    fn sixteen_ram_banks() {
+        let mut mapper = Mbc5::new(numbered_rom(4), 16 * RAM_BANK_SIZE, false, false);
+        mapper.write_rom(0x0000, 0x0A);
+        for bank in 0..16 {
+            mapper.write_rom(0x4000, bank);
+            mapper.write_ram(0xA000, bank);
+        }
+        for bank in 0..16 {
+            mapper.write_rom(0x4000, bank);
+            assert_eq!(mapper.read_ram(0xA000), bank);
+        }
+        assert!(!mapper.rumble());
+
+        mapper.write_rom(0x0000, 0x00);
+        assert_eq!(mapper.read_ram(0xA000), 0xFF);
+    }
+
+    #[test]
+    fn rumble_uses_bit_3() {
+        let mut mapper = Mbc5::new(numbered_rom(4), 8 * RAM_BANK_SIZE, false, true);
+        mapper.write_rom(0x0000, 0x0A);
+        mapper.write_rom(0x4000, 0x01);
+        mapper.write_ram(0xA000, 0x11);
+
+        mapper.write_rom(0x4000, 0x09);
+        assert!(mapper.rumble());
+        assert_eq!(mapper.read_ram(0xA000), 0x11);
+        mapper.write_rom(0x4000, 0x01);
+        assert!(!mapper.rumble());
+    }
+
+    #[test]
+    fn saved_with_a_battery() {
+        let mut mapper = Mbc5::new(numbered_rom(4), RAM_BANK_SIZE, true, false);
+        mapper.write_rom(0x0000, 0x0A);
+        mapper.write_ram(0xA123, 0x45);
+
+        let save = mapper.save_data().unwrap();
+        let mut loaded = Mbc5::new(numbered_rom(4), RAM_BANK_SIZE, true, false);
+        loaded.load_save_data(&save);
+        loaded.write_rom(0x0000, 0x0A);
+        assert_eq!(loaded.read_ram(0xA123), 0x45);
+
+        let mapper = Mbc5::new(numbered_rom(4), RAM_BANK_SIZE, false, false);
+        assert_eq!(mapper.save_data(), None);
+    }
+}
@@ -0,0 +1,249 @@
+use super::eeprom::Eeprom;
+use super::{banked_rom, wrap_rom_bank, Mapper};
+use std::cell::Cell;
+use std::rc::Rc;
+
+/// Accelerometer reading when level.
+const CENTER: u16 = 0x81D0;
+/// Change in the reading for 1g.
+const ONE_G: f32 = 112.0;
+
+/// Where the MBC7 accelerometer gets its tilt from, a mouse, an
+/// analog stick or keys on the host.
+pub trait TiltSource {
+    /// The tilt on each axis in g, about -1.0 to 1.0.
+    fn tilt(&self) -> (f32, f32);
+}
+
+/// A tilt the frontend sets as input arrives, shared through an Rc.
+impl TiltSource for Cell<(f32, f32)> {
+    fn tilt(&self) -> (f32, f32) {
+        self.get()
+    }
+}
+
+impl<T: TiltSource + ?Sized> TiltSource for Rc<T> {
+    fn tilt(&self) -> (f32, f32) {
+        (**self).tilt()
+    }
+}
+
+/// MBC7, up to 2MB of ROM, a two axis accelerometer and a 93LC56
+/// EEPROM in place of RAM.
+///
+/// Both RAM enables must be set for the registers at 0xA000-0xAFFF,
+/// selected by address bits 4-7. Writing 0x55 then 0xAA latches the
+/// accelerometer.
+pub struct Mbc7 {
+    rom: Vec<u8>,
+    eeprom: Eeprom,
+    tilt: Option<Box<dyn TiltSource>>,
+    ram_enabled: bool,
+    ram_enabled2: bool,
+    bank: u8,
+    /// The latched X and Y readings.
+    x: u16,
+    y: u16,
+    /// The latch was cleared and is waiting for 0xAA.
+    erased: bool,
+}
+
+impl Mbc7 {
+    pub fn new(rom: Vec<u8>, tilt: Option<Box<dyn TiltSource>>) -> Self {
+        Self {
+            rom,
+            eeprom: Eeprom::default(),
+            tilt,
+            ram_enabled: false,
+            ram_enabled2: false,
+            bank: 0x01,
+            x: 0x8000,
+            y: 0x8000,
+            erased: false,
+        }
+    }
+
+    fn latch(&mut self) {
+        let (x, y) = self.tilt.as_ref().map_or((0.0, 0.0), |t| t.tilt());
+        let reading = |g: f32| (CENTER as f32 + g.clamp(-4.0, 4.0) * ONE_G) as u16;
+        self.x = reading(x);
+        self.y = reading(y);
+    }
+}
+
+impl Mapper for Mbc7 {
+    fn read_rom(&self, addr: u16) -> u8 {
+        banked_rom(&self.rom, self.rom_bank(addr), addr)
+    }
+
+    fn write_rom(&mut self, addr: u16, v: u8) {
+        match addr {
+            0x0000..=0x1FFF => self.ram_enabled = v == 0x0A,
+            0x2000..=0x3FFF => self.bank = v & 0x7F,
+            0x4000..=0x5FFF => self.ram_enabled2 = v == 0x40,
+            _ => {}
+        }
+    }
+
+    fn read_ram(&self, addr: u16) -> u8 {
+        if !self.ram_enabled || !self.ram_enabled2 || addr >= 0xB000 {
+            return 0xFF;
+        }
+        match (addr >> 4) & 0x0F {
+            0x2 => self.x as u8,
+            0x3 => (self.x >> 8) as u8,
+            0x4 => self.y as u8,
+            0x5 => (self.y >> 8) as u8,
+            0x6 => 0x00,
+            0x8 => self.eeprom.read(),
+            _ => 0xFF,
+        }
+    }
+
+    fn write_ram(&mut self, addr: u16, v: u8) -> bool {
+        if !self.ram_enabled || !self.ram_enabled2 || addr >= 0xB000 {
+            return false;
+        }
+        match (addr >> 4) & 0x0F {
+            0x0 if v == 0x55 => {
+                self.x = 0x8000;
+                self.y = 0x8000;
+                self.erased = true;
+                false
+            }
+            0x1 if v == 0xAA && self.erased => {
+                self.latch();
+                self.erased = false;
+                false
+            }
+            0x8 => self.eeprom.write(v),
+            _ => false,
+        }
+    }
+
+    fn rom_bank(&self, addr: u16) -> u16 {
+        let bank = if addr < 0x4000 { 0 } else { self.bank as usize };
+        wrap_rom_bank(&self.rom, bank)
+    }
+
+    fn rom_mut(&mut self) -> &mut [u8] {
+        &mut self.rom
+    }
+
+    /// The EEPROM, it keeps its contents without a battery.
+    fn save_data(&self) -> Option<Vec<u8>> {
+        Some(self.eeprom.save())
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        self.eeprom.load(data);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mbc::numbered_rom;
+
+    fn enabled(tilt: Option<Box<dyn TiltSource>>) -> Mbc7 {
+        let mut mapper = Mbc7::new(numbered_rom(8), tilt);
+        mapper.write_rom(0x0000, 0x0A);
+        mapper.write_rom(0x4000, 0x40);
+        mapper
+    }
+
+    fn reading(mapper: &Mbc7) -> (u16, u16) {
+        let byte = |addr| mapper.read_ram(addr) as u16;
+        (
+            byte(0xA020) | (byte(0xA030) << 8),
+            byte(0xA040) | (byte(0xA050) << 8),
+        )
+    }
+
+    #[test]
+    fn registers_need_both_enables() {
+        let mut mapper = Mbc7::new(numbered_rom(8), None);
+        assert_eq!(mapper.read_ram(0xA060), 0xFF);
+        mapper.write_rom(0x0000, 0x0A);
+        assert_eq!(mapper.read_ram(0xA060), 0xFF);
+        mapper.write_rom(0x4000, 0x40);
+        assert_eq!(mapper.read_ram(0xA060), 0x00);
+        assert_eq!(mapper.read_ram(0xA070), 0xFF);
+        assert_eq!(mapper.read_ram(0xB060), 0xFF);
+
+        mapper.write_rom(0x2000, 0x05);
+        assert_eq!(mapper.read_rom(0x4000), 5);
+    }
+
+    #[test]
+    fn accelerometer_latch() {
+        let tilt = Rc::new(Cell::new((0.0, 0.0)));
+        let mut mapper = enabled(Some(Box::new(tilt.clone())));
+        assert_eq!(reading(&mapper), (0x8000, 0x8000));
+
+        // 0xAA alone does nothing.
+        mapper.write_ram(0xA010, 0xAA);
+        assert_eq!(reading(&mapper), (0x8000, 0x8000));
+
+        mapper.write_ram(0xA000, 0x55);
+        mapper.write_ram(0xA010, 0xAA);
+        assert_eq!(reading(&mapper), (CENTER, CENTER));
+
+        tilt.set((1.0, -0.5));
+        assert_eq!(reading(&mapper), (CENTER, CENTER));
+        mapper.write_ram(0xA000, 0x55);
+        assert_eq!(reading(&mapper), (0x8000, 0x8000));
+        mapper.write_ram(0xA010, 0xAA);
+        assert_eq!(reading(&mapper), (CENTER + 112, CENTER - 56));
+    }
+
+    fn clock(mapper: &mut Mbc7, bits: u32, count: u32) {
+        for i in (0..count).rev() {
+            let di = if bits & (1 << i) != 0 { 0x02 } else { 0x00 };
+            mapper.write_ram(0xA080, 0x80 | di);
+            mapper.write_ram(0xA080, 0xC0 | di);
+        }
+    }
+
+    #[test]
+    fn eeprom_is_saved() {
+        let mut mapper = enabled(None);
+        // EWEN then WRITE 0x00 = 0x1234, bit by bit through 0xA080.
+        clock(&mut mapper, 0b100_1100_0000, 11);
+        mapper.write_ram(0xA080, 0x00);
+        clock(&mut mapper, 0b101_0000_0000, 11);
+        clock(&mut mapper, 0x1234, 16);
+
+        let save = mapper.save_data().unwrap();
+        assert_eq!(&save[0..2], &[0x34, 0x12]);
+        // Writing the same value again changes nothing.
+        mapper.write_ram(0xA080, 0x00);
+        clock(&mut mapper, 0b101_0000_0000, 11);
+        clock(&mut mapper, 0x1234 >> 1, 15);
+        mapper.write_ram(0xA080, 0x80);
+        assert!(!mapper.write_ram(0xA080, 0xC0));
+
+        let mut loaded = enabled(None);
+        loaded.load_save_data(&save);
+        assert_eq!(loaded.save_data().unwrap(), save);
+    }
+
+    #[test]
+    fn only_eeprom_writes_are_saved() {
+        let mut mapper = enabled(None);
+        // Toggling CLK, and a READ, leave the EEPROM as it was.
+        for v in [0x80, 0xC0, 0x82, 0xC2, 0x00] {
+            assert!(!mapper.write_ram(0xA080, v));
+        }
+        clock(&mut mapper, 0b110_0000_0000, 11);
+        clock(&mut mapper, 0, 17);
+        mapper.write_ram(0xA080, 0x00);
+
+        clock(&mut mapper, 0b100_1100_0000, 11);
+        mapper.write_ram(0xA080, 0x00);
+        clock(&mut mapper, 0b101_0000_0000, 11);
+        clock(&mut mapper, 0x1234 >> 1, 15);
+        mapper.write_ram(0xA080, 0x80);
+        assert!(mapper.write_ram(0xA080, 0xC0));
+    }
+}
@@ -0,0 +1,184 @@
+use super::{banked_rom, load_into, ram_offset, wrap_rom_bank, Mapper};
+
+/// MMM01, the mapper on multicarts that boot a menu and then hand
+/// the cartridge to one game, which sees an MBC1 like mapper over
+/// its part of the ROM.
+///
+/// Until bit 6 of 0x0000-0x1FFF is set the menu in the last 32KB of
+/// ROM is mapped, and the menu writes the game's base bank and masks
+/// to the upper register bits. Setting bit 6 maps the game and locks
+/// those bits, so later writes only reach the bits the game owns.
+pub struct Mmm01 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    battery: bool,
+    ram_enabled: bool,
+    /// The game is mapped and the upper register bits are locked.
+    mapped: bool,
+    /// 9 bit ROM bank, 5 low bits, then 2 middle and 2 high ones.
+    rom_bank: u16,
+    /// ROM bank bits 1-4 that are fixed to the game's base.
+    rom_mask: u8,
+    ram_bank: u8,
+}
+
+impl Mmm01 {
+    pub fn new(rom: Vec<u8>, ram_size: usize, battery: bool) -> Self {
+        Self {
+            rom,
+            ram: vec![0x00; ram_size],
+            battery,
+            ram_enabled: false,
+            mapped: false,
+            rom_bank: 0x000,
+            rom_mask: 0x00,
+            ram_bank: 0x00,
+        }
+    }
+
+    /// The ROM bank bits the game selects with 0x2000-0x3FFF.
+    fn game_bits(&self) -> u16 {
+        0x1F & !((self.rom_mask as u16) << 1)
+    }
+
+    fn ram_offset(&self, addr: u16) -> Option<usize> {
+        if !self.ram_enabled {
+            return None;
+        }
+        ram_offset(&self.ram, self.ram_bank as usize, addr)
+    }
+}
+
+impl Mapper for Mmm01 {
+    fn read_rom(&self, addr: u16) -> u8 {
+        banked_rom(&self.rom, self.rom_bank(addr), addr)
+    }
+
+    fn write_rom(&mut self, addr: u16, v: u8) {
+        match addr {
+            0x0000..=0x1FFF => {
+                self.ram_enabled = v & 0x0F == 0x0A;
+                self.mapped |= v & 0x40 != 0;
+            }
+            0x2000..=0x3FFF if self.mapped => {
+                let bits = self.game_bits();
+                self.rom_bank = (self.rom_bank & !bits) | (v as u16 & bits);
+            }
+            0x2000..=0x3FFF => self.rom_bank = (self.rom_bank & 0x180) | (v as u16 & 0x7F),
+            0x4000..=0x5FFF if self.mapped => self.ram_bank = (self.ram_bank & 0x0C) | (v & 0x03),
+            0x4000..=0x5FFF => {
+                self.ram_bank = v & 0x0F;
+                self.rom_bank = (self.rom_bank & 0x07F) | ((v as u16 & 0x30) << 3);
+            }
+            0x6000..=0x7FFF if !self.mapped => self.rom_mask = (v >> 2) & 0x0F,
+            _ => {}
+        }
+    }
+
+    fn read_ram(&self, addr: u16) -> u8 {
+        self.ram_offset(addr).map_or(0xFF, |i| self.ram[i])
+    }
+
+    fn write_ram(&mut self, addr: u16, v: u8) -> bool {
+        match self.ram_offset(addr) {
+            Some(i) => {
+                self.ram[i] = v;
+                self.battery
+            }
+            None => false,
+        }
+    }
+
+    fn rom_bank(&self, addr: u16) -> u16 {
+        let bank = match (self.mapped, addr < 0x4000) {
+            // The menu, the last two banks.
+            (false, true) => 0x1FE,
+            (false, false) => 0x1FF,
+            (true, true) => self.rom_bank & !self.game_bits(),
+            (true, false) if self.rom_bank & self.game_bits() == 0 => self.rom_bank | 0x01,
+            (true, false) => self.rom_bank,
+        } as usize;
+        wrap_rom_bank(&self.rom, bank)
+    }
+
+    fn rom_mut(&mut self) -> &mut [u8] {
+        &mut self.rom
+    }
+
+    fn save_data(&self) -> Option<Vec<u8>> {
+        self.battery.then(|| self.ram.clone())
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        load_into(&mut self.ram, data);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cartridge::Cartridge;
+    use crate::mbc::{for_cartridge, numbered_rom, MapperOptions, RAM_BANK_SIZE, ROM_BANK_SIZE};
+
+    #[test]
+    fn menu_is_the_last_32kb() {
+        let mut mapper = Mmm01::new(numbered_rom(64), 0, false);
+        assert_eq!(mapper.read_rom(0x0000), 62);
+        assert_eq!(mapper.read_rom(0x4000), 63);
+        // Bank writes don't move the menu.
+        mapper.write_rom(0x2000, 0x05);
+        assert_eq!(mapper.read_rom(0x4000), 63);
+    }
+
+    #[test]
+    fn game_is_locked_to_its_banks() {
+        let mut mapper = Mmm01::new(numbered_rom(64), 4 * RAM_BANK_SIZE, true);
+        // A 128KB game at bank 0x10, with ROM bank bits 3-4 fixed.
+        mapper.write_rom(0x2000, 0x10);
+        mapper.write_rom(0x6000, 0b0011_0000);
+        mapper.write_rom(0x4000, 0x02);
+        mapper.write_rom(0x0000, 0x4A);
+
+        assert_eq!(mapper.read_rom(0x0000), 0x10);
+        assert_eq!(mapper.read_rom(0x4000), 0x11);
+        mapper.write_rom(0x2000, 0x03);
+        assert_eq!(mapper.read_rom(0x4000), 0x13);
+        mapper.write_rom(0x2000, 0x1F);
+        assert_eq!(mapper.read_rom(0x4000), 0x17);
+        assert_eq!(mapper.read_rom(0x0000), 0x10);
+
+        // The mask and base no longer change.
+        mapper.write_rom(0x6000, 0x00);
+        mapper.write_rom(0x0000, 0x0A);
+        mapper.write_rom(0x2000, 0x00);
+        assert_eq!(mapper.read_rom(0x4000), 0x11);
+
+        mapper.write_ram(0xA000, 0x42);
+        mapper.write_rom(0x4000, 0x03);
+        mapper.write_ram(0xA000, 0x43);
+        mapper.write_rom(0x4000, 0x02);
+        assert_eq!(mapper.read_ram(0xA000), 0x42);
+        let save = mapper.save_data().unwrap();
+        assert_eq!(save[2 * RAM_BANK_SIZE], 0x42);
+        assert_eq!(save[3 * RAM_BANK_SIZE], 0x43);
+    }
+
+    #[test]
+    fn header_is_in_the_menu() {
+        // Bank 0 starts the first game, an MBC1 cart, and the MMM01
+        // header with 32KB of battery backed RAM is in the menu.
+        let mut data = numbered_rom(64);
+        data[0x0147] = 0x01;
+        let menu = 62 * ROM_BANK_SIZE;
+        data[menu + 0x0147] = 0x0D;
+        data[menu + 0x0149] = 0x03;
+
+        let mapper = for_cartridge(&Cartridge { data }, MapperOptions::default());
+        assert_eq!(mapper.read_rom(0x0000), 62);
+        assert_eq!(mapper.read_rom(0x4000), 63);
+        assert_eq!(
+            mapper.save_data().map(|ram| ram.len()),
+            Some(4 * RAM_BANK_SIZE)
+        );
+    }
+}
@@ -0,0 +1,175 @@
+use crate::cartridge::Cartridge;
+use crate::header::{has_battery, parse_cartridge_type, parse_ram_size, CartridgeType};
+
+mod camera;
+mod eeprom;
+mod huc1;
+mod huc3;
+mod ir;
+mod mbc1;
+mod mbc2;
+mod mbc3;
+mod mbc5;
+mod mbc7;
+mod mmm01;
+mod rom_only;
+mod rom_ram;
+mod rtc;
+
+pub use camera::{Camera, CameraSource, ImageFiles};
+pub use huc1::Huc1;
+pub use huc3::Huc3;
+pub use ir::IrLink;
+pub use mbc1::Mbc1;
+pub use mbc2::Mbc2;
+pub use mbc3::Mbc3;
+pub use mbc5::Mbc5;
+pub use mbc7::{Mbc7, TiltSource};
+pub use mmm01::Mmm01;
+pub use rom_only::RomOnly;
+pub use rom_ram::RomRam;
+pub use rtc::RtcClock;
+
+pub const ROM_BANK_SIZE: usize = 0x4000;
+pub const RAM_BANK_SIZE: usize = 0x2000;
+
+/// The memory bank controller on a cartridge, it owns the ROM and
+/// any RAM and decides what the CPU sees at 0x0000-0x7FFF and
+/// 0xA000-0xBFFF.
+pub trait Mapper {
+    fn read_rom(&self, addr: u16) -> u8;
+
+    /// Writes to ROM never change it, they set the mapper's
+    /// registers.
+    fn write_rom(&mut self, addr: u16, v: u8);
+
+    fn read_ram(&self, addr: u16) -> u8;
+
+    /// True when the write changed battery backed state, so the save
+    /// needs writing out.
+    fn write_ram(&mut self, addr: u16, v: u8) -> bool;
+
+    /// The ROM bank mapped at addr, which must be in 0x0000-0x7FFF.
+    fn rom_bank(&self, addr: u16) -> u16;
+
+    /// The whole ROM, for debuggers patching code in place.
+    fn rom_mut(&mut self) -> &mut [u8];
+
+    /// The battery backed state to keep between sessions, None if
+    /// the cartridge has no battery.
+    fn save_data(&self) -> Option<Vec<u8>> {
+        None
+    }
+
+    /// Restore state taken with save_data.
+    fn load_save_data(&mut self, _data: &[u8]) {}
+
+    /// Advance by clocks at the fixed 4.19MHz rate, for hardware on
+    /// the cartridge with its own clock.
+    fn tick(&mut self, _clocks: u64) {}
+
+    /// True while a rumble cart's motor is on.
+    fn rumble(&self) -> bool {
+        false
+    }
+
+    /// The tone a cartridge's speaker is playing, if any.
+    fn tone(&self) -> Option<u8> {
+        None
+    }
+}
+
+/// Settings for the cartridge hardware that talks to the host.
+#[derive(Default)]
+pub struct MapperOptions {
+    pub rtc_clock: RtcClock,
+    /// Feeds the MBC7 accelerometer, level when None.
+    pub tilt: Option<Box<dyn TiltSource>>,
+    /// The other end of the HuC1 and HuC3 infrared port.
+    pub ir: Option<Box<dyn IrLink>>,
+    /// What the Pocket Camera sees, a flat grey when None.
+    pub camera: Option<Box<dyn CameraSource>>,
+}
+
+/// The mapper for cartridge, from the type byte in its header. ROMs
+/// too short to have a header are treated as ROM only.
+pub fn for_cartridge(cartridge: &Cartridge, options: MapperOptions) -> Box<dyn Mapper> {
+    let rom = cartridge.data.clone();
+    if rom.len() < 0x0150 {
+        return Box::new(RomOnly::new(rom));
+    }
+
+    let header = header_offset(&rom);
+    let kind = rom[header + 0x0147];
+    let ram_size = parse_ram_size(rom[header + 0x0149]);
+    let battery = has_battery(kind);
+    match parse_cartridge_type(kind) {
+        CartridgeType::MBC1 => Box::new(Mbc1::new(rom, ram_size, battery)),
+        CartridgeType::MBC2 => Box::new(Mbc2::new(rom, battery)),
+        CartridgeType::RomRam => Box::new(RomRam::new(rom, ram_size, battery)),
+        CartridgeType::MMM01 => Box::new(Mmm01::new(rom, ram_size, battery)),
+        CartridgeType::MBC3 => {
+            let rtc = matches!(kind, 0x0F | 0x10).then_some(options.rtc_clock);
+            Box::new(Mbc3::new(rom, ram_size, rtc, battery))
+        }
+        CartridgeType::MBC5 => {
+            let rumble = matches!(kind, 0x1C..=0x1E);
+            Box::new(Mbc5::new(rom, ram_size, battery, rumble))
+        }
+        CartridgeType::MBC7 => Box::new(Mbc7::new(rom, options.tilt)),
+        CartridgeType::PocketCamera => Box::new(Camera::new(rom, options.camera)),
+        CartridgeType::HuC1 => Box::new(Huc1::new(rom, ram_size, options.ir)),
+        CartridgeType::HuC3 => Box::new(Huc3::new(rom, ram_size, options.rtc_clock, options.ir)),
+        _ => Box::new(RomOnly::new(rom)),
+    }
+}
+
+/// Where the header that describes the cartridge is. MMM01 multicarts
+/// boot the menu in the last 32KB, and its header names the mapper,
+/// while bank 0 holds the first game's own header.
+fn header_offset(rom: &[u8]) -> usize {
+    let menu = rom.len().saturating_sub(2 * ROM_BANK_SIZE);
+    if menu > 0 && matches!(rom[menu + 0x0147], 0x0B..=0x0D) {
+        menu
+    } else {
+        0
+    }
+}
+
+/// The byte the CPU sees at addr in 0x0000-0x7FFF with bank mapped
+/// in that half of it.
+fn banked_rom(rom: &[u8], bank: u16, addr: u16) -> u8 {
+    rom[(bank as usize * ROM_BANK_SIZE + (addr as usize % ROM_BANK_SIZE)) % rom.len()]
+}
+
+/// bank wrapped to the number of banks in rom, the unconnected high
+/// bank bits are ignored.
+fn wrap_rom_bank(rom: &[u8], bank: usize) -> u16 {
+    (bank % (rom.len() / ROM_BANK_SIZE).max(1)) as u16
+}
+
+/// The offset in ram of addr in 0xA000-0xBFFF with bank mapped, None
+/// if there is no RAM.
+fn ram_offset(ram: &[u8], bank: usize, addr: u16) -> Option<usize> {
+    if ram.is_empty() {
+        return None;
+    }
+    Some((bank * RAM_BANK_SIZE + (addr as usize - 0xA000)) % ram.len())
+}
+
+/// Copy a save into ram. A short save leaves the rest of ram as it
+/// is and anything past its end is ignored.
+fn load_into(ram: &mut [u8], data: &[u8]) {
+    let len = ram.len().min(data.len());
+    ram[..len].copy_from_slice(&data[..len]);
+}
+
+/// A ROM where the first byte of every bank is its number.
+#[cfg(test)]
+fn numbered_rom(banks: usize) -> Vec<u8> {
+    let mut rom = vec![0x00; banks * ROM_BANK_SIZE];
+    for bank in 0..banks {
+        rom[bank * ROM_BANK_SIZE] = bank as u8;
+    }
+    rom
+}
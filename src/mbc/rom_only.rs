@@ -0,0 +1,63 @@
+use super::{Mapper, ROM_BANK_SIZE};
+
+/// 32KB of ROM and nothing else.
+pub struct RomOnly {
+    rom: Vec<u8>,
+}
+
+impl RomOnly {
+    /// The ROM is padded with zeros, or cut, to 32KB.
+    pub fn new(mut rom: Vec<u8>) -> Self {
+        rom.resize(2 * ROM_BANK_SIZE, 0x00);
+        Self { rom }
+    }
+}
+
+impl Default for RomOnly {
+    fn default() -> Self {
+        Self::new(Vec::new())
+    }
+}
+
+impl Mapper for RomOnly {
+    fn read_rom(&self, addr: u16) -> u8 {
+        self.rom[addr as usize]
+    }
+
+    fn write_rom(&mut self, _addr: u16, _v: u8) {}
+
+    fn read_ram(&self, _addr: u16) -> u8 {
+        0xFF
+    }
+
+    fn write_ram(&mut self, _addr: u16, _v: u8) -> bool {
+        false
+    }
+
+    fn rom_bank(&self, addr: u16) -> u16 {
+        addr / ROM_BANK_SIZE as u16
+    }
+
+    fn rom_mut(&mut self) -> &mut [u8] {
+        &mut self.rom
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rom_is_read_only() {
+        let mut mapper = RomOnly::new(vec![0x12, 0x34]);
+        mapper.write_rom(0x0000, 0xFF);
+        mapper.write_ram(0xA000, 0x56);
+
+        assert_eq!(mapper.read_rom(0x0000), 0x12);
+        assert_eq!(mapper.read_rom(0x0001), 0x34);
+        assert_eq!(mapper.read_rom(0x7FFF), 0x00);
+        assert_eq!(mapper.read_ram(0xA000), 0xFF);
+        assert_eq!(mapper.rom_bank(0x3FFF), 0);
+        assert_eq!(mapper.rom_bank(0x4000), 1);
+    }
+}
@@ -0,0 +1,86 @@
+use super::{load_into, ram_offset, Mapper, ROM_BANK_SIZE};
+
+/// 32KB of ROM and up to 8KB of RAM wired straight to the bus, no
+/// banking and no enable register.
+pub struct RomRam {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    battery: bool,
+}
+
+impl RomRam {
+    /// The ROM is padded with zeros, or cut, to 32KB.
+    pub fn new(mut rom: Vec<u8>, ram_size: usize, battery: bool) -> Self {
+        rom.resize(2 * ROM_BANK_SIZE, 0x00);
+        Self {
+            rom,
+            ram: vec![0x00; ram_size],
+            battery,
+        }
+    }
+}
+
+impl Mapper for RomRam {
+    fn read_rom(&self, addr: u16) -> u8 {
+        self.rom[addr as usize]
+    }
+
+    fn write_rom(&mut self, _addr: u16, _v: u8) {}
+
+    fn read_ram(&self, addr: u16) -> u8 {
+        ram_offset(&self.ram, 0, addr).map_or(0xFF, |i| self.ram[i])
+    }
+
+    fn write_ram(&mut self, addr: u16, v: u8) -> bool {
+        match ram_offset(&self.ram, 0, addr) {
+            Some(i) => {
+                self.ram[i] = v;
+                self.battery
+            }
+            None => false,
+        }
+    }
+
+    fn rom_bank(&self, addr: u16) -> u16 {
+        addr / ROM_BANK_SIZE as u16
+    }
+
+    fn rom_mut(&mut self) -> &mut [u8] {
+        &mut self.rom
+    }
+
+    fn save_data(&self) -> Option<Vec<u8>> {
+        self.battery.then(|| self.ram.clone())
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        load_into(&mut self.ram, data);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mbc::RAM_BANK_SIZE;
+
+    #[test]
+    fn ram_needs_no_enable() {
+        let mut mapper = RomRam::new(vec![0x12], RAM_BANK_SIZE, true);
+        mapper.write_rom(0x0000, 0x00);
+        assert!(mapper.write_ram(0xA000, 0x56));
+        assert!(mapper.write_ram(0xBFFF, 0x78));
+
+        assert_eq!(mapper.read_rom(0x0000), 0x12);
+        assert_eq!(mapper.read_ram(0xA000), 0x56);
+        assert_eq!(mapper.read_ram(0xBFFF), 0x78);
+        assert_eq!(mapper.save_data().unwrap()[0], 0x56);
+    }
+
+    #[test]
+    fn no_ram_no_battery() {
+        let mut mapper = RomRam::new(Vec::new(), 0, false);
+        assert!(!mapper.write_ram(0xA000, 0x56));
+        assert_eq!(mapper.read_ram(0xA000), 0xFF);
+        assert_eq!(mapper.save_data(), None);
+    }
+}
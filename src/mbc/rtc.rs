@@ -0,0 +1,410 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Clocks at the fixed 4.19MHz rate in one second.
+pub const CLOCKS_PER_SECOND: u64 = 4_194_304;
+
+/// Bytes the RTC adds to a battery save.
+pub const SAVE_SIZE: usize = 48;
+
+/// What drives the real time clock.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum RtcClock {
+    /// Time passes with the host's wall clock, as on a real cart.
+    #[default]
+    Host,
+    /// Time passes with emulated cycles, so it runs fast or slow
+    /// with the emulator and stops when it is paused.
+    Emulated,
+}
+
+/// Counts the seconds that pass on a cartridge clock, from the host's
+/// wall clock or from emulated clocks depending on RtcClock.
+#[derive(Debug, Clone)]
+pub struct SecondCounter {
+    clock: RtcClock,
+    /// Clocks towards the next second in emulated mode.
+    clocks: u64,
+    /// Host time in seconds elapsed was last taken at.
+    synced: u64,
+}
+
+impl SecondCounter {
+    pub fn new(clock: RtcClock) -> Self {
+        Self {
+            clock,
+            clocks: 0,
+            synced: host_now(),
+        }
+    }
+
+    /// Advance by clocks at the fixed 4.19MHz rate, returning the
+    /// seconds completed in emulated mode.
+    pub fn tick(&mut self, clocks: u64) -> u64 {
+        if self.clock != RtcClock::Emulated {
+            return 0;
+        }
+        self.clocks += clocks;
+        let seconds = self.clocks / CLOCKS_PER_SECOND;
+        self.clocks %= CLOCKS_PER_SECOND;
+        seconds
+    }
+
+    /// Host seconds since the last sync, always 0 in emulated mode.
+    pub fn elapsed(&self) -> u64 {
+        match self.clock {
+            RtcClock::Host => host_now().saturating_sub(self.synced),
+            RtcClock::Emulated => 0,
+        }
+    }
+
+    /// Take the host seconds since the last sync.
+    pub fn sync(&mut self) -> u64 {
+        let elapsed = self.elapsed();
+        self.synced = host_now();
+        elapsed
+    }
+
+    /// Start counting from a whole second, now.
+    pub fn restart(&mut self) {
+        self.clocks = 0;
+        self.synced = host_now();
+    }
+}
+
+/// The MBC3 real time clock.
+///
+/// The live registers count while the clock is not halted, reads see
+/// the registers copied by the last latch.
+#[derive(Debug, Clone)]
+pub struct Rtc {
+    counter: SecondCounter,
+    seconds: u8,
+    minutes: u8,
+    hours: u8,
+    /// 9 bit day counter.
+    days: u16,
+    halted: bool,
+    /// The day counter overflowed, stays set until written.
+    carry: bool,
+    latched: [u8; 5],
+}
+
+impl Rtc {
+    pub fn new(clock: RtcClock) -> Self {
+        Self {
+            counter: SecondCounter::new(clock),
+            seconds: 0,
+            minutes: 0,
+            hours: 0,
+            days: 0,
+            halted: false,
+            carry: false,
+            latched: [0x00; 5],
+        }
+    }
+
+    /// The latched value of register 0x08-0x0C.
+    pub fn read(&self, register: u8) -> u8 {
+        match register {
+            0x08..=0x0C => self.latched[(register - 0x08) as usize],
+            _ => 0xFF,
+        }
+    }
+
+    /// Write the live value of register 0x08-0x0C.
+    pub fn write(&mut self, register: u8, v: u8) {
+        self.sync();
+        match register {
+            0x08 => {
+                self.seconds = v & 0x3F;
+                self.counter.restart();
+            }
+            0x09 => self.minutes = v & 0x3F,
+            0x0A => self.hours = v & 0x1F,
+            0x0B => self.days = (self.days & 0x100) | v as u16,
+            0x0C => {
+                self.days = (self.days & 0xFF) | ((v as u16 & 0x01) << 8);
+                self.halted = v & 0x40 != 0;
+                self.carry = v & 0x80 != 0;
+            }
+            _ => {}
+        }
+    }
+
+    /// Copy the live registers to the ones reads see.
+    pub fn latch(&mut self) {
+        self.sync();
+        self.latched = self.registers();
+    }
+
+    /// Advance by clocks at the fixed 4.19MHz rate, in emulated mode.
+    pub fn tick(&mut self, clocks: u64) {
+        if self.halted {
+            return;
+        }
+        let seconds = self.counter.tick(clocks);
+        if seconds > 0 {
+            self.advance(seconds);
+        }
+    }
+
+    /// Count seconds on the live registers, unless halted.
+    pub fn advance(&mut self, mut seconds: u64) {
+        if self.halted {
+            return;
+        }
+
+        // Registers written out of range count up to their mask and
+        // wrap without carrying, step them one second at a time.
+        while self.seconds >= 60 || self.minutes >= 60 || self.hours >= 24 {
+            if seconds == 0 {
+                return;
+            }
+            self.step();
+            seconds -= 1;
+        }
+
+        let total = self.seconds as u64
+            + 60 * self.minutes as u64
+            + 3600 * self.hours as u64
+            + 86400 * self.days as u64
+            + seconds;
+        let days = total / 86400;
+        if days >= 512 {
+            self.carry = true;
+        }
+        self.days = (days % 512) as u16;
+        self.hours = (total % 86400 / 3600) as u8;
+        self.minutes = (total % 3600 / 60) as u8;
+        self.seconds = (total % 60) as u8;
+    }
+
+    fn step(&mut self) {
+        self.seconds = (self.seconds + 1) & 0x3F;
+        if self.seconds != 60 {
+            return;
+        }
+        self.seconds = 0;
+        self.minutes = (self.minutes + 1) & 0x3F;
+        if self.minutes != 60 {
+            return;
+        }
+        self.minutes = 0;
+        self.hours = (self.hours + 1) & 0x1F;
+        if self.hours != 24 {
+            return;
+        }
+        self.hours = 0;
+        self.days += 1;
+        if self.days == 512 {
+            self.days = 0;
+            self.carry = true;
+        }
+    }
+
+    /// Bring the live registers up to date with the host clock.
+    fn sync(&mut self) {
+        let seconds = self.counter.sync();
+        self.advance(seconds);
+    }
+
+    fn registers(&self) -> [u8; 5] {
+        [
+            self.seconds,
+            self.minutes,
+            self.hours,
+            self.days as u8,
+            ((self.carry as u8) << 7) | ((self.halted as u8) << 6) | (self.days >> 8) as u8,
+        ]
+    }
+
+    /// The state as it is commonly appended to a save, the live and
+    /// latched registers as 32 bit words then the host time as a 64
+    /// bit word, all little endian.
+    pub fn save(&self) -> [u8; SAVE_SIZE] {
+        let mut rtc = self.clone();
+        rtc.sync();
+
+        let mut data = [0x00; SAVE_SIZE];
+        let words = rtc.registers().into_iter().chain(rtc.latched);
+        for (i, v) in words.enumerate() {
+            data[i * 4] = v;
+        }
+        data[40..].copy_from_slice(&host_now().to_le_bytes());
+        data
+    }
+
+    /// Restore a state from save. The clock keeps running while the
+    /// emulator is closed, so the time since the save is added.
+    pub fn load(&mut self, data: &[u8]) {
+        if data.len() < SAVE_SIZE {
+            return;
+        }
+        let word = |i: usize| data[i * 4];
+        self.halted = false;
+        for register in 0x08..=0x0C {
+            self.write(register, word(register as usize - 0x08));
+        }
+        for (i, latched) in self.latched.iter_mut().enumerate() {
+            *latched = word(5 + i);
+        }
+
+        self.advance(seconds_since(&data[40..48]));
+        self.counter.restart();
+    }
+}
+
+pub(super) fn host_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Read a little endian 64 bit word from the first 8 bytes of data.
+pub(super) fn read_u64(data: &[u8]) -> u64 {
+    let mut bytes = [0x00; 8];
+    bytes.copy_from_slice(&data[..8]);
+    u64::from_le_bytes(bytes)
+}
+
+/// Seconds from the host time saved in the first 8 bytes of data
+/// until now.
+pub(super) fn seconds_since(data: &[u8]) -> u64 {
+    host_now().saturating_sub(read_u64(data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rtc() -> Rtc {
+        Rtc::new(RtcClock::Emulated)
+    }
+
+    #[test]
+    fn second_counter() {
+        let mut counter = SecondCounter::new(RtcClock::Emulated);
+        assert_eq!(counter.tick(CLOCKS_PER_SECOND - 1), 0);
+        assert_eq!(counter.tick(CLOCKS_PER_SECOND * 2 + 1), 3);
+        counter.tick(CLOCKS_PER_SECOND - 1);
+        counter.restart();
+        assert_eq!(counter.tick(1), 0);
+        assert_eq!(counter.sync(), 0);
+
+        let mut counter = SecondCounter::new(RtcClock::Host);
+        assert_eq!(counter.tick(CLOCKS_PER_SECOND * 10), 0);
+        counter.synced -= 5;
+        assert_eq!(counter.elapsed(), 5);
+        assert_eq!(counter.sync(), 5);
+        assert_eq!(counter.elapsed(), 0);
+    }
+
+    #[test]
+    fn counts_with_emulated_clocks() {
+        let mut rtc = rtc();
+        rtc.tick(CLOCKS_PER_SECOND - 1);
+        rtc.latch();
+        assert_eq!(rtc.read(0x08), 0);
+
+        rtc.tick(1);
+        rtc.latch();
+        assert_eq!(rtc.read(0x08), 1);
+
+        rtc.tick(CLOCKS_PER_SECOND * 60);
+        rtc.latch();
+        assert_eq!((rtc.read(0x08), rtc.read(0x09)), (1, 1));
+    }
+
+    #[test]
+    fn reads_are_latched() {
+        let mut rtc = rtc();
+        rtc.advance(5);
+        assert_eq!(rtc.read(0x08), 0);
+        rtc.latch();
+        assert_eq!(rtc.read(0x08), 5);
+        rtc.advance(5);
+        assert_eq!(rtc.read(0x08), 5);
+    }
+
+    #[test]
+    fn day_counter_carries() {
+        let mut rtc = rtc();
+        rtc.write(0x0B, 0xFF);
+        rtc.write(0x0C, 0x01);
+        rtc.write(0x0A, 23);
+        rtc.write(0x09, 59);
+        rtc.write(0x08, 59);
+        rtc.advance(1);
+        rtc.latch();
+        assert_eq!(rtc.read(0x0B), 0x00);
+        assert_eq!(rtc.read(0x0C), 0x80);
+
+        // The carry sticks until it is written.
+        rtc.advance(86400);
+        rtc.latch();
+        assert_eq!(rtc.read(0x0B), 0x01);
+        assert_eq!(rtc.read(0x0C), 0x80);
+        rtc.write(0x0C, 0x00);
+        rtc.latch();
+        assert_eq!(rtc.read(0x0C), 0x00);
+    }
+
+    #[test]
+    fn halt_stops_the_clock() {
+        let mut rtc = rtc();
+        rtc.write(0x0C, 0x40);
+        rtc.tick(CLOCKS_PER_SECOND * 10);
+        rtc.advance(10);
+        rtc.latch();
+        assert_eq!(rtc.read(0x08), 0);
+        assert_eq!(rtc.read(0x0C), 0x40);
+
+        rtc.write(0x0C, 0x00);
+        rtc.advance(10);
+        rtc.latch();
+        assert_eq!(rtc.read(0x08), 10);
+    }
+
+    #[test]
+    fn out_of_range_values_wrap_without_carry() {
+        let mut rtc = rtc();
+        rtc.write(0x08, 62);
+        rtc.advance(2);
+        rtc.latch();
+        assert_eq!((rtc.read(0x08), rtc.read(0x09)), (0, 0));
+
+        rtc.write(0x08, 59);
+        rtc.advance(1);
+        rtc.latch();
+        assert_eq!((rtc.read(0x08), rtc.read(0x09)), (0, 1));
+
+        rtc.write(0x0A, 30);
+        rtc.write(0x09, 59);
+        rtc.write(0x08, 59);
+        rtc.advance(3600);
+        rtc.latch();
+        assert_eq!(rtc.read(0x0A), 31);
+        rtc.advance(3600);
+        rtc.latch();
+        assert_eq!((rtc.read(0x0A), rtc.read(0x0B)), (0, 0));
+    }
+
+    #[test]
+    fn save_and_load_keep_time_passing() {
+        let mut rtc = rtc();
+        rtc.write(0x0A, 5);
+        rtc.latch();
+        let mut save = rtc.save();
+        assert_eq!(save[8], 5);
+
+        // Saved an hour ago.
+        let saved = host_now() - 3600;
+        save[40..].copy_from_slice(&saved.to_le_bytes());
+        let mut loaded = Rtc::new(RtcClock::Emulated);
+        loaded.load(&save);
+        assert_eq!(loaded.read(0x0A), 5);
+        loaded.latch();
+        assert_eq!(loaded.read(0x0A), 6);
+    }
+}
@@ -1,60 +1,374 @@
+use crate::bus::Bus;
 use crate::cartridge::Cartridge;
+use crate::dma::{Hdma, OamDma, HDMA_BLOCK_LEN, HDMA_BLOCK_MCYCLES};
+use crate::interrupts::{InterruptKind, IF_ADDR};
+use crate::mbc::{self, Mapper, MapperOptions, RomOnly};
+use crate::model::HardwareModel;
+use crate::speed::SpeedSwitch;
 use crate::tile::Tile;
-use std::cell::RefCell;
+use crate::timer::Timer;
+use std::cell::{Cell, RefCell};
 use std::io;
-use std::rc::Rc;
+
+pub const P1_ADDR: u16 = 0xFF00;
+pub const VBK_ADDR: u16 = 0xFF4F;
+pub const SVBK_ADDR: u16 = 0xFF70;
+/// Writing a non-zero value unmaps the boot ROM until reset.
+pub const BOOT_ADDR: u16 = 0xFF50;
 
 pub struct MemoryMap {
-    memory: Rc<RefCell<[u8; 0xFFFF + 1]>>,
+    cgb: bool,
+    mapper: RefCell<Box<dyn Mapper>>,
+    /// Mapped over the cartridge until BOOT is written.
+    boot_rom: RefCell<Option<Vec<u8>>>,
+    /// Two 8KB banks on a CGB, selected by VBK.
+    vram: RefCell<[u8; 0x4000]>,
+    vbk: Cell<u8>,
+    /// Eight 4KB banks on a CGB, bank 0 at 0xC000 and the bank
+    /// selected by SVBK at 0xD000.
+    wram: RefCell<[u8; 0x8000]>,
+    svbk: Cell<u8>,
+    oam: RefCell<[u8; 0xA0]>,
+    /// I/O registers without a device of their own yet.
+    io: RefCell<[u8; 0x80]>,
+    hram: RefCell<[u8; 0x7F]>,
+    ie: Cell<u8>,
+    timer: RefCell<Timer>,
+    speed: RefCell<SpeedSwitch>,
+    dma: RefCell<OamDma>,
+    hdma: RefCell<Hdma>,
+    /// M-cycles the CPU is held off the bus by HDMA.
+    stall: Cell<u16>,
+    /// Clocks at the fixed 4.19MHz rate, the PPU and APU run off
+    /// these whatever the CPU speed.
+    clocks: Cell<u64>,
+    /// Battery backed cartridge state was written since take_ram_dirty.
+    ram_dirty: Cell<bool>,
 }
 
 impl Default for MemoryMap {
+    /// A DMG with an empty 32KB cartridge.
     fn default() -> MemoryMap {
+        let mut io = [0; 0x80];
+        // P1 with no buttons pressed.
+        io[(P1_ADDR - MemSectors::IOReg.val()) as usize] = 0xCF;
         MemoryMap {
-            memory: Rc::new(RefCell::new([0; 0xFFFF + 1])),
+            cgb: false,
+            mapper: RefCell::new(Box::new(RomOnly::default())),
+            boot_rom: RefCell::new(None),
+            vram: RefCell::new([0; 0x4000]),
+            vbk: Cell::new(0),
+            wram: RefCell::new([0; 0x8000]),
+            svbk: Cell::new(0),
+            oam: RefCell::new([0; 0xA0]),
+            io: RefCell::new(io),
+            hram: RefCell::new([0; 0x7F]),
+            ie: Cell::new(0),
+            timer: RefCell::new(Timer::default()),
+            speed: RefCell::new(SpeedSwitch::default()),
+            dma: RefCell::new(OamDma::default()),
+            hdma: RefCell::new(Hdma::default()),
+            stall: Cell::new(0),
+            clocks: Cell::new(0),
+            ram_dirty: Cell::new(false),
         }
     }
 }
 
 impl MemoryMap {
-    pub fn load_cartridge(&self, cartridge: &Cartridge) {
-        // println!("Cartridge size: {}", cartridge.data.len());
-        (0..0xFFFF).for_each(|pos| {
-            if pos >= cartridge.data.len() {
-                self.memory.borrow_mut()[pos] = 0x00;
-            } else {
-                self.memory.borrow_mut()[pos] = cartridge.data[pos];
-            };
-        });
+    /// A memory map with the CGB only registers.
+    pub fn cgb() -> MemoryMap {
+        MemoryMap {
+            cgb: true,
+            speed: RefCell::new(SpeedSwitch::cgb()),
+            hdma: RefCell::new(Hdma::cgb()),
+            ..MemoryMap::default()
+        }
     }
 
-    pub fn read_byte(&self, pos: u16) -> Result<u8, io::Error> {
-        if pos > MemSectors::IE.val() {
-            Err(io::Error::new(
-                io::ErrorKind::InvalidInput,
-                "Cannot read from out of bounds memory.",
-            ))
+    /// A memory map for model, with the CGB only registers on a CGB
+    /// or AGB.
+    pub fn for_model(model: HardwareModel) -> MemoryMap {
+        if model.is_cgb() {
+            MemoryMap::cgb()
         } else {
-            // println!("read_byte --> {:X} :: {:X}", pos, self.memory.borrow_mut()[pos as usize]);
-            Ok(self.memory.borrow_mut()[pos as usize])
+            MemoryMap::default()
+        }
+    }
+
+    /// Map a boot ROM over 0x0000-0x00FF, and 0x0200 up to its end
+    /// for the larger CGB ones, until BOOT is written.
+    pub fn load_boot_rom(&self, rom: Vec<u8>) {
+        *self.boot_rom.borrow_mut() = Some(rom);
+    }
+
+    pub fn boot_rom_mapped(&self) -> bool {
+        self.boot_rom.borrow().is_some()
+    }
+
+    fn read_boot_rom(&self, pos: u16) -> Option<u8> {
+        let boot_rom = self.boot_rom.borrow();
+        let rom = boot_rom.as_ref()?;
+        match pos {
+            // The cartridge header shows through.
+            0x0100..=0x01FF => None,
+            _ => rom.get(pos as usize).copied(),
         }
     }
 
+    pub fn load_cartridge(&self, cartridge: &Cartridge) {
+        self.load_cartridge_with(cartridge, MapperOptions::default());
+    }
+
+    pub fn load_cartridge_with(&self, cartridge: &Cartridge, options: MapperOptions) {
+        *self.mapper.borrow_mut() = mbc::for_cartridge(cartridge, options);
+    }
+
+    pub fn read_byte(&self, pos: u16) -> Result<u8, io::Error> {
+        let byte = match MemSectors::of(pos) {
+            MemSectors::RomBank0 | MemSectors::RomBank1 => match self.read_boot_rom(pos) {
+                Some(byte) => byte,
+                None => self.mapper.borrow().read_rom(pos),
+            },
+            MemSectors::VRam => self.vram.borrow()[self.vram_index(pos)],
+            MemSectors::ExtRam => self.mapper.borrow().read_ram(pos),
+            MemSectors::WorkRam0 | MemSectors::WorkRam1 | MemSectors::EchoRam => {
+                self.wram.borrow()[self.wram_index(pos)]
+            }
+            MemSectors::OAM => self.oam.borrow()[(pos - MemSectors::OAM.val()) as usize],
+            MemSectors::Unused => 0x00,
+            MemSectors::IOReg => self.read_io(pos),
+            MemSectors::HRam => self.hram.borrow()[(pos - MemSectors::HRam.val()) as usize],
+            MemSectors::IE => self.ie.get(),
+        };
+        Ok(byte)
+    }
+
     pub fn write_byte(&self, pos: u16, byte: u8) -> Result<u8, io::Error> {
-        // println!("\x1b[92mWriting {:#4X?} to [{:#6X?}]\x1b[0m", byte, pos);
-        self.memory.borrow_mut()[pos as usize] = byte;
+        match MemSectors::of(pos) {
+            MemSectors::RomBank0 | MemSectors::RomBank1 => {
+                self.mapper.borrow_mut().write_rom(pos, byte)
+            }
+            MemSectors::VRam => self.vram.borrow_mut()[self.vram_index(pos)] = byte,
+            MemSectors::ExtRam => {
+                if self.mapper.borrow_mut().write_ram(pos, byte) {
+                    self.ram_dirty.set(true);
+                }
+            }
+            MemSectors::WorkRam0 | MemSectors::WorkRam1 | MemSectors::EchoRam => {
+                self.wram.borrow_mut()[self.wram_index(pos)] = byte
+            }
+            MemSectors::OAM => self.oam.borrow_mut()[(pos - MemSectors::OAM.val()) as usize] = byte,
+            MemSectors::Unused => {}
+            MemSectors::IOReg => self.write_io(pos, byte),
+            MemSectors::HRam => {
+                self.hram.borrow_mut()[(pos - MemSectors::HRam.val()) as usize] = byte
+            }
+            MemSectors::IE => self.ie.set(byte),
+        }
         Ok(byte)
-        /* if pos < MemSectors::RomBank1.val() {
-            println!("\x1b[91mCan't {:#4X?} to [{:#6X?}]\x1b[0m", byte, pos);
-            Err(io::Error::new(
-                io::ErrorKind::PermissionDenied,
-                format!("Cannot write to ROM Banks at 0x{:X?}.", pos),
-            ))
+    }
+
+    /// Write a byte the way a debugger would. ROM is patched in the
+    /// bank mapped at pos instead of the write going to the mapper,
+    /// anywhere else this is write_byte.
+    pub fn patch_byte(&self, pos: u16, byte: u8) -> Result<u8, io::Error> {
+        match self.rom_bank(pos) {
+            Some(bank) => {
+                let mut mapper = self.mapper.borrow_mut();
+                let rom = mapper.rom_mut();
+                let offset =
+                    bank as usize * mbc::ROM_BANK_SIZE + (pos as usize % mbc::ROM_BANK_SIZE);
+                let len = rom.len();
+                rom[offset % len] = byte;
+                Ok(byte)
+            }
+            None => self.write_byte(pos, byte),
+        }
+    }
+
+    /// The cartridge's battery backed state, see Mapper::save_data.
+    pub fn save_data(&self) -> Option<Vec<u8>> {
+        self.mapper.borrow().save_data()
+    }
+
+    pub fn load_save_data(&self, data: &[u8]) {
+        self.mapper.borrow_mut().load_save_data(data)
+    }
+
+    /// True if cartridge RAM was written since the last call.
+    pub fn take_ram_dirty(&self) -> bool {
+        self.ram_dirty.replace(false)
+    }
+
+    /// True while a rumble cart's motor is on, for the frontend to
+    /// pass on to the controller.
+    pub fn rumble(&self) -> bool {
+        self.mapper.borrow().rumble()
+    }
+
+    /// The tone a HuC3 cart's speaker is playing, for the frontend to
+    /// play alongside the APU.
+    pub fn tone(&self) -> Option<u8> {
+        self.mapper.borrow().tone()
+    }
+
+    fn vram_index(&self, pos: u16) -> usize {
+        (self.vbk.get() & 0x01) as usize * 0x2000 + (pos - MemSectors::VRam.val()) as usize
+    }
+
+    /// Echo RAM is folded onto WRAM here.
+    fn wram_index(&self, pos: u16) -> usize {
+        let offset = (pos - MemSectors::WorkRam0.val()) as usize % 0x2000;
+        match offset {
+            0x0000..=0x0FFF => offset,
+            _ => (self.svbk.get() & 0x07).max(1) as usize * 0x1000 + offset - 0x1000,
+        }
+    }
+
+    fn read_io(&self, pos: u16) -> u8 {
+        match pos {
+            IF_ADDR => 0xE0 | self.io.borrow()[(IF_ADDR - MemSectors::IOReg.val()) as usize],
+            _ if Timer::contains(pos) => self.timer.borrow().read(pos),
+            _ if SpeedSwitch::contains(pos) => self.speed.borrow().read(pos),
+            _ if OamDma::contains(pos) => self.dma.borrow().read(pos),
+            _ if Hdma::contains(pos) => self.hdma.borrow().read(pos),
+            VBK_ADDR if self.cgb => 0xFE | self.vbk.get(),
+            SVBK_ADDR if self.cgb => 0xF8 | self.svbk.get(),
+            VBK_ADDR | SVBK_ADDR | BOOT_ADDR => 0xFF,
+            _ => self.io.borrow()[(pos - MemSectors::IOReg.val()) as usize],
+        }
+    }
+
+    fn write_io(&self, pos: u16, byte: u8) {
+        match pos {
+            IF_ADDR => {
+                self.io.borrow_mut()[(IF_ADDR - MemSectors::IOReg.val()) as usize] = byte & 0x1F
+            }
+            _ if Timer::contains(pos) => self.timer.borrow_mut().write(pos, byte),
+            _ if SpeedSwitch::contains(pos) => self.speed.borrow_mut().write(pos, byte),
+            _ if OamDma::contains(pos) => self.dma.borrow_mut().write(pos, byte),
+            _ if Hdma::contains(pos) => {
+                let blocks = self.hdma.borrow_mut().write(pos, byte);
+                if let Some(blocks) = blocks {
+                    for _ in 0..blocks {
+                        let block = self.hdma.borrow_mut().next_block();
+                        self.hdma_block(block);
+                    }
+                }
+            }
+            VBK_ADDR if self.cgb => self.vbk.set(byte & 0x01),
+            SVBK_ADDR if self.cgb => self.svbk.set(byte & 0x07),
+            VBK_ADDR | SVBK_ADDR => {}
+            BOOT_ADDR if byte != 0 => *self.boot_rom.borrow_mut() = None,
+            BOOT_ADDR => {}
+            _ => self.io.borrow_mut()[(pos - MemSectors::IOReg.val()) as usize] = byte,
+        }
+    }
+
+    /// Advance everything on the bus by one M-cycle.
+    pub fn tick(&self) {
+        let (clocks, running) = self.speed.borrow_mut().tick();
+        self.clocks.set(self.clocks.get() + clocks);
+        self.mapper.borrow_mut().tick(clocks);
+        if !running {
+            return;
+        }
+
+        if self.timer.borrow_mut().tick() {
+            self.request_interrupt(InterruptKind::Timer);
+        }
+        self.stall.set(self.stall.get().saturating_sub(1));
+
+        let copy = self.dma.borrow_mut().tick();
+        if let Some((source, i)) = copy {
+            // Sources above 0xDFFF see WRAM, as echo RAM does.
+            let source = if source >= 0xE000 {
+                source - 0x2000
+            } else {
+                source
+            };
+            let byte = self.read_byte(source).unwrap_or(0xFF);
+            self.oam.borrow_mut()[i as usize] = byte;
+        }
+    }
+
+    /// Copy an HDMA block to VRAM and hold the CPU off the bus for it.
+    fn hdma_block(&self, (source, dest): (u16, u16)) {
+        for i in 0..HDMA_BLOCK_LEN {
+            let byte = self.read_byte(source.wrapping_add(i)).unwrap_or(0xFF);
+            self.write_byte(dest + i, byte).ok();
+        }
+        let mcycles = if self.is_double_speed() {
+            2 * HDMA_BLOCK_MCYCLES
         } else {
-            println!("\x1b[92mWriting {:#4X?} to [{:#6X?}]\x1b[0m", byte, pos);
-            self.memory.borrow_mut()[pos as usize] = byte;
-            Ok(byte)
-        } */
+            HDMA_BLOCK_MCYCLES
+        };
+        self.stall.set(self.stall.get() + mcycles);
+    }
+
+    /// Called by the PPU as it enters HBlank, copies a block of a
+    /// running HBlank DMA.
+    pub fn hblank(&self) {
+        let block = self.hdma.borrow_mut().hblank();
+        if let Some(block) = block {
+            self.hdma_block(block);
+        }
+    }
+
+    /// True while HDMA holds the CPU off the bus, or a speed switch
+    /// has the CPU clock stopped.
+    pub fn stalled(&self) -> bool {
+        self.stall.get() > 0 || self.speed.borrow().is_paused()
+    }
+
+    /// Read a byte as the CPU sees it, which during OAM DMA is 0xFF
+    /// below 0xFF00.
+    ///
+    /// DMA holds the cartridge, WRAM and video buses, while I/O, HRAM
+    /// and IE sit on the CPU's own bus and stay reachable. That is how
+    /// a DMA is restarted, from code running in HRAM writing 0xFF46.
+    pub fn cpu_read(&self, pos: u16) -> Result<u8, io::Error> {
+        if pos < 0xFF00 && self.dma.borrow().is_blocking() {
+            return Ok(0xFF);
+        }
+        self.read_byte(pos)
+    }
+
+    /// Write a byte as the CPU would, writes below 0xFF00 are lost
+    /// during OAM DMA, see cpu_read.
+    pub fn cpu_write(&self, pos: u16, byte: u8) -> Result<(), io::Error> {
+        if pos < 0xFF00 && self.dma.borrow().is_blocking() {
+            return Ok(());
+        }
+        self.write_byte(pos, byte).map(|_| ())
+    }
+
+    fn request_interrupt(&self, kind: InterruptKind) {
+        self.io.borrow_mut()[(IF_ADDR - MemSectors::IOReg.val()) as usize] |= kind.bit();
+    }
+
+    /// Clocks at the fixed 4.19MHz rate since power on.
+    pub fn clocks(&self) -> u64 {
+        self.clocks.get()
+    }
+
+    pub fn is_double_speed(&self) -> bool {
+        self.speed.borrow().is_double()
+    }
+
+    /// Start a speed switch if KEY1 is armed, see SpeedSwitch::switch.
+    /// The CPU is stalled until it is done.
+    pub fn speed_switch(&self) -> bool {
+        self.speed.borrow_mut().switch().is_some()
+    }
+
+    /// The ROM bank mapped at pos, or None if pos is not in ROM.
+    pub fn rom_bank(&self, pos: u16) -> Option<u16> {
+        match MemSectors::of(pos) {
+            MemSectors::RomBank0 | MemSectors::RomBank1 => Some(self.mapper.borrow().rom_bank(pos)),
+            _ => None,
+        }
     }
 
     pub fn print_tile(&self, pos: u16) {
@@ -66,6 +380,68 @@ impl MemoryMap {
     }
 }
 
+impl Bus for MemoryMap {
+    fn read(&mut self, addr: u16) -> Result<u8, io::Error> {
+        Bus::read(&mut &*self, addr)
+    }
+
+    fn write(&mut self, addr: u16, v: u8) -> Result<(), io::Error> {
+        Bus::write(&mut &*self, addr, v)
+    }
+
+    fn tick(&mut self) {
+        Bus::tick(&mut &*self)
+    }
+
+    fn peek(&self, addr: u16) -> Result<u8, io::Error> {
+        Bus::peek(&self, addr)
+    }
+
+    fn rom_bank(&self, addr: u16) -> Option<u16> {
+        Bus::rom_bank(&self, addr)
+    }
+
+    fn speed_switch(&mut self) -> bool {
+        Bus::speed_switch(&mut &*self)
+    }
+
+    fn stalled(&self) -> bool {
+        Bus::stalled(&self)
+    }
+}
+
+/// The map is shared through RefCells, so a plain reference is
+/// enough to drive it.
+impl Bus for &MemoryMap {
+    fn read(&mut self, addr: u16) -> Result<u8, io::Error> {
+        self.cpu_read(addr)
+    }
+
+    fn write(&mut self, addr: u16, v: u8) -> Result<(), io::Error> {
+        self.cpu_write(addr, v)
+    }
+
+    fn tick(&mut self) {
+        MemoryMap::tick(self)
+    }
+
+    fn peek(&self, addr: u16) -> Result<u8, io::Error> {
+        self.read_byte(addr)
+    }
+
+    fn rom_bank(&self, addr: u16) -> Option<u16> {
+        MemoryMap::rom_bank(self, addr)
+    }
+
+    fn speed_switch(&mut self) -> bool {
+        MemoryMap::speed_switch(self)
+    }
+
+    fn stalled(&self) -> bool {
+        MemoryMap::stalled(self)
+    }
+}
+
 pub enum MemSectors {
     RomBank0,
     RomBank1,
@@ -82,6 +458,33 @@ pub enum MemSectors {
 }
 
 impl MemSectors {
+    /// The region pos falls in.
+    ///
+    /// # Examples
+    /// ```
+    /// use rust_boy::memorymap::MemSectors;
+    /// assert!(matches!(MemSectors::of(0x3FFF), MemSectors::RomBank0));
+    /// assert!(matches!(MemSectors::of(0xE000), MemSectors::EchoRam));
+    /// assert!(matches!(MemSectors::of(0xFEA0), MemSectors::Unused));
+    /// assert!(matches!(MemSectors::of(0xFFFF), MemSectors::IE));
+    /// ```
+    pub fn of(pos: u16) -> MemSectors {
+        match pos {
+            0x0000..=0x3FFF => MemSectors::RomBank0,
+            0x4000..=0x7FFF => MemSectors::RomBank1,
+            0x8000..=0x9FFF => MemSectors::VRam,
+            0xA000..=0xBFFF => MemSectors::ExtRam,
+            0xC000..=0xCFFF => MemSectors::WorkRam0,
+            0xD000..=0xDFFF => MemSectors::WorkRam1,
+            0xE000..=0xFDFF => MemSectors::EchoRam,
+            0xFE00..=0xFE9F => MemSectors::OAM,
+            0xFEA0..=0xFEFF => MemSectors::Unused,
+            0xFF00..=0xFF7F => MemSectors::IOReg,
+            0xFF80..=0xFFFE => MemSectors::HRam,
+            0xFFFF => MemSectors::IE,
+        }
+    }
+
     fn val(&self) -> u16 {
         match *self {
             MemSectors::RomBank0 => 0x0000,
@@ -99,3 +502,265 @@ impl MemSectors {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timer::TMA_ADDR;
+
+    #[test]
+    fn rom_is_read_only() {
+        let memmap = MemoryMap::default();
+        memmap.load_cartridge(&Cartridge {
+            data: vec![0x12; 0x8000],
+        });
+
+        memmap.write_byte(0x0000, 0x34).unwrap();
+        memmap.write_byte(0x7FFF, 0x34).unwrap();
+        assert_eq!(memmap.read_byte(0x0000).unwrap(), 0x12);
+        assert_eq!(memmap.read_byte(0x7FFF).unwrap(), 0x12);
+
+        memmap.patch_byte(0x4000, 0x56).unwrap();
+        assert_eq!(memmap.read_byte(0x4000).unwrap(), 0x56);
+        assert_eq!(memmap.rom_bank(0x4000), Some(1));
+        assert_eq!(memmap.rom_bank(0x8000), None);
+    }
+
+    #[test]
+    fn cartridge_type_picks_the_mapper() {
+        let mut data = vec![0x00; 4 * 0x4000];
+        data[0x0147] = 0x01;
+        data[3 * 0x4000] = 0x33;
+        let memmap = MemoryMap::default();
+        memmap.load_cartridge(&Cartridge { data });
+
+        memmap.write_byte(0x2000, 0x03).unwrap();
+        assert_eq!(memmap.read_byte(0x4000).unwrap(), 0x33);
+        assert_eq!(memmap.rom_bank(0x4000), Some(3));
+    }
+
+    #[test]
+    fn echo_ram_mirrors_work_ram() {
+        let memmap = MemoryMap::default();
+
+        memmap.write_byte(0xC123, 0x42).unwrap();
+        assert_eq!(memmap.read_byte(0xE123).unwrap(), 0x42);
+        memmap.write_byte(0xFDFF, 0x24).unwrap();
+        assert_eq!(memmap.read_byte(0xDDFF).unwrap(), 0x24);
+        // 0xDE00-0xDFFF has no mirror.
+        memmap.write_byte(0xDFFF, 0x99).unwrap();
+        assert_eq!(memmap.read_byte(0xFDFF).unwrap(), 0x24);
+    }
+
+    #[test]
+    fn unusable_area() {
+        let memmap = MemoryMap::default();
+
+        memmap.write_byte(0xFEA0, 0x42).unwrap();
+        memmap.write_byte(0xFEFF, 0x42).unwrap();
+        assert_eq!(memmap.read_byte(0xFEA0).unwrap(), 0x00);
+        assert_eq!(memmap.read_byte(0xFEFF).unwrap(), 0x00);
+        assert_eq!(memmap.read_byte(0xFE9F).unwrap(), 0x00);
+        assert_eq!(memmap.read_byte(P1_ADDR).unwrap(), 0xCF);
+    }
+
+    #[test]
+    fn regions_are_separate() {
+        let memmap = MemoryMap::default();
+        let addrs = [
+            0x8000, 0x9FFF, 0xC000, 0xDFFF, 0xFE00, 0xFE9F, 0xFF80, 0xFFFE, 0xFFFF,
+        ];
+
+        for (i, addr) in addrs.iter().enumerate() {
+            memmap.write_byte(*addr, i as u8 + 1).unwrap();
+        }
+        for (i, addr) in addrs.iter().enumerate() {
+            assert_eq!(memmap.read_byte(*addr).unwrap(), i as u8 + 1);
+        }
+        // No cartridge RAM on a ROM only cart.
+        memmap.write_byte(0xA000, 0x42).unwrap();
+        assert_eq!(memmap.read_byte(0xA000).unwrap(), 0xFF);
+    }
+
+    #[test]
+    fn if_upper_bits_read_set() {
+        let memmap = MemoryMap::default();
+
+        assert_eq!(memmap.read_byte(IF_ADDR).unwrap(), 0xE0);
+        memmap.write_byte(IF_ADDR, 0xFF).unwrap();
+        assert_eq!(memmap.read_byte(IF_ADDR).unwrap(), 0xFF);
+        memmap.write_byte(IF_ADDR, 0x04).unwrap();
+        assert_eq!(memmap.read_byte(IF_ADDR).unwrap(), 0xE4);
+    }
+
+    #[test]
+    fn oam_dma_copies_and_blocks() {
+        let memmap = MemoryMap::default();
+        for i in 0..0xA0 {
+            memmap.write_byte(0xC100 + i, i as u8).unwrap();
+        }
+        memmap.cpu_write(0xFF80, 0x12).unwrap();
+
+        memmap.cpu_write(0xFF46, 0xC1).unwrap();
+        assert_eq!(memmap.cpu_read(0xFF46).unwrap(), 0xC1);
+        // The setup cycle.
+        memmap.tick();
+        assert_eq!(memmap.cpu_read(0xC100).unwrap(), 0x00);
+
+        for _ in 0..0xA0 {
+            memmap.tick();
+            assert_eq!(memmap.cpu_read(0xC101).unwrap(), 0xFF);
+            assert_eq!(memmap.cpu_read(0xFF80).unwrap(), 0x12);
+        }
+        memmap.cpu_write(0xC000, 0x34).unwrap();
+        assert_eq!(memmap.read_byte(0xC000).unwrap(), 0x00);
+
+        memmap.tick();
+        assert_eq!(memmap.cpu_read(0xC101).unwrap(), 0x01);
+        for i in 0..0xA0 {
+            assert_eq!(memmap.read_byte(0xFE00 + i).unwrap(), i as u8);
+        }
+
+        // 0xE0 and up copy from WRAM.
+        memmap.write_byte(0xFE05, 0x00).unwrap();
+        memmap.cpu_write(0xFF46, 0xE1).unwrap();
+        for _ in 0..=0xA0 {
+            memmap.tick();
+        }
+        assert_eq!(memmap.read_byte(0xFE05).unwrap(), 0x05);
+    }
+
+    #[test]
+    fn io_is_reachable_during_oam_dma() {
+        let memmap = MemoryMap::default();
+        memmap.cpu_write(0xFF46, 0xC1).unwrap();
+        memmap.tick();
+        memmap.tick();
+        assert_eq!(memmap.cpu_read(0xC000).unwrap(), 0xFF);
+
+        memmap.cpu_write(TMA_ADDR, 0x42).unwrap();
+        assert_eq!(memmap.cpu_read(TMA_ADDR).unwrap(), 0x42);
+        memmap.cpu_write(0xFFFF, 0x1F).unwrap();
+        assert_eq!(memmap.cpu_read(0xFFFF).unwrap(), 0x1F);
+    }
+
+    #[test]
+    fn cgb_vram_and_wram_banks() {
+        let memmap = MemoryMap::cgb();
+        assert_eq!(memmap.read_byte(VBK_ADDR).unwrap(), 0xFE);
+        assert_eq!(memmap.read_byte(SVBK_ADDR).unwrap(), 0xF8);
+
+        memmap.write_byte(0x8000, 0x11).unwrap();
+        memmap.write_byte(VBK_ADDR, 0xFF).unwrap();
+        assert_eq!(memmap.read_byte(VBK_ADDR).unwrap(), 0xFF);
+        assert_eq!(memmap.read_byte(0x8000).unwrap(), 0x00);
+        memmap.write_byte(0x8000, 0x22).unwrap();
+        memmap.write_byte(VBK_ADDR, 0x00).unwrap();
+        assert_eq!(memmap.read_byte(0x8000).unwrap(), 0x11);
+
+        // Banks 0 and 1 are both bank 1 at 0xD000.
+        for bank in 0..8 {
+            memmap.write_byte(SVBK_ADDR, bank).unwrap();
+            memmap.write_byte(0xD000, 0x10 + bank).unwrap();
+        }
+        memmap.write_byte(SVBK_ADDR, 0x00).unwrap();
+        assert_eq!(memmap.read_byte(0xD000).unwrap(), 0x11);
+        for bank in 2..8 {
+            memmap.write_byte(SVBK_ADDR, 0xF8 | bank).unwrap();
+            assert_eq!(memmap.read_byte(SVBK_ADDR).unwrap(), 0xF8 | bank);
+            assert_eq!(memmap.read_byte(0xD000).unwrap(), 0x10 + bank);
+            assert_eq!(memmap.read_byte(0xF000).unwrap(), 0x10 + bank);
+        }
+        memmap.write_byte(0xC000, 0x33).unwrap();
+        assert_eq!(memmap.read_byte(0xE000).unwrap(), 0x33);
+    }
+
+    #[test]
+    fn no_banks_on_dmg() {
+        let memmap = MemoryMap::default();
+        memmap.write_byte(0x8000, 0x11).unwrap();
+        memmap.write_byte(0xD000, 0x22).unwrap();
+        memmap.write_byte(VBK_ADDR, 0x01).unwrap();
+        memmap.write_byte(SVBK_ADDR, 0x02).unwrap();
+        memmap.write_byte(0xFF55, 0x00).unwrap();
+        assert_eq!(memmap.read_byte(VBK_ADDR).unwrap(), 0xFF);
+        assert_eq!(memmap.read_byte(SVBK_ADDR).unwrap(), 0xFF);
+        assert_eq!(memmap.read_byte(0x8000).unwrap(), 0x11);
+        assert_eq!(memmap.read_byte(0xD000).unwrap(), 0x22);
+        assert!(!memmap.stalled());
+    }
+
+    #[test]
+    fn hdma_copies_to_the_vram_bank() {
+        let memmap = MemoryMap::cgb();
+        for i in 0..0x40 {
+            memmap.write_byte(0xC000 + i, i as u8).unwrap();
+        }
+        memmap.write_byte(VBK_ADDR, 0x01).unwrap();
+        memmap.write_byte(0xFF51, 0xC0).unwrap();
+        memmap.write_byte(0xFF52, 0x00).unwrap();
+        memmap.write_byte(0xFF53, 0x01).unwrap();
+        memmap.write_byte(0xFF54, 0x00).unwrap();
+
+        // General purpose, two blocks at once.
+        memmap.write_byte(0xFF55, 0x01).unwrap();
+        assert_eq!(memmap.read_byte(0xFF55).unwrap(), 0xFF);
+        assert_eq!(memmap.read_byte(0x811F).unwrap(), 0x1F);
+        assert_eq!(memmap.read_byte(0x8120).unwrap(), 0x00);
+        for _ in 0..2 * HDMA_BLOCK_MCYCLES {
+            assert!(memmap.stalled());
+            memmap.tick();
+        }
+        assert!(!memmap.stalled());
+
+        // HBlank, one block per HBlank carrying on from there.
+        memmap.write_byte(0xFF55, 0x81).unwrap();
+        assert_eq!(memmap.read_byte(0xFF55).unwrap(), 0x01);
+        assert_eq!(memmap.read_byte(0x8120).unwrap(), 0x00);
+        memmap.hblank();
+        assert!(memmap.stalled());
+        assert_eq!(memmap.read_byte(0x812F).unwrap(), 0x2F);
+        assert_eq!(memmap.read_byte(0x8130).unwrap(), 0x00);
+        memmap.hblank();
+        assert_eq!(memmap.read_byte(0x813F).unwrap(), 0x3F);
+        assert_eq!(memmap.read_byte(0xFF55).unwrap(), 0xFF);
+
+        memmap.write_byte(VBK_ADDR, 0x00).unwrap();
+        assert_eq!(memmap.read_byte(0x811F).unwrap(), 0x00);
+    }
+
+    #[test]
+    fn boot_rom_until_boot_is_written() {
+        let memmap = MemoryMap::default();
+        memmap.load_cartridge(&Cartridge {
+            data: vec![0x12; 0x8000],
+        });
+        memmap.load_boot_rom(vec![0x34; 0x100]);
+        assert!(memmap.boot_rom_mapped());
+        assert_eq!(memmap.read_byte(0x0000).unwrap(), 0x34);
+        assert_eq!(memmap.read_byte(0x00FF).unwrap(), 0x34);
+        assert_eq!(memmap.read_byte(0x0100).unwrap(), 0x12);
+
+        memmap.write_byte(BOOT_ADDR, 0x00).unwrap();
+        assert_eq!(memmap.read_byte(0x0000).unwrap(), 0x34);
+        memmap.write_byte(BOOT_ADDR, 0x01).unwrap();
+        assert!(!memmap.boot_rom_mapped());
+        assert_eq!(memmap.read_byte(0x0000).unwrap(), 0x12);
+        assert_eq!(memmap.read_byte(BOOT_ADDR).unwrap(), 0xFF);
+    }
+
+    #[test]
+    fn cgb_boot_rom_skips_the_header() {
+        let memmap = MemoryMap::for_model(HardwareModel::CGB);
+        memmap.load_cartridge(&Cartridge {
+            data: vec![0x12; 0x8000],
+        });
+        memmap.load_boot_rom(vec![0x34; HardwareModel::CGB.boot_rom_len()]);
+        assert_eq!(memmap.read_byte(0x00FF).unwrap(), 0x34);
+        assert_eq!(memmap.read_byte(0x0100).unwrap(), 0x12);
+        assert_eq!(memmap.read_byte(0x01FF).unwrap(), 0x12);
+        assert_eq!(memmap.read_byte(0x0200).unwrap(), 0x34);
+        assert_eq!(memmap.read_byte(0x08FF).unwrap(), 0x34);
+        assert_eq!(memmap.read_byte(0x0900).unwrap(), 0x12);
+    }
+}
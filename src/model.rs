@@ -0,0 +1,151 @@
+use crate::registers::Registers;
+use std::fmt;
+use std::str::FromStr;
+
+/// The Game Boy being emulated. Without a boot ROM it decides the
+/// state the CPU and I/O registers start in, the state each model's
+/// boot ROM leaves behind.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum HardwareModel {
+    /// The first DMG boot ROM revision.
+    DMG0,
+    #[default]
+    DMG,
+    /// Game Boy Pocket and Light.
+    MGB,
+    SGB,
+    SGB2,
+    CGB,
+    /// A Game Boy Advance running Game Boy software.
+    AGB,
+}
+
+impl HardwareModel {
+    pub fn is_cgb(self) -> bool {
+        matches!(self, HardwareModel::CGB | HardwareModel::AGB)
+    }
+
+    /// The size of the model's boot ROM. The CGB one is 0x900 bytes,
+    /// with a hole at 0x0100-0x01FF where the cartridge header shows
+    /// through.
+    pub fn boot_rom_len(self) -> usize {
+        if self.is_cgb() {
+            0x0900
+        } else {
+            0x0100
+        }
+    }
+
+    /// AF, BC, DE and HL as the boot ROM leaves them, for a cartridge
+    /// with a header checksum that is not zero.
+    ///
+    /// # Examples
+    /// ```
+    /// use rust_boy::model::HardwareModel;
+    /// assert_eq!(HardwareModel::DMG.registers().get_af(), 0x01B0);
+    /// assert_eq!(HardwareModel::CGB.registers().get_af(), 0x1180);
+    /// ```
+    pub fn registers(self) -> Registers {
+        let [a, f, b, c, d, e, h, l] = match self {
+            HardwareModel::DMG0 => [0x01, 0x00, 0xFF, 0x13, 0x00, 0xC1, 0x84, 0x03],
+            HardwareModel::DMG => [0x01, 0xB0, 0x00, 0x13, 0x00, 0xD8, 0x01, 0x4D],
+            HardwareModel::MGB => [0xFF, 0xB0, 0x00, 0x13, 0x00, 0xD8, 0x01, 0x4D],
+            HardwareModel::SGB => [0x01, 0x00, 0x00, 0x14, 0x00, 0x00, 0xC0, 0x60],
+            HardwareModel::SGB2 => [0xFF, 0x00, 0x00, 0x14, 0x00, 0x00, 0xC0, 0x60],
+            HardwareModel::CGB => [0x11, 0x80, 0x00, 0x00, 0xFF, 0x56, 0x00, 0x0D],
+            HardwareModel::AGB => [0x11, 0x00, 0x01, 0x00, 0xFF, 0x56, 0x00, 0x0D],
+        };
+        Registers {
+            a,
+            f,
+            b,
+            c,
+            d,
+            e,
+            h,
+            l,
+        }
+    }
+
+    /// The I/O registers the boot ROM leaves set, in the order to
+    /// write them. DIV is left to count from 0.
+    pub fn io_registers(self) -> Vec<(u16, u8)> {
+        let sgb = matches!(self, HardwareModel::SGB | HardwareModel::SGB2);
+        vec![
+            (0xFF00, 0xCF),
+            (0xFF02, if self.is_cgb() { 0x7F } else { 0x7E }),
+            (0xFF05, 0x00),
+            (0xFF06, 0x00),
+            (0xFF07, 0x00),
+            (0xFF10, 0x80),
+            (0xFF11, 0xBF),
+            (0xFF12, 0xF3),
+            (0xFF14, 0xBF),
+            (0xFF16, 0x3F),
+            (0xFF17, 0x00),
+            (0xFF19, 0xBF),
+            (0xFF1A, 0x7F),
+            (0xFF1B, 0xFF),
+            (0xFF1C, 0x9F),
+            (0xFF1E, 0xBF),
+            (0xFF20, 0xFF),
+            (0xFF21, 0x00),
+            (0xFF22, 0x00),
+            (0xFF23, 0xBF),
+            (0xFF24, 0x77),
+            (0xFF25, 0xF3),
+            (0xFF26, if sgb { 0xF0 } else { 0xF1 }),
+            (0xFF40, 0x91),
+            (
+                0xFF41,
+                if self == HardwareModel::DMG0 {
+                    0x81
+                } else {
+                    0x85
+                },
+            ),
+            (0xFF42, 0x00),
+            (0xFF43, 0x00),
+            (0xFF44, 0x90),
+            (0xFF45, 0x00),
+            (0xFF47, 0xFC),
+            (0xFF48, 0xFF),
+            (0xFF49, 0xFF),
+            (0xFF4A, 0x00),
+            (0xFF4B, 0x00),
+            (0xFF50, 0x01),
+            (0xFFFF, 0x00),
+        ]
+    }
+}
+
+impl FromStr for HardwareModel {
+    type Err = String;
+
+    /// Parse a model name, ignoring case.
+    ///
+    /// # Examples
+    /// ```
+    /// use rust_boy::model::HardwareModel;
+    /// assert_eq!("sgb2".parse(), Ok(HardwareModel::SGB2));
+    /// assert!("gba".parse::<HardwareModel>().is_err());
+    /// ```
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_uppercase().as_str() {
+            "DMG0" => Ok(HardwareModel::DMG0),
+            "DMG" => Ok(HardwareModel::DMG),
+            "MGB" => Ok(HardwareModel::MGB),
+            "SGB" => Ok(HardwareModel::SGB),
+            "SGB2" => Ok(HardwareModel::SGB2),
+            "CGB" => Ok(HardwareModel::CGB),
+            "AGB" => Ok(HardwareModel::AGB),
+            _ => Err(format!("Unknown hardware model: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for HardwareModel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
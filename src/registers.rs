@@ -1,4 +1,6 @@
-#[derive(Debug)]
+use crate::alu::{self, FLAG_C, FLAG_H};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum StdRegN {
     A,
     B,
@@ -11,7 +13,7 @@ pub enum StdRegN {
     N,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum StdReg {
     A,
     B,
@@ -23,7 +25,32 @@ pub enum StdReg {
     HL,
 }
 
-#[derive(Debug)]
+impl StdReg {
+    /// Decode the operand encoded in the low three bits
+    /// of an opcode. B, C, D, E, H, L, (HL), A.
+    ///
+    /// # Examples
+    /// ```
+    /// use rust_boy::registers::StdReg;
+    /// assert!(matches!(StdReg::from_bits(0x00), StdReg::B));
+    /// assert!(matches!(StdReg::from_bits(0x7E), StdReg::HL));
+    /// assert!(matches!(StdReg::from_bits(0xCF), StdReg::A));
+    /// ```
+    pub fn from_bits(bits: u8) -> Self {
+        match bits & 0b111 {
+            0 => StdReg::B,
+            1 => StdReg::C,
+            2 => StdReg::D,
+            3 => StdReg::E,
+            4 => StdReg::H,
+            5 => StdReg::L,
+            6 => StdReg::HL,
+            _ => StdReg::A,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum IncDecReg {
     A,
     B,
@@ -39,7 +66,48 @@ pub enum IncDecReg {
     MemHL,
 }
 
-#[derive(Debug)]
+impl From<StdReg> for StdRegN {
+    fn from(reg: StdReg) -> Self {
+        match reg {
+            StdReg::A => StdRegN::A,
+            StdReg::B => StdRegN::B,
+            StdReg::C => StdRegN::C,
+            StdReg::D => StdRegN::D,
+            StdReg::E => StdRegN::E,
+            StdReg::H => StdRegN::H,
+            StdReg::L => StdRegN::L,
+            StdReg::HL => StdRegN::HL,
+        }
+    }
+}
+
+impl From<StdReg> for IncDecReg {
+    fn from(reg: StdReg) -> Self {
+        match reg {
+            StdReg::A => IncDecReg::A,
+            StdReg::B => IncDecReg::B,
+            StdReg::C => IncDecReg::C,
+            StdReg::D => IncDecReg::D,
+            StdReg::E => IncDecReg::E,
+            StdReg::H => IncDecReg::H,
+            StdReg::L => IncDecReg::L,
+            StdReg::HL => IncDecReg::MemHL,
+        }
+    }
+}
+
+impl From<LoadRegnnn> for IncDecReg {
+    fn from(reg: LoadRegnnn) -> Self {
+        match reg {
+            LoadRegnnn::BC => IncDecReg::BC,
+            LoadRegnnn::DE => IncDecReg::DE,
+            LoadRegnnn::HL => IncDecReg::HL,
+            LoadRegnnn::SP => IncDecReg::SP,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum LoadReg {
     A,
     B,
@@ -55,7 +123,7 @@ pub enum LoadReg {
     N,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum LoadRegnnn {
     BC,
     DE,
@@ -63,7 +131,15 @@ pub enum LoadRegnnn {
     SP,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StackReg {
+    BC,
+    DE,
+    HL,
+    AF,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum FlagCond {
     NZ,
     Z,
@@ -105,7 +181,7 @@ impl FlagCond {
     }
 }
 
-#[derive(Default)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
 pub struct Registers {
     pub a: u8,
     pub f: u8,
@@ -118,18 +194,6 @@ pub struct Registers {
 }
 
 impl Registers {
-    pub fn dmg0() -> Self {
-        Self {
-            a: 0x01,
-            f: 0xB0,
-            b: 0x00,
-            c: 0x13,
-            d: 0x00,
-            e: 0xD8,
-            h: 0x01,
-            l: 0x4D,
-        }
-    }
     pub fn get_af(&self) -> u16 {
         ((self.a as u16) << 8) | self.f as u16
     }
@@ -294,7 +358,52 @@ pub fn clear_flag(flag: u8, pos: u32) -> u8 {
 /// assert_eq!(will_half_carry(v1, v2), true);
 /// ```
 pub fn will_half_carry(v1: u8, v2: u8) -> bool {
-    (v1 & 0xf).wrapping_add(v2 & 0xf) & 0x10 == 0x10
+    alu::add(v1, v2).1 & FLAG_H != 0
+}
+
+/// Returns true if the half carry bit will
+/// be set when adding the 16 bit values v1 and v2.
+/// The half carry is taken from bit 11.
+///
+/// # Examples
+///
+/// ```
+/// use rust_boy::registers::will_half_carry_u16;
+///
+/// let v1 = 0x0800;
+/// let v2 = 0x0800;
+/// assert_eq!(will_half_carry_u16(v1, v2), true);
+///
+/// let v1 = 0x00FF;
+/// let v2 = 0x0001;
+/// assert_eq!(will_half_carry_u16(v1, v2), false);
+///
+/// let v1 = 0xF000;
+/// let v2 = 0x1000;
+/// assert_eq!(will_half_carry_u16(v1, v2), false);
+/// ```
+pub fn will_half_carry_u16(v1: u16, v2: u16) -> bool {
+    alu::add16(v1, v2, 0x00).1 & FLAG_H != 0
+}
+
+/// Returns true if the carry bit will be set
+/// when adding the 16 bit values v1 and v2.
+///
+/// # Examples
+///
+/// ```
+/// use rust_boy::registers::will_carry_u16;
+///
+/// let v1 = 0x8000;
+/// let v2 = 0x8000;
+/// assert_eq!(will_carry_u16(v1, v2), true);
+///
+/// let v1 = 0xFFFE;
+/// let v2 = 0x0001;
+/// assert_eq!(will_carry_u16(v1, v2), false);
+/// ```
+pub fn will_carry_u16(v1: u16, v2: u16) -> bool {
+    alu::add16(v1, v2, 0x00).1 & FLAG_C != 0
 }
 
 /// Returns true if the half carry bit will
@@ -314,7 +423,7 @@ pub fn will_half_carry(v1: u8, v2: u8) -> bool {
 /// assert_eq!(will_half_borrow(v1, v2), false);
 /// ```
 pub fn will_half_borrow(v1: u8, v2: u8) -> bool {
-    (v1 & 0xf).wrapping_sub(v2 & 0xf) & 0x10 == 0x10
+    alu::sub(v1, v2).1 & FLAG_H != 0
 }
 
 /// Returns true if the carry bit will be set
@@ -325,16 +434,20 @@ pub fn will_half_borrow(v1: u8, v2: u8) -> bool {
 /// ```
 /// use rust_boy::registers::will_carry;
 ///
-/// let v1 = 0b00000001;
-/// let v2 = 0b00000001;
+/// let v1 = 0b1000_0000;
+/// let v2 = 0b1000_0000;
 /// assert_eq!(will_carry(v1, v2), true);
 ///
-/// let v1 = 0b00000000;
-/// let v2 = 0b00000000;
+/// let v1 = 0b0000_0001;
+/// let v2 = 0b0000_0001;
 /// assert_eq!(will_carry(v1, v2), false);
+///
+/// let v1 = 0b1111_1111;
+/// let v2 = 0b0000_0001;
+/// assert_eq!(will_carry(v1, v2), true);
 /// ```
 pub fn will_carry(v1: u8, v2: u8) -> bool {
-    ((v1 & 0x01) + (v2 & 0x01)) & 0x02 == 0x02
+    alu::add(v1, v2).1 & FLAG_C != 0
 }
 
 /// Returns true if the carry bit will be set
@@ -354,7 +467,7 @@ pub fn will_carry(v1: u8, v2: u8) -> bool {
 /// assert_eq!(will_borrow(v1, v2), false);
 /// ```
 pub fn will_borrow(v1: u8, v2: u8) -> bool {
-   v1 < v2 
+    alu::sub(v1, v2).1 & FLAG_C != 0
 }
 
 pub fn dec(value: u8, amt: u8) -> (u8, bool) {
@@ -0,0 +1,173 @@
+use crate::memorymap::MemoryMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// How long cartridge RAM may stay dirty before it is written out.
+pub const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The .sav file that keeps a battery backed cart's RAM, and clock,
+/// between sessions.
+///
+/// Load it once the cartridge is in, call update as the emulator runs
+/// so RAM the game writes reaches the disk soon after, and flush on
+/// exit. The file is written to a temporary then renamed over the
+/// old one, so a crash while saving leaves the last save whole.
+pub struct SaveFile {
+    path: PathBuf,
+    interval: Duration,
+    /// When cartridge RAM was first written since the last flush.
+    dirty_since: Option<Instant>,
+}
+
+impl SaveFile {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            interval: FLUSH_INTERVAL,
+            dirty_since: None,
+        }
+    }
+
+    /// The save for the ROM at rom_path, the same path with a .sav
+    /// extension.
+    ///
+    /// # Examples
+    /// ```
+    /// use rust_boy::save::SaveFile;
+    /// let save = SaveFile::for_rom("roms/tetris.gb");
+    /// assert_eq!(save.path().to_str(), Some("roms/tetris.sav"));
+    /// ```
+    pub fn for_rom<P: AsRef<Path>>(rom_path: P) -> Self {
+        Self::new(rom_path.as_ref().with_extension("sav"))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn set_interval(&mut self, interval: Duration) {
+        self.interval = interval;
+    }
+
+    /// Load the save into the cartridge in memmap. Ok(false) if the
+    /// cart has no battery or there is no save yet.
+    pub fn load(&self, memmap: &MemoryMap) -> io::Result<bool> {
+        if memmap.save_data().is_none() {
+            return Ok(false);
+        }
+        match fs::read(&self.path) {
+            Ok(data) => {
+                memmap.load_save_data(&data);
+                Ok(true)
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Flush once cartridge RAM has been dirty for the interval. Call
+    /// it often, it does nothing until RAM is written.
+    pub fn update(&mut self, memmap: &MemoryMap) -> io::Result<()> {
+        if memmap.take_ram_dirty() {
+            self.dirty_since.get_or_insert_with(Instant::now);
+        }
+        match self.dirty_since {
+            Some(since) if since.elapsed() >= self.interval => self.flush(memmap),
+            _ => Ok(()),
+        }
+    }
+
+    /// Write the save now, whether or not RAM is dirty. Does nothing
+    /// for carts without a battery.
+    pub fn flush(&mut self, memmap: &MemoryMap) -> io::Result<()> {
+        memmap.take_ram_dirty();
+        self.dirty_since = None;
+        let Some(data) = memmap.save_data() else {
+            return Ok(());
+        };
+        let tmp = self.path.with_extension("sav.tmp");
+        fs::write(&tmp, data)?;
+        fs::rename(&tmp, &self.path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cartridge::Cartridge;
+
+    fn cart(kind: u8) -> MemoryMap {
+        let mut data = vec![0x00; 0x8000];
+        data[0x0147] = kind;
+        data[0x0149] = 0x02;
+        let memmap = MemoryMap::default();
+        memmap.load_cartridge(&Cartridge { data });
+        memmap.write_byte(0x0000, 0x0A).unwrap();
+        memmap
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("rust_boy_{}_{}.sav", name, std::process::id()))
+    }
+
+    #[test]
+    fn dirty_ram_is_flushed_after_the_interval() {
+        let path = temp_path("flush");
+        let memmap = cart(0x03);
+        let mut save = SaveFile::new(&path);
+        assert!(!save.load(&memmap).unwrap());
+
+        // Nothing written, nothing saved.
+        save.set_interval(Duration::ZERO);
+        save.update(&memmap).unwrap();
+        assert!(!path.exists());
+
+        save.set_interval(Duration::from_secs(3600));
+        memmap.write_byte(0xA000, 0x42).unwrap();
+        save.update(&memmap).unwrap();
+        assert!(!path.exists());
+        save.set_interval(Duration::ZERO);
+        save.update(&memmap).unwrap();
+        assert_eq!(fs::read(&path).unwrap()[0], 0x42);
+
+        let loaded = cart(0x03);
+        assert!(save.load(&loaded).unwrap());
+        assert_eq!(loaded.read_byte(0xA000).unwrap(), 0x42);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn no_battery_no_save() {
+        let path = temp_path("no_battery");
+        let memmap = cart(0x02);
+        memmap.write_byte(0xA000, 0x42).unwrap();
+        assert!(!memmap.take_ram_dirty());
+        let mut save = SaveFile::new(&path);
+        save.set_interval(Duration::ZERO);
+        save.update(&memmap).unwrap();
+        save.flush(&memmap).unwrap();
+        assert!(!path.exists());
+        assert!(!save.load(&memmap).unwrap());
+    }
+
+    #[test]
+    fn disabled_ram_is_not_dirty() {
+        let memmap = cart(0x03);
+        memmap.write_byte(0x0000, 0x00).unwrap();
+        memmap.write_byte(0xA000, 0x42).unwrap();
+        assert!(!memmap.take_ram_dirty());
+        memmap.write_byte(0x0000, 0x0A).unwrap();
+        memmap.write_byte(0xA000, 0x42).unwrap();
+        assert!(memmap.take_ram_dirty());
+    }
+
+    #[test]
+    fn rom_ram_battery_is_saved() {
+        let memmap = cart(0x09);
+        memmap.write_byte(0xA000, 0x42).unwrap();
+        assert!(memmap.take_ram_dirty());
+        assert_eq!(memmap.save_data().unwrap()[0], 0x42);
+    }
+}
@@ -0,0 +1,133 @@
+pub const KEY1_ADDR: u16 = 0xFF4D;
+
+/// M-cycles the CPU sits idle after STOP while the clock settles.
+pub const SWITCH_MCYCLES: u16 = 2050;
+
+/// The CGB speed switch, KEY1.
+///
+/// In double speed the CPU, and the timer that runs off its clock,
+/// get twice as many cycles as the PPU and APU. A switch is armed by
+/// setting bit 0 of KEY1 and happens on the next STOP. On a DMG
+/// there is no KEY1, it reads 0xFF and writes are ignored.
+#[derive(Debug, Default)]
+pub struct SpeedSwitch {
+    cgb: bool,
+    double: bool,
+    armed: bool,
+    /// M-cycles left before the clocks are running again.
+    pause: u16,
+}
+
+impl SpeedSwitch {
+    pub fn cgb() -> Self {
+        Self {
+            cgb: true,
+            ..Self::default()
+        }
+    }
+
+    /// True for KEY1 at 0xFF4D.
+    ///
+    /// # Examples
+    /// ```
+    /// use rust_boy::speed::SpeedSwitch;
+    /// assert!(SpeedSwitch::contains(0xFF4D));
+    /// assert!(!SpeedSwitch::contains(0xFF4C));
+    /// ```
+    pub fn contains(addr: u16) -> bool {
+        addr == KEY1_ADDR
+    }
+
+    pub fn read(&self, _addr: u16) -> u8 {
+        if self.cgb {
+            ((self.double as u8) << 7) | 0x7E | self.armed as u8
+        } else {
+            0xFF
+        }
+    }
+
+    pub fn write(&mut self, _addr: u16, v: u8) {
+        if self.cgb {
+            self.armed = v & 0x01 != 0;
+        }
+    }
+
+    pub fn is_double(&self) -> bool {
+        self.double
+    }
+
+    /// Switch speed if KEY1 is armed, called when STOP is executed.
+    /// Returns the M-cycles the CPU is paused for.
+    pub fn switch(&mut self) -> Option<u16> {
+        if !self.armed {
+            return None;
+        }
+        self.armed = false;
+        self.double = !self.double;
+        self.pause = SWITCH_MCYCLES;
+        Some(SWITCH_MCYCLES)
+    }
+
+    /// True while a switch has the CPU clock stopped.
+    pub fn is_paused(&self) -> bool {
+        self.pause > 0
+    }
+
+    /// Advance by one CPU M-cycle. Returns the number of clocks at
+    /// the fixed 4.19MHz rate that passed, and whether the CPU clock
+    /// is running, it is stopped for the length of a switch.
+    pub fn tick(&mut self) -> (u64, bool) {
+        let clocks = if self.double { 2 } else { 4 };
+        if self.pause > 0 {
+            self.pause -= 1;
+            (clocks, false)
+        } else {
+            (clocks, true)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn no_key1_on_dmg() {
+        let mut speed = SpeedSwitch::default();
+        speed.write(KEY1_ADDR, 0x01);
+        assert_eq!(speed.read(KEY1_ADDR), 0xFF);
+        assert_eq!(speed.switch(), None);
+        assert_eq!(speed.tick(), (4, true));
+    }
+
+    #[test]
+    fn switch_needs_arming() {
+        let mut speed = SpeedSwitch::cgb();
+        assert_eq!(speed.read(KEY1_ADDR), 0x7E);
+        assert_eq!(speed.switch(), None);
+
+        speed.write(KEY1_ADDR, 0x01);
+        assert_eq!(speed.read(KEY1_ADDR), 0x7F);
+        assert_eq!(speed.switch(), Some(SWITCH_MCYCLES));
+        assert!(speed.is_double());
+        assert_eq!(speed.read(KEY1_ADDR), 0xFE);
+        assert_eq!(speed.switch(), None);
+    }
+
+    #[test]
+    fn clock_stops_during_switch() {
+        let mut speed = SpeedSwitch::cgb();
+        speed.write(KEY1_ADDR, 0x01);
+        speed.switch();
+
+        for _ in 0..SWITCH_MCYCLES {
+            assert_eq!(speed.tick(), (2, false));
+        }
+        assert_eq!(speed.tick(), (2, true));
+
+        speed.write(KEY1_ADDR, 0x01);
+        speed.switch();
+        assert!(!speed.is_double());
+        assert_eq!(speed.tick(), (4, false));
+    }
+}
@@ -36,11 +36,9 @@ impl std::fmt::Display for Tile {
 
                 bit_pos += 1;
             }
-            print!("{:2X} {:2X} ", self.data[i], self.data[i + 1]);
             row.push('\n');
         }
 
-        println!();
         write!(f, "{}", row)
     }
 }
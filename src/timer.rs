@@ -0,0 +1,234 @@
+pub const DIV_ADDR: u16 = 0xFF04;
+pub const TIMA_ADDR: u16 = 0xFF05;
+pub const TMA_ADDR: u16 = 0xFF06;
+pub const TAC_ADDR: u16 = 0xFF07;
+
+/// The divider and timer registers, DIV, TIMA, TMA and TAC.
+///
+/// DIV is the upper byte of a 16 bit counter that advances every
+/// clock. TIMA is incremented on the falling edge of the counter
+/// bit selected by TAC, which is why writes to DIV and TAC can
+/// also increment it.
+#[derive(Debug)]
+pub struct Timer {
+    counter: u16,
+    tima: u8,
+    tma: u8,
+    tac: u8,
+    /// TIMA overflowed last cycle and reads 0x00, TMA is loaded
+    /// and the interrupt requested on the next cycle.
+    overflow: bool,
+    /// TIMA was reloaded from TMA this cycle.
+    reloading: bool,
+}
+
+impl Default for Timer {
+    fn default() -> Self {
+        Self {
+            counter: 0x0000,
+            tima: 0x00,
+            tma: 0x00,
+            tac: 0xF8,
+            overflow: false,
+            reloading: false,
+        }
+    }
+}
+
+impl Timer {
+    /// True for the timer registers at 0xFF04-0xFF07.
+    ///
+    /// # Examples
+    /// ```
+    /// use rust_boy::timer::Timer;
+    /// assert!(Timer::contains(0xFF04));
+    /// assert!(Timer::contains(0xFF07));
+    /// assert!(!Timer::contains(0xFF0F));
+    /// ```
+    pub fn contains(addr: u16) -> bool {
+        (DIV_ADDR..=TAC_ADDR).contains(&addr)
+    }
+
+    /// Advance by one M-cycle. Returns true when the timer
+    /// interrupt should be requested.
+    pub fn tick(&mut self) -> bool {
+        let mut interrupt = false;
+
+        self.reloading = false;
+        if self.overflow {
+            self.overflow = false;
+            self.reloading = true;
+            self.tima = self.tma;
+            interrupt = true;
+        }
+
+        let signal = self.signal();
+        self.counter = self.counter.wrapping_add(4);
+        if signal && !self.signal() {
+            self.increment();
+        }
+
+        interrupt
+    }
+
+    pub fn read(&self, addr: u16) -> u8 {
+        match addr {
+            DIV_ADDR => (self.counter >> 8) as u8,
+            TIMA_ADDR => self.tima,
+            TMA_ADDR => self.tma,
+            TAC_ADDR => self.tac,
+            _ => 0xFF,
+        }
+    }
+
+    pub fn write(&mut self, addr: u16, v: u8) {
+        match addr {
+            DIV_ADDR => {
+                let signal = self.signal();
+                self.counter = 0x0000;
+                if signal {
+                    self.increment();
+                }
+            }
+            // Ignored on the reload cycle, cancels a pending reload.
+            TIMA_ADDR if self.reloading => {}
+            TIMA_ADDR => {
+                self.tima = v;
+                self.overflow = false;
+            }
+            TMA_ADDR => {
+                self.tma = v;
+                if self.reloading {
+                    self.tima = v;
+                }
+            }
+            TAC_ADDR => {
+                let signal = self.signal();
+                self.tac = v | 0xF8;
+                if signal && !self.signal() {
+                    self.increment();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// The counter bit TIMA counts, ANDed with the enable bit.
+    fn signal(&self) -> bool {
+        let bit = match self.tac & 0b11 {
+            0b00 => 9,
+            0b01 => 3,
+            0b10 => 5,
+            _ => 7,
+        };
+        self.tac & 0b100 != 0 && self.counter & (1 << bit) != 0
+    }
+
+    fn increment(&mut self) {
+        let (tima, overflow) = self.tima.overflowing_add(1);
+        self.tima = tima;
+        self.overflow = overflow;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn div_counts_every_64_cycles() {
+        let mut timer = Timer::default();
+        for _ in 0..63 {
+            timer.tick();
+        }
+        assert_eq!(timer.read(DIV_ADDR), 0x00);
+        timer.tick();
+        assert_eq!(timer.read(DIV_ADDR), 0x01);
+
+        timer.write(DIV_ADDR, 0x55);
+        assert_eq!(timer.read(DIV_ADDR), 0x00);
+    }
+
+    #[test]
+    fn tima_frequencies() {
+        for (tac, period) in [(0b100, 256), (0b101, 4), (0b110, 16), (0b111, 64)] {
+            let mut timer = Timer::default();
+            timer.write(TAC_ADDR, tac);
+            for _ in 0..period - 1 {
+                timer.tick();
+            }
+            assert_eq!(timer.read(TIMA_ADDR), 0x00);
+            timer.tick();
+            assert_eq!(timer.read(TIMA_ADDR), 0x01);
+        }
+    }
+
+    #[test]
+    fn tima_disabled() {
+        let mut timer = Timer::default();
+        timer.write(TAC_ADDR, 0b001);
+        for _ in 0..64 {
+            assert!(!timer.tick());
+        }
+        assert_eq!(timer.read(TIMA_ADDR), 0x00);
+        assert_eq!(timer.read(TAC_ADDR), 0xF9);
+    }
+
+    #[test]
+    fn overflow_reloads_a_cycle_late() {
+        let mut timer = Timer::default();
+        timer.write(TMA_ADDR, 0x42);
+        timer.write(TIMA_ADDR, 0xFF);
+        timer.write(TAC_ADDR, 0b101);
+
+        for _ in 0..4 {
+            assert!(!timer.tick());
+        }
+        assert_eq!(timer.read(TIMA_ADDR), 0x00);
+        assert!(timer.tick());
+        assert_eq!(timer.read(TIMA_ADDR), 0x42);
+    }
+
+    #[test]
+    fn write_cancels_pending_reload() {
+        let mut timer = Timer::default();
+        timer.write(TMA_ADDR, 0x42);
+        timer.write(TIMA_ADDR, 0xFF);
+        timer.write(TAC_ADDR, 0b101);
+        for _ in 0..4 {
+            timer.tick();
+        }
+
+        timer.write(TIMA_ADDR, 0x10);
+        assert!(!timer.tick());
+        assert_eq!(timer.read(TIMA_ADDR), 0x10);
+    }
+
+    #[test]
+    fn writes_on_the_reload_cycle() {
+        let mut timer = Timer::default();
+        timer.write(TMA_ADDR, 0x42);
+        timer.write(TIMA_ADDR, 0xFF);
+        timer.write(TAC_ADDR, 0b101);
+        for _ in 0..5 {
+            timer.tick();
+        }
+
+        timer.write(TIMA_ADDR, 0x10);
+        assert_eq!(timer.read(TIMA_ADDR), 0x42);
+        timer.write(TMA_ADDR, 0x24);
+        assert_eq!(timer.read(TIMA_ADDR), 0x24);
+    }
+
+    #[test]
+    fn div_reset_falling_edge() {
+        let mut timer = Timer::default();
+        timer.write(TAC_ADDR, 0b101);
+        for _ in 0..2 {
+            timer.tick();
+        }
+        // Bit 3 of the counter is set, clearing it is a falling edge.
+        timer.write(DIV_ADDR, 0x00);
+        assert_eq!(timer.read(TIMA_ADDR), 0x01);
+    }
+}
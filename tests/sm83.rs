@@ -0,0 +1,272 @@
+// Runs the SingleStepTests sm83 vectors, one JSON file of cases per
+// opcode, through Cpu::step.
+//
+// The full set of vectors is not checked in. Point SM83_TESTS at a
+// checkout of https://github.com/SingleStepTests/sm83 (the directory
+// holding the .json files), or place them in tests/sm83/, and run
+// with --ignored. A few cases in the same format are kept in
+// tests/sm83_sample/ so the runner itself is always exercised.
+//
+// Run with --nocapture to see the per-opcode table.
+
+use rust_boy::bus::Bus;
+use rust_boy::cpu::{Cpu, CpuState};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const IE_ADDR: u16 = 0xFFFF;
+
+struct State {
+    cpu: CpuState,
+    ie: Option<u8>,
+    ram: Vec<(u16, u8)>,
+}
+
+/// A bus access, the address, the data and 'r' or 'w'.
+type Access = (u16, u8, char);
+
+/// Flat RAM that records every access the CPU makes.
+struct RecordingBus {
+    mem: Box<[u8; 0x10000]>,
+    accesses: Vec<Access>,
+}
+
+impl Bus for RecordingBus {
+    fn read(&mut self, addr: u16) -> Result<u8, io::Error> {
+        let v = self.mem[addr as usize];
+        self.accesses.push((addr, v, 'r'));
+        Ok(v)
+    }
+
+    fn write(&mut self, addr: u16, v: u8) -> Result<(), io::Error> {
+        self.mem[addr as usize] = v;
+        self.accesses.push((addr, v, 'w'));
+        Ok(())
+    }
+
+    fn tick(&mut self) {}
+
+    fn peek(&self, addr: u16) -> Result<u8, io::Error> {
+        Ok(self.mem[addr as usize])
+    }
+}
+
+/// The reads and writes in a case's cycle list, internal cycles
+/// are left out.
+fn parse_accesses(cycles: &[Value]) -> Vec<Access> {
+    cycles
+        .iter()
+        .filter_map(|cycle| {
+            let kind = cycle[2].as_str()?;
+            let kind = if kind.contains('r') {
+                'r'
+            } else if kind.contains('w') {
+                'w'
+            } else {
+                return None;
+            };
+            Some((cycle[0].as_u64()? as u16, cycle[1].as_u64()? as u8, kind))
+        })
+        .collect()
+}
+
+impl State {
+    fn parse(v: &Value) -> State {
+        let byte = |k: &str| v[k].as_u64().unwrap_or(0) as u8;
+        let word = |k: &str| v[k].as_u64().unwrap_or(0) as u16;
+        let ram = v["ram"]
+            .as_array()
+            .map(|ram| {
+                ram.iter()
+                    .map(|pair| {
+                        (
+                            pair[0].as_u64().unwrap() as u16,
+                            pair[1].as_u64().unwrap() as u8,
+                        )
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        State {
+            cpu: CpuState {
+                a: byte("a"),
+                b: byte("b"),
+                c: byte("c"),
+                d: byte("d"),
+                e: byte("e"),
+                f: byte("f"),
+                h: byte("h"),
+                l: byte("l"),
+                pc: word("pc"),
+                sp: word("sp"),
+                ime: byte("ime") != 0,
+                ..CpuState::default()
+            },
+            ie: v["ie"].as_u64().map(|ie| ie as u8),
+            ram,
+        }
+    }
+}
+
+/// Run one case, returning a description of the first mismatch.
+///
+/// The vectors model the SM83 fetch/execute overlap: the opcode has
+/// already been fetched, so PC starts one past it, and the last bus
+/// cycle of every case fetches the following opcode. PC is moved back
+/// a byte to run the case here, and the cycle counts still line up.
+/// The bus accesses line up once our opcode fetch and their fetch of
+/// the next opcode are dropped.
+fn run_case(case: &Value) -> Result<(), String> {
+    let initial = State::parse(&case["initial"]);
+    let expected = State::parse(&case["final"]);
+    let cycles = case["cycles"].as_array().cloned().unwrap_or_default();
+    let mut expected_accesses = parse_accesses(&cycles);
+    expected_accesses.pop();
+
+    let mut bus = RecordingBus {
+        mem: Box::new([0; 0x10000]),
+        accesses: Vec::new(),
+    };
+    for (addr, v) in &initial.ram {
+        bus.mem[*addr as usize] = *v;
+    }
+    if let Some(ie) = initial.ie {
+        bus.mem[IE_ADDR as usize] = ie;
+    }
+
+    let mut cpu = Cpu::new(bus);
+    cpu.set_state(&CpuState {
+        pc: initial.cpu.pc.wrapping_sub(1),
+        ..initial.cpu
+    });
+
+    cpu.step().map_err(|e| e.to_string())?;
+
+    let got = cpu.state();
+    let want = expected.cpu;
+    let mut errors = Vec::new();
+    let mut check = |name: &str, got: u16, want: u16| {
+        if got != want {
+            errors.push(format!("{} {:04X} != {:04X}", name, got, want));
+        }
+    };
+    check("A", got.a as u16, want.a as u16);
+    check("B", got.b as u16, want.b as u16);
+    check("C", got.c as u16, want.c as u16);
+    check("D", got.d as u16, want.d as u16);
+    check("E", got.e as u16, want.e as u16);
+    check("F", got.f as u16, want.f as u16);
+    check("H", got.h as u16, want.h as u16);
+    check("L", got.l as u16, want.l as u16);
+    check("PC", got.pc.wrapping_add(1), want.pc);
+    check("SP", got.sp, want.sp);
+    check("IME", got.ime as u16, want.ime as u16);
+    check("cycles", cpu.mcycles() as u16, cycles.len() as u16);
+    for (addr, want) in &expected.ram {
+        let got = cpu.bus().mem[*addr as usize];
+        check(&format!("[{:04X}]", addr), got as u16, *want as u16);
+    }
+    let accesses = cpu.bus().accesses.get(1..).unwrap_or_default();
+    if accesses != expected_accesses {
+        errors.push(format!("bus {:X?} != {:X?}", accesses, expected_accesses));
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors.join(", "))
+    }
+}
+
+fn vector_dir() -> Option<PathBuf> {
+    let dir = env::var_os("SM83_TESTS")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/sm83"));
+    dir.is_dir().then_some(dir)
+}
+
+fn sample_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/sm83_sample")
+}
+
+fn load_cases(path: &Path) -> Vec<Value> {
+    serde_json::from_str(&fs::read_to_string(path).unwrap())
+        .unwrap_or_else(|e| panic!("{}: {}", path.display(), e))
+}
+
+/// Run every .json file in dir and fail with the first failing case
+/// of each opcode.
+fn run_vectors(dir: &Path) {
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+    files.sort();
+
+    // Opcode name -> (passed, failed, first failure).
+    let mut results: BTreeMap<String, (usize, usize, Option<String>)> = BTreeMap::new();
+    for path in &files {
+        let opcode = path.file_stem().unwrap().to_string_lossy().to_string();
+        let cases = load_cases(path);
+
+        let entry = results.entry(opcode).or_default();
+        for case in &cases {
+            match run_case(case) {
+                Ok(()) => entry.0 += 1,
+                Err(err) => {
+                    entry.1 += 1;
+                    entry
+                        .2
+                        .get_or_insert_with(|| format!("{}: {}", case["name"], err));
+                }
+            }
+        }
+    }
+
+    let mut failed = Vec::new();
+    println!("{:<8} {:>6} {:>6}", "opcode", "pass", "fail");
+    for (opcode, (pass, fail, first)) in &results {
+        println!("{:<8} {:>6} {:>6}", opcode, pass, fail);
+        if let Some(first) = first {
+            failed.push(first.clone());
+        }
+    }
+
+    assert!(!results.is_empty(), "no vectors in {}", dir.display());
+    assert!(
+        failed.is_empty(),
+        "{} of {} opcodes failed:\n{}",
+        failed.len(),
+        results.len(),
+        failed.join("\n")
+    );
+}
+
+#[test]
+#[ignore = "needs the sm83 vectors in SM83_TESTS or tests/sm83/"]
+fn sm83_single_step() {
+    let dir = vector_dir().expect("sm83 vectors not found, set SM83_TESTS or fill tests/sm83/");
+    run_vectors(&dir);
+}
+
+#[test]
+fn sm83_sample() {
+    run_vectors(&sample_dir());
+}
+
+#[test]
+fn mismatches_are_reported() {
+    // LD (HL),A with the wrong A and the wrong byte written.
+    let mut case = load_cases(&sample_dir().join("sample.json")).swap_remove(2);
+    case["final"]["a"] = 0x41.into();
+    case["cycles"][0][1] = 0x43.into();
+    let err = run_case(&case).unwrap_err();
+    assert!(err.contains("A 0042 != 0041"), "{}", err);
+    assert!(err.contains("bus"), "{}", err);
+}